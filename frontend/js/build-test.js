@@ -45,11 +45,14 @@ async function compileCode() {
     const reader = response.body.getReader();
     const decoder = new TextDecoder('utf-8');
     let pending = '';
-    let wasmBase64 = '';
-    let inWasm = false;
+    const payloads = { wasm: '', lock: '' };
+    const markers = [
+      { key: 'wasm', start: WASM_BASE64_START, end: WASM_BASE64_END },
+      { key: 'lock', start: CARGO_LOCK_BASE64_START, end: CARGO_LOCK_BASE64_END },
+    ];
+    let active = null;
 
-    const startHold = Math.max(WASM_BASE64_START.length - 1, 0);
-    const endHold = Math.max(WASM_BASE64_END.length - 1, 0);
+    const startHold = Math.max(...markers.map((marker) => marker.start.length)) - 1;
 
     while (true) {
       const { value, done } = await reader.read();
@@ -57,9 +60,16 @@ async function compileCode() {
       pending += decoder.decode(value, { stream: true });
 
       while (true) {
-        if (!inWasm) {
-          const idx = pending.indexOf(WASM_BASE64_START);
-          if (idx === -1) {
+        if (!active) {
+          let idx = -1;
+          for (const marker of markers) {
+            const markerIdx = pending.indexOf(marker.start);
+            if (markerIdx !== -1 && (idx === -1 || markerIdx < idx)) {
+              idx = markerIdx;
+              active = marker;
+            }
+          }
+          if (!active) {
             if (pending.length > startHold) {
               appendConsoleText(consoleEl, pending.slice(0, -startHold));
               pending = pending.slice(-startHold);
@@ -67,33 +77,39 @@ async function compileCode() {
             break;
           }
           appendConsoleText(consoleEl, pending.slice(0, idx));
-          pending = pending.slice(idx + WASM_BASE64_START.length);
-          inWasm = true;
+          pending = pending.slice(idx + active.start.length);
         } else {
-          const idx = pending.indexOf(WASM_BASE64_END);
+          const endHold = active.end.length - 1;
+          const idx = pending.indexOf(active.end);
           if (idx === -1) {
             if (pending.length > endHold) {
-              wasmBase64 += pending.slice(0, -endHold);
+              payloads[active.key] += pending.slice(0, -endHold);
               pending = pending.slice(-endHold);
             }
             break;
           }
-          wasmBase64 += pending.slice(0, idx);
-          pending = pending.slice(idx + WASM_BASE64_END.length);
-          inWasm = false;
+          payloads[active.key] += pending.slice(0, idx);
+          pending = pending.slice(idx + active.end.length);
+          active = null;
         }
       }
     }
 
     pending += decoder.decode();
     if (pending) {
-      if (inWasm) {
-        wasmBase64 += pending;
+      if (active) {
+        payloads[active.key] += pending;
       } else {
         appendConsoleText(consoleEl, pending);
       }
     }
 
+    if (payloads.lock.trim()) {
+      const lockBytes = Uint8Array.from(atob(payloads.lock.replace(/\s+/g, '')), (c) => c.charCodeAt(0));
+      storeResolvedLockfile(new TextDecoder('utf-8').decode(lockBytes));
+    }
+
+    const wasmBase64 = payloads.wasm;
     if (wasmBase64.trim()) {
       const cleaned = wasmBase64.replace(/\s+/g, '');
       const binary = atob(cleaned);
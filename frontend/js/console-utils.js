@@ -12,6 +12,8 @@ function extractContractName(code) {
 
 const WASM_BASE64_START = '<<<SOROBAN_WASM_BASE64_START>>>';
 const WASM_BASE64_END = '<<<SOROBAN_WASM_BASE64_END>>>';
+const CARGO_LOCK_BASE64_START = '<<<SOROBAN_CARGO_LOCK_BASE64_START>>>';
+const CARGO_LOCK_BASE64_END = '<<<SOROBAN_CARGO_LOCK_BASE64_END>>>';
 
 const ANSI_BASE_COLORS = Object.freeze([
   '#000000', '#aa0000', '#00aa00', '#aa5500', '#0000aa', '#aa00aa', '#00aaaa', '#aaaaaa'
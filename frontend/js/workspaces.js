@@ -1213,6 +1213,18 @@ function createNewFile(fileName, content = '') {
   refreshWorkspaceEditor({ preferredFile: normalizedPath });
 }
 
+// Keeps the Cargo.lock a build resolved, so locked builds can reuse it.
+function storeResolvedLockfile(content) {
+  const workspace = getActiveWorkspace();
+  if (!workspace || files['Cargo.lock'] === content) return;
+
+  files['Cargo.lock'] = content;
+  workspace.files = files;
+  workspace.updatedAt = Date.now();
+  persistWorkspaceState();
+  renderWorkspaceManager();
+}
+
 function renameActiveWorkspace(name) {
   const workspace = getActiveWorkspace();
   if (!workspace) return;
@@ -36,11 +36,67 @@ export interface MutationResponse {
   seq: number;
 }
 
+export interface CommandOptions {
+  filter?: string;
+  exact?: boolean;
+  ignored?: boolean;
+  rawLog?: boolean;
+  optimize?: boolean;
+  language?: "typescript" | "rust" | "python";
+  locked?: boolean;
+}
+
+export interface ScoutFinding {
+  detector: string;
+  severity: string;
+  message: string;
+  file: string | null;
+  startLine: number | null;
+  endLine: number | null;
+  startColumn?: number;
+  endColumn?: number;
+  remediation?: string;
+}
+
+export interface ScoutSuppression {
+  detector: string;
+  file: string | null;
+  line: number | null;
+  source: "inline" | "baseline";
+  reason?: string;
+}
+
+export interface AuditReport {
+  new: ScoutFinding[];
+  suppressed: ScoutFinding[];
+  fixed: ScoutSuppression[];
+}
+
+export interface BuildArtifact {
+  sha256: string;
+  filename: string;
+  size: number;
+  buildHash: string;
+  logSize: number;
+  createdAt: number;
+  buildDurationMs: number;
+  url: string;
+}
+
 export interface CommandResponse {
   ok: boolean;
   command: string;
   output: string;
   revision: number;
+  diagnostics?: Record<string, unknown>[];
+  tests?: Record<string, unknown>[];
+  findings?: AuditReport;
+  artifact?: BuildArtifact;
+  optimizedArtifact?: BuildArtifact;
+  jobId?: string;
+  files?: Record<string, string>;
+  contracts?: BuildArtifact[];
+  cargoLock?: string;
 }
 
 type FetchLike = typeof fetch;
@@ -102,11 +158,20 @@ export class IdeClient {
     });
   }
 
-  async runCommand(projectId: string, command: string): Promise<CommandResponse> {
-    return this.request<CommandResponse>(`/api/mcp/v1/projects/${encodeURIComponent(projectId)}/commands`, {
-      method: "POST",
-      body: JSON.stringify({ command }),
-    });
+  async runCommand(projectId: string, command: string, options: CommandOptions = {}): Promise<CommandResponse> {
+    const response = await this.request<CommandResponse>(
+      `/api/mcp/v1/projects/${encodeURIComponent(projectId)}/commands`,
+      {
+        method: "POST",
+        body: JSON.stringify({ command, ...options }),
+      },
+    );
+    for (const artifact of [response.artifact, response.optimizedArtifact, ...(response.contracts ?? [])]) {
+      if (artifact) {
+        artifact.url = new URL(artifact.url, `${this.apiUrl}/`).toString();
+      }
+    }
+    return response;
   }
 
   private async request<T>(path: string, init: RequestInit = {}): Promise<T> {
@@ -193,12 +193,26 @@ server.tool(
 
 server.tool(
   "soropg_run_command",
-  "Run a whitelisted SoroPG backend command. Allowed commands: build, test, audit. Deploy returns unsupported in v1.",
+  "Run a whitelisted SoroPG backend command. Allowed commands: build, test, audit, bindings. Deploy returns unsupported in v1.",
   {
     ...projectIdSchema.shape,
-    command: z.enum(["build", "test", "audit", "deploy"]),
+    command: z.enum(["build", "test", "audit", "bindings", "deploy"]),
+    filter: z.string().optional().describe("Test command only: run tests whose path contains this string."),
+    exact: z.boolean().optional().describe("Test command only: match the filter exactly."),
+    ignored: z.boolean().optional().describe("Test command only: run only #[ignore] tests."),
+    rawLog: z.boolean().optional().describe("Audit command only: return the full Scout log instead of the findings summary."),
+    optimize: z.boolean().optional().describe("Build command only: also produce an optimized WASM and report the size saving."),
+    language: z
+      .enum(["typescript", "rust", "python"])
+      .optional()
+      .describe("Bindings command only: client language to generate. Returned files can be written into the project with soropg_create_file."),
+    locked: z
+      .boolean()
+      .optional()
+      .describe("Build against the project's Cargo.lock with --locked. Responses carry the resolved lockfile as cargoLock."),
   },
-  async ({ projectId, command }) => runTool(() => client.runCommand(resolveProjectId(projectId), command)),
+  async ({ projectId, command, ...options }) =>
+    runTool(() => client.runCommand(resolveProjectId(projectId), command, options)),
 );
 
 try {
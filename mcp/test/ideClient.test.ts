@@ -44,4 +44,22 @@ describe("IdeClient", () => {
 
     await expect(client.listProjects()).rejects.toThrow("upstream unavailable");
   });
+
+  it("resolves build artifact download urls", async () => {
+    const client = new IdeClient(
+      { apiUrl: "https://soropg.com/", apiKey: "x".repeat(40) },
+      async () =>
+        jsonResponse({
+          ok: true,
+          command: "build",
+          output: "",
+          revision: 1,
+          artifact: { sha256: "ab".repeat(32), filename: "hello.wasm", url: `/artifacts/${"ab".repeat(32)}.wasm` },
+        }),
+    );
+
+    const response = await client.runCommand("workspace-1", "build");
+
+    expect(response.artifact?.url).toBe(`https://soropg.com/artifacts/${"ab".repeat(32)}.wasm`);
+  });
 });
@@ -0,0 +1,421 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+const DEFAULT_ARTIFACT_DIR: &str = "artifact-cache";
+const DEFAULT_ARTIFACT_CACHE_MB: u64 = 1024;
+
+/// Persistent build artifact store shared by every route.
+pub static ARTIFACTS: Lazy<ArtifactStore> = Lazy::new(|| {
+    let root = env::var("SOROPG_ARTIFACT_DIR")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_ARTIFACT_DIR.to_string());
+    let max_mb = env::var("SOROPG_ARTIFACT_CACHE_MB")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_ARTIFACT_CACHE_MB);
+    ArtifactStore::open(root, max_mb.saturating_mul(1024 * 1024))
+});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArtifactRecord {
+    pub build_hash: String,
+    pub output_filename: String,
+    pub wasm_sha256: String,
+    pub wasm_size: u64,
+    pub log_size: u64,
+    pub created_at: u64,
+    pub last_used_at: u64,
+    #[serde(default)]
+    pub build_duration_ms: u64,
+    #[serde(default)]
+    pub lock_size: u64,
+}
+
+impl ArtifactRecord {
+    fn stored_bytes(&self) -> u64 {
+        self.wasm_size
+            .saturating_add(self.log_size)
+            .saturating_add(self.lock_size)
+    }
+}
+
+/// Public description of a stored build, served next to the WASM download.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactMetadata {
+    pub sha256: String,
+    pub filename: String,
+    pub size: u64,
+    pub build_hash: String,
+    pub log_size: u64,
+    pub created_at: u64,
+    pub build_duration_ms: u64,
+    pub url: String,
+}
+
+impl From<&ArtifactRecord> for ArtifactMetadata {
+    fn from(record: &ArtifactRecord) -> Self {
+        ArtifactMetadata {
+            sha256: record.wasm_sha256.clone(),
+            filename: record.output_filename.clone(),
+            size: record.wasm_size,
+            build_hash: record.build_hash.clone(),
+            log_size: record.log_size,
+            created_at: record.created_at,
+            build_duration_ms: record.build_duration_ms,
+            url: artifact_url(&record.wasm_sha256),
+        }
+    }
+}
+
+/// Cache key of the optimized WASM built from the sources `build_hash` names.
+pub fn optimized_build_hash(build_hash: &str) -> String {
+    hex::encode(Sha256::digest(
+        format!("optimized:{}", build_hash).as_bytes(),
+    ))
+}
+
+/// Cache key of one contract of the Cargo workspace `build_hash` names.
+pub fn contract_build_hash(build_hash: &str, package: &str) -> String {
+    hex::encode(Sha256::digest(
+        format!("contract:{}:{}", package, build_hash).as_bytes(),
+    ))
+}
+
+/// Cache key of a `--locked` build of the sources `build_hash` names, which
+/// may resolve differently from an unlocked build of the same files.
+pub fn locked_build_hash(build_hash: &str) -> String {
+    hex::encode(Sha256::digest(format!("locked:{}", build_hash).as_bytes()))
+}
+
+/// Download path of a stored WASM, by the SHA-256 of its bytes.
+pub fn artifact_url(wasm_sha256: &str) -> String {
+    format!("/artifacts/{}.wasm", wasm_sha256)
+}
+
+pub struct CachedBuild {
+    pub record: ArtifactRecord,
+    pub wasm: Vec<u8>,
+    pub log: Vec<u8>,
+    /// The `Cargo.lock` the build resolved, when one was stored.
+    pub lockfile: Option<String>,
+}
+
+/// Content-addressed WASM cache keyed by the SHA-256 of the submitted files.
+///
+/// Each entry is stored as `{hash}.wasm`, `{hash}.log`, an optional
+/// `{hash}.lock`, and `{hash}.json` so the
+/// index can be rebuilt from disk after a restart. Entries are evicted least
+/// recently used first once the stored bytes exceed `max_bytes`.
+pub struct ArtifactStore {
+    root: PathBuf,
+    max_bytes: u64,
+    index: Mutex<HashMap<String, ArtifactRecord>>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let tmp_path = path.with_extension("partial");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to store {}: {}", path.display(), e))
+}
+
+impl ArtifactStore {
+    pub fn open(root: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        let root = root.into();
+        let mut index = HashMap::new();
+
+        if let Err(e) = fs::create_dir_all(&root) {
+            warn!(dir=%root.display(), error=%e, "failed to create artifact cache directory");
+        }
+        if let Ok(entries) = fs::read_dir(&root) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let record = fs::read(&path)
+                    .ok()
+                    .and_then(|raw| serde_json::from_slice::<ArtifactRecord>(&raw).ok());
+                match record {
+                    Some(record)
+                        if is_valid_hash(&record.build_hash)
+                            && root.join(format!("{}.wasm", record.build_hash)).is_file() =>
+                    {
+                        index.insert(record.build_hash.clone(), record);
+                    }
+                    _ => {
+                        warn!(path=%path.display(), "discarding unreadable artifact metadata");
+                        let _ = fs::remove_file(&path);
+                    }
+                }
+            }
+        }
+
+        let store = ArtifactStore {
+            root,
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        if let Ok(mut index) = store.index.lock() {
+            store.evict(&mut index, None);
+        }
+        store
+    }
+
+    fn entry_path(&self, build_hash: &str, extension: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", build_hash, extension))
+    }
+
+    pub fn get(&self, build_hash: &str) -> Option<CachedBuild> {
+        if !is_valid_hash(build_hash) {
+            return None;
+        }
+        let mut index = self.index.lock().ok()?;
+        let record = index.get_mut(build_hash)?;
+
+        let wasm = fs::read(self.entry_path(build_hash, "wasm")).ok();
+        let log = fs::read(self.entry_path(build_hash, "log")).unwrap_or_default();
+        let lockfile = fs::read_to_string(self.entry_path(build_hash, "lock")).ok();
+        let Some(wasm) = wasm else {
+            index.remove(build_hash);
+            self.remove_files(build_hash);
+            return None;
+        };
+
+        record.last_used_at = now_secs();
+        let record = record.clone();
+        if let Ok(raw) = serde_json::to_vec(&record) {
+            let _ = write_atomic(&self.entry_path(build_hash, "json"), &raw);
+        }
+
+        Some(CachedBuild {
+            record,
+            wasm,
+            log,
+            lockfile,
+        })
+    }
+
+    /// Looks a build up by the SHA-256 of its WASM instead of its sources.
+    pub fn find_wasm(&self, wasm_sha256: &str) -> Option<CachedBuild> {
+        if !is_valid_hash(wasm_sha256) {
+            return None;
+        }
+        let build_hash = {
+            let index = self.index.lock().ok()?;
+            index
+                .values()
+                .find(|record| record.wasm_sha256.eq_ignore_ascii_case(wasm_sha256))?
+                .build_hash
+                .clone()
+        };
+        self.get(&build_hash)
+    }
+
+    pub fn put(
+        &self,
+        build_hash: &str,
+        output_filename: &str,
+        wasm: &[u8],
+        log: &[u8],
+        build_duration: Duration,
+    ) -> Result<ArtifactRecord, String> {
+        if !is_valid_hash(build_hash) {
+            return Err(format!("Invalid build hash: {}", build_hash));
+        }
+
+        let now = now_secs();
+        let record = ArtifactRecord {
+            build_hash: build_hash.to_string(),
+            output_filename: output_filename.to_string(),
+            wasm_sha256: hex::encode(Sha256::digest(wasm)),
+            wasm_size: wasm.len() as u64,
+            log_size: log.len() as u64,
+            created_at: now,
+            last_used_at: now,
+            build_duration_ms: build_duration.as_millis() as u64,
+            lock_size: 0,
+        };
+        if record.stored_bytes() > self.max_bytes {
+            return Err("Artifact exceeds the cache size limit".to_string());
+        }
+
+        let mut index = self
+            .index
+            .lock()
+            .map_err(|_| "Artifact cache lock failed".to_string())?;
+        write_atomic(&self.entry_path(build_hash, "wasm"), wasm)?;
+        write_atomic(&self.entry_path(build_hash, "log"), log)?;
+        // A lockfile stored with an earlier build of the key belongs to that
+        // build; the new one stores its own with `put_lockfile`.
+        let lock = self.entry_path(build_hash, "lock");
+        if lock.exists() {
+            fs::remove_file(&lock)
+                .map_err(|e| format!("Failed to remove {}: {}", lock.display(), e))?;
+        }
+        let raw = serde_json::to_vec(&record).map_err(|e| e.to_string())?;
+        write_atomic(&self.entry_path(build_hash, "json"), &raw)?;
+
+        index.insert(build_hash.to_string(), record.clone());
+        self.evict(&mut index, Some(build_hash));
+        Ok(record)
+    }
+
+    /// Stores the `Cargo.lock` resolved by an already stored build.
+    pub fn put_lockfile(&self, build_hash: &str, lockfile: &str) -> Result<(), String> {
+        let mut index = self
+            .index
+            .lock()
+            .map_err(|_| "Artifact cache lock failed".to_string())?;
+        let Some(record) = index.get_mut(build_hash) else {
+            return Err(format!("No stored build for {}", build_hash));
+        };
+        write_atomic(&self.entry_path(build_hash, "lock"), lockfile.as_bytes())?;
+        record.lock_size = lockfile.len() as u64;
+        let raw = serde_json::to_vec(&*record).map_err(|e| e.to_string())?;
+        write_atomic(&self.entry_path(build_hash, "json"), &raw)?;
+        self.evict(&mut index, Some(build_hash));
+        Ok(())
+    }
+
+    fn evict(&self, index: &mut HashMap<String, ArtifactRecord>, keep: Option<&str>) {
+        let mut total: u64 = index.values().map(ArtifactRecord::stored_bytes).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        let mut candidates: Vec<_> = index
+            .values()
+            .filter(|record| Some(record.build_hash.as_str()) != keep)
+            .map(|record| (record.last_used_at, record.build_hash.clone()))
+            .collect();
+        candidates.sort();
+
+        for (_, build_hash) in candidates {
+            if total <= self.max_bytes {
+                break;
+            }
+            if let Some(record) = index.remove(&build_hash) {
+                total = total.saturating_sub(record.stored_bytes());
+                self.remove_files(&build_hash);
+            }
+        }
+    }
+
+    fn remove_files(&self, build_hash: &str) {
+        for extension in ["json", "wasm", "log", "lock"] {
+            let _ = fs::remove_file(self.entry_path(build_hash, extension));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn hash_of(seed: &str) -> String {
+        hex::encode(Sha256::digest(seed.as_bytes()))
+    }
+
+    #[test]
+    fn stores_and_reloads_builds_across_restarts() {
+        let dir = TempDir::new().unwrap();
+        let hash = hash_of("hello");
+        {
+            let store = ArtifactStore::open(dir.path(), 1024);
+            store
+                .put(
+                    &hash,
+                    "hello-12345678.wasm",
+                    b"\0asm",
+                    b"build log",
+                    Duration::from_secs(42),
+                )
+                .unwrap();
+        }
+
+        let store = ArtifactStore::open(dir.path(), 1024);
+        let cached = store.get(&hash).expect("cached build");
+        assert_eq!(cached.wasm, b"\0asm");
+        assert_eq!(cached.log, b"build log");
+        assert_eq!(cached.record.output_filename, "hello-12345678.wasm");
+        assert_eq!(cached.record.build_duration_ms, 42_000);
+        assert!(cached.lockfile.is_none());
+
+        store.put_lockfile(&hash, "version = 4\n").unwrap();
+        assert_eq!(
+            store.get(&hash).unwrap().lockfile.as_deref(),
+            Some("version = 4\n")
+        );
+        assert!(store.put_lockfile(&hash_of("missing"), "").is_err());
+
+        // Rebuilding the key drops the earlier build's lockfile.
+        store
+            .put(&hash, "hello-12345678.wasm", b"\0asm", b"", Duration::ZERO)
+            .unwrap();
+        assert!(store.get(&hash).unwrap().lockfile.is_none());
+        assert!(!dir.path().join(format!("{}.lock", hash)).exists());
+        store.put_lockfile(&hash, "version = 4\n").unwrap();
+
+        let wasm_sha256 = hex::encode(Sha256::digest(b"\0asm"));
+        let found = store.find_wasm(&wasm_sha256).expect("found by wasm hash");
+        assert_eq!(found.record.build_hash, hash);
+        assert_eq!(
+            ArtifactMetadata::from(&found.record).url,
+            format!("/artifacts/{}.wasm", wasm_sha256)
+        );
+        assert!(store.find_wasm(&hash).is_none());
+        assert!(store.get(&hash_of("missing")).is_none());
+        assert!(store.get("../escape").is_none());
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let dir = TempDir::new().unwrap();
+        let store = ArtifactStore::open(dir.path(), 20);
+        let (a, b, c) = (hash_of("a"), hash_of("b"), hash_of("c"));
+
+        store
+            .put(&a, "a.wasm", &[0; 8], b"", Duration::ZERO)
+            .unwrap();
+        store
+            .put(&b, "b.wasm", &[0; 8], b"", Duration::ZERO)
+            .unwrap();
+        {
+            let mut index = store.index.lock().unwrap();
+            index.get_mut(&a).unwrap().last_used_at = 0;
+        }
+        store
+            .put(&c, "c.wasm", &[0; 8], b"", Duration::ZERO)
+            .unwrap();
+
+        assert!(store.get(&a).is_none());
+        assert!(store.get(&b).is_some());
+        assert!(store.get(&c).is_some());
+        assert!(!dir.path().join(format!("{}.wasm", a)).exists());
+    }
+}
@@ -0,0 +1,453 @@
+use std::{env, fs, path::Path, time::SystemTime};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ed25519_dalek::{Signer, SigningKey};
+use once_cell::sync::{Lazy, OnceCell};
+use rand_core::OsRng;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use stellar_xdr::curr::{
+    ContractDataDurability, ContractExecutable, ContractId, Hash, LedgerEntryData, LedgerKey,
+    LedgerKeyContractCode, LedgerKeyContractData, Limits, ReadXdr, ScAddress, ScVal, WriteXdr,
+};
+use tracing::warn;
+
+use crate::{docker::run_in_docker_no_files, inspect::inspect_wasm, models::VerifyRequest};
+
+/// Rust toolchain of verification builds, installed in the sandbox image next
+/// to the floating nightly. Keep in sync with `Dockerfile.sandbox`.
+pub const VERIFY_TOOLCHAIN: &str = "1.89.0";
+const ATTESTATION_VERSION: u32 = 2;
+const NETWORKS: &[&str] = &["testnet", "mainnet", "public", "futurenet"];
+
+/// Key signing every attestation, read from `SOROPG_ATTESTATION_KEY` as a
+/// hex-encoded 32-byte ed25519 seed.
+pub static ATTESTATION_KEY: Lazy<SigningKey> = Lazy::new(|| {
+    let configured = env::var("SOROPG_ATTESTATION_KEY")
+        .ok()
+        .filter(|value| !value.trim().is_empty());
+    if let Some(value) = configured {
+        match hex::decode(value.trim())
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        {
+            Some(seed) => return SigningKey::from_bytes(&seed),
+            None => warn!("SOROPG_ATTESTATION_KEY is not a hex-encoded 32-byte seed"),
+        }
+    }
+    warn!("no attestation key configured, signing with an ephemeral key");
+    SigningKey::generate(&mut OsRng)
+});
+
+/// Tool versions of verification builds, probed once in a sandbox that runs
+/// no submitted code.
+static TOOLCHAIN: OnceCell<Toolchain> = OnceCell::new();
+
+/// What a verification build is compared against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyTarget {
+    /// SHA-256 of the deployed WASM.
+    Sha256(String),
+    /// A deployed contract whose WASM is fetched from the network.
+    Contract {
+        contract_id: String,
+        network: String,
+    },
+}
+
+impl VerifyTarget {
+    pub fn from_request(req: &VerifyRequest) -> Result<Self, String> {
+        if let Some(sha256) = &req.sha256 {
+            let sha256 = sha256.trim().to_ascii_lowercase();
+            if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err("verify.sha256 must be a hex-encoded SHA-256".to_string());
+            }
+            return Ok(VerifyTarget::Sha256(sha256));
+        }
+        let Some(contract_id) = req.contract_id.as_deref().map(str::trim) else {
+            return Err("verify needs a sha256 or a contractId".to_string());
+        };
+        // Strkey contract ids are 56 base32 characters starting with `C`.
+        if contract_id.len() != 56
+            || !contract_id.starts_with('C')
+            || !contract_id
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || ('2'..='7').contains(&c))
+        {
+            return Err("verify.contractId is not a contract address".to_string());
+        }
+        let network = req
+            .network
+            .as_deref()
+            .map(|network| network.trim().to_ascii_lowercase())
+            .unwrap_or_else(|| "testnet".to_string());
+        if !NETWORKS.contains(&network.as_str()) {
+            return Err(format!(
+                "Unsupported network: {}. Use {}",
+                network,
+                NETWORKS.join(", ")
+            ));
+        }
+        Ok(VerifyTarget::Contract {
+            contract_id: contract_id.to_string(),
+            network,
+        })
+    }
+
+    /// SHA-256 of the deployed WASM, fetched by the server itself for a
+    /// contract target.
+    async fn expected_sha256(&self) -> Result<String, String> {
+        match self {
+            VerifyTarget::Sha256(sha256) => Ok(sha256.clone()),
+            VerifyTarget::Contract {
+                contract_id,
+                network,
+            } => {
+                let wasm = fetch_contract_wasm(rpc_url(network), contract_id).await?;
+                Ok(hex::encode(Sha256::digest(wasm)))
+            }
+        }
+    }
+}
+
+fn rpc_url(network: &str) -> &'static str {
+    match network {
+        "mainnet" | "public" => "https://mainnet.sorobanrpc.com",
+        "futurenet" => "https://rpc-futurenet.stellar.org",
+        _ => "https://soroban-testnet.stellar.org",
+    }
+}
+
+/// Reads one ledger entry through the `getLedgerEntries` RPC method.
+async fn ledger_entry(rpc: &str, key: &LedgerKey) -> Result<LedgerEntryData, String> {
+    let key = key.to_xdr(Limits::none()).map_err(|e| e.to_string())?;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getLedgerEntries",
+        "params": { "keys": [BASE64.encode(key)] },
+    });
+    let response = reqwest::Client::new()
+        .post(rpc)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(request.to_string())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", rpc, e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the response of {}: {}", rpc, e))?;
+    let response: Value = serde_json::from_str(&response)
+        .map_err(|e| format!("Invalid response from {}: {}", rpc, e))?;
+    if let Some(error) = response.get("error") {
+        return Err(format!("{} returned an error: {}", rpc, error));
+    }
+    let entry = response["result"]["entries"][0]["xdr"]
+        .as_str()
+        .ok_or_else(|| "Ledger entry not found".to_string())?;
+    let entry = BASE64
+        .decode(entry)
+        .map_err(|e| format!("Invalid ledger entry: {}", e))?;
+    LedgerEntryData::from_xdr(entry, Limits::none())
+        .map_err(|e| format!("Invalid ledger entry: {}", e))
+}
+
+/// Hash of the WASM a contract instance entry runs.
+fn executable_hash(instance: &LedgerEntryData) -> Result<Hash, String> {
+    match instance {
+        LedgerEntryData::ContractData(data) => match &data.val {
+            ScVal::ContractInstance(instance) => match &instance.executable {
+                ContractExecutable::Wasm(hash) => Ok(hash.clone()),
+                ContractExecutable::StellarAsset => {
+                    Err("The contract is a Stellar asset contract, not a WASM".to_string())
+                }
+            },
+            _ => Err("Ledger entry is not a contract instance".to_string()),
+        },
+        _ => Err("Ledger entry is not contract data".to_string()),
+    }
+}
+
+/// WASM bytes of a contract code entry, checked against the hash they were
+/// looked up by.
+fn contract_code(code: LedgerEntryData, hash: &Hash) -> Result<Vec<u8>, String> {
+    let LedgerEntryData::ContractCode(code) = code else {
+        return Err("Ledger entry is not contract code".to_string());
+    };
+    let wasm = code.code.to_vec();
+    if Sha256::digest(&wasm).as_slice() != hash.0 {
+        return Err("Contract code does not match its hash".to_string());
+    }
+    Ok(wasm)
+}
+
+/// Fetches a deployed contract's WASM on the host, outside the sandbox.
+async fn fetch_contract_wasm(rpc: &str, contract_id: &str) -> Result<Vec<u8>, String> {
+    let contract = contract_id
+        .parse::<ContractId>()
+        .map_err(|e| format!("Invalid contract id: {}", e))?;
+    let instance = LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::Contract(contract),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    });
+    let hash = executable_hash(&ledger_entry(rpc, &instance).await?)?;
+    let code = LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() });
+    contract_code(ledger_entry(rpc, &code).await?, &hash)
+}
+
+/// Probes the verification toolchain in a fresh sandbox that runs no
+/// submitted code, so a build cannot choose what the attestation names.
+async fn toolchain() -> Result<Toolchain, String> {
+    if let Some(toolchain) = TOOLCHAIN.get() {
+        return Ok(toolchain.clone());
+    }
+    let (output, _workspace) = run_in_docker_no_files(&format!(
+        "RUSTUP_TOOLCHAIN={} rustc --version; stellar --version | head -n 1",
+        VERIFY_TOOLCHAIN
+    ))
+    .await
+    .map_err(|e| format!("Failed to probe the verification toolchain: {}", e))?;
+    let output = String::from_utf8_lossy(&output);
+    let version = |tool: &str| {
+        output
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with(tool))
+            .map(str::to_string)
+            .ok_or_else(|| format!("Failed to probe the {} version", tool.trim()))
+    };
+    let toolchain = Toolchain {
+        pinned: VERIFY_TOOLCHAIN.to_string(),
+        rustc: version("rustc ")?,
+        stellar_cli: version("stellar ")?,
+    };
+    Ok(TOOLCHAIN.get_or_init(|| toolchain).clone())
+}
+
+/// Evidence a verification build is attested against, gathered by the
+/// server rather than read from the job workspace the build could write.
+pub struct HostEvidence {
+    pub expected_sha256: String,
+    pub toolchain: Toolchain,
+}
+
+impl HostEvidence {
+    pub async fn gather(target: &VerifyTarget) -> Result<Self, String> {
+        Ok(HostEvidence {
+            expected_sha256: target.expected_sha256().await?,
+            toolchain: toolchain().await?,
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Toolchain {
+    pub pinned: String,
+    pub rustc: String,
+    pub stellar_cli: String,
+}
+
+/// Statement that a source tree rebuilt to a given WASM.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Attestation {
+    pub version: u32,
+    pub source_sha256: String,
+    pub output_sha256: String,
+    pub expected_sha256: String,
+    pub matches: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    pub toolchain: Toolchain,
+    pub sdk_version: Option<String>,
+    pub cargo_lock_sha256: Option<String>,
+    pub created_at: u64,
+}
+
+/// An attestation with its ed25519 signature. `payload` is the exact JSON the
+/// signature covers; `attestation` is the same document for convenience.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedAttestation {
+    pub attestation: Attestation,
+    pub payload: String,
+    pub algorithm: &'static str,
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Compares a verification build with its target and signs the result.
+/// `workspace` is the sandbox directory the build ran in, `wasm` the artifact
+/// it produced and `source_sha256` the [`files_hash`] of the submitted files.
+///
+/// [`files_hash`]: crate::models::files_hash
+pub fn attest(
+    workspace: &Path,
+    wasm: &[u8],
+    source_sha256: &str,
+    target: &VerifyTarget,
+    evidence: HostEvidence,
+) -> Result<SignedAttestation, String> {
+    let (contract_id, network) = match target {
+        VerifyTarget::Sha256(_) => (None, None),
+        VerifyTarget::Contract {
+            contract_id,
+            network,
+        } => (Some(contract_id.clone()), Some(network.clone())),
+    };
+    let HostEvidence {
+        expected_sha256,
+        toolchain,
+    } = evidence;
+
+    let output_sha256 = hex::encode(Sha256::digest(wasm));
+    let sdk_version = inspect_wasm(wasm)
+        .ok()
+        .and_then(|inspection| inspection.meta.get("rssdkver").cloned());
+    let cargo_lock_sha256 = fs::read(workspace.join("project").join("Cargo.lock"))
+        .ok()
+        .map(|lock| hex::encode(Sha256::digest(&lock)));
+    let created_at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let attestation = Attestation {
+        version: ATTESTATION_VERSION,
+        source_sha256: source_sha256.to_string(),
+        matches: output_sha256 == expected_sha256,
+        output_sha256,
+        expected_sha256,
+        contract_id,
+        network,
+        toolchain,
+        sdk_version,
+        cargo_lock_sha256,
+        created_at,
+    };
+    let payload = serde_json::to_string(&attestation).map_err(|e| e.to_string())?;
+    let signature = ATTESTATION_KEY.sign(payload.as_bytes());
+    Ok(SignedAttestation {
+        attestation,
+        payload,
+        algorithm: "ed25519",
+        public_key: hex::encode(ATTESTATION_KEY.verifying_key().as_bytes()),
+        signature: hex::encode(signature.to_bytes()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+    use stellar_xdr::curr::{
+        ContractCodeEntry, ContractCodeEntryExt, ContractDataEntry, ExtensionPoint,
+        ScContractInstance,
+    };
+    use tempfile::TempDir;
+
+    const WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    fn workspace() -> TempDir {
+        let workspace = TempDir::new().unwrap();
+        fs::create_dir(workspace.path().join("project")).unwrap();
+        fs::write(workspace.path().join("project/Cargo.lock"), "version = 4\n").unwrap();
+        workspace
+    }
+
+    fn evidence(deployed: &[u8]) -> HostEvidence {
+        HostEvidence {
+            expected_sha256: hex::encode(Sha256::digest(deployed)),
+            toolchain: Toolchain {
+                pinned: VERIFY_TOOLCHAIN.to_string(),
+                rustc: "rustc 1.89.0 (29483883e 2025-08-04)".to_string(),
+                stellar_cli: "stellar 23.1.4".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn signs_matching_attestation() {
+        let workspace = workspace();
+        let target = VerifyTarget::Sha256(hex::encode(Sha256::digest(WASM)));
+        let signed = attest(workspace.path(), WASM, "source", &target, evidence(WASM)).unwrap();
+
+        assert!(signed.attestation.matches);
+        assert_eq!(signed.attestation.toolchain.stellar_cli, "stellar 23.1.4");
+        assert!(signed.attestation.cargo_lock_sha256.is_some());
+
+        let key: [u8; 32] = hex::decode(&signed.public_key).unwrap().try_into().unwrap();
+        let signature: [u8; 64] = hex::decode(&signed.signature).unwrap().try_into().unwrap();
+        VerifyingKey::from_bytes(&key)
+            .unwrap()
+            .verify(
+                signed.payload.as_bytes(),
+                &Signature::from_bytes(&signature),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn reports_mismatch_against_deployed_wasm() {
+        let workspace = workspace();
+        let target = VerifyTarget::Contract {
+            contract_id: "C".repeat(56),
+            network: "testnet".to_string(),
+        };
+        let deployed = evidence(b"\0asm\x01\0\0\0\0");
+        let signed = attest(workspace.path(), WASM, "source", &target, deployed).unwrap();
+        assert!(!signed.attestation.matches);
+        assert_eq!(signed.attestation.network.as_deref(), Some("testnet"));
+    }
+
+    #[test]
+    fn reads_deployed_code_by_its_executable_hash() {
+        let hash = Hash(Sha256::digest(WASM).into());
+        let instance = LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(hash.clone()),
+                storage: None,
+            }),
+        });
+        assert_eq!(executable_hash(&instance).unwrap(), hash);
+
+        let code = |wasm: &[u8]| {
+            LedgerEntryData::ContractCode(ContractCodeEntry {
+                ext: ContractCodeEntryExt::V0,
+                hash: hash.clone(),
+                code: wasm.to_vec().try_into().unwrap(),
+            })
+        };
+        assert_eq!(contract_code(code(WASM), &hash).unwrap(), WASM);
+        assert!(contract_code(code(b"forged"), &hash).is_err());
+    }
+
+    #[test]
+    fn validates_targets() {
+        let request = |sha256: Option<&str>, contract_id: Option<&str>| VerifyRequest {
+            sha256: sha256.map(str::to_string),
+            contract_id: contract_id.map(str::to_string),
+            network: None,
+        };
+        assert!(VerifyTarget::from_request(&request(Some("abc"), None)).is_err());
+        assert!(VerifyTarget::from_request(&request(None, None)).is_err());
+        assert!(VerifyTarget::from_request(&request(None, Some("C; rm -rf /"))).is_err());
+        let target = VerifyTarget::from_request(&request(None, Some(&"C".repeat(56)))).unwrap();
+        assert_eq!(
+            target,
+            VerifyTarget::Contract {
+                contract_id: "C".repeat(56),
+                network: "testnet".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Cursor, Write},
+    path::Path,
+};
+
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+/// Sandbox directory, relative to `/workspace`, the bindings are written to.
+pub const BINDINGS_DIR: &str = "bindings";
+const MAX_BINDING_FILES: usize = 500;
+const MAX_BINDINGS_BYTES: usize = 8 * 1024 * 1024;
+
+/// Client language of `stellar contract bindings`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingsLanguage {
+    Typescript,
+    Rust,
+    Python,
+}
+
+impl BindingsLanguage {
+    /// Parses a requested language. TypeScript is the default.
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value
+            .map(|value| value.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("typescript") | Some("ts") => Ok(BindingsLanguage::Typescript),
+            Some("rust") => Ok(BindingsLanguage::Rust),
+            Some("python") => Ok(BindingsLanguage::Python),
+            Some(other) => Err(format!(
+                "Unsupported bindings language: {}. Use typescript, rust, or python",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BindingsLanguage::Typescript => "typescript",
+            BindingsLanguage::Rust => "rust",
+            BindingsLanguage::Python => "python",
+        }
+    }
+
+    /// Sandbox step generating bindings for the WASM at `$wasm_path`.
+    pub fn command(self) -> String {
+        let out_dir = format!("/workspace/{}", BINDINGS_DIR);
+        match self {
+            // The Rust generator prints a single module instead of a package.
+            BindingsLanguage::Rust => format!(
+                "mkdir -p {out} && stellar contract bindings rust --wasm \"$wasm_path\" > {out}/lib.rs",
+                out = out_dir
+            ),
+            language => format!(
+                "stellar contract bindings {} --wasm \"$wasm_path\" --output-dir {} --overwrite",
+                language.as_str(),
+                out_dir
+            ),
+        }
+    }
+}
+
+/// Reads the generated bindings from a sandbox workspace as a file map whose
+/// paths start with `bindings/{language}/`, ready to merge into the project.
+/// Symlinks are skipped so the sandbox cannot point the server at host files.
+pub fn read_bindings(
+    workspace: &Path,
+    language: BindingsLanguage,
+) -> Result<BTreeMap<String, String>, String> {
+    let root = workspace.join(BINDINGS_DIR);
+    let prefix = format!("{}/{}", BINDINGS_DIR, language.as_str());
+    let mut files = BTreeMap::new();
+    let mut total = 0;
+    let mut pending = vec![(root.clone(), prefix)];
+
+    while let Some((dir, relative)) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Bindings were not generated: {}", e))?;
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = format!("{}/{}", relative, name);
+            if file_type.is_dir() {
+                if name != "node_modules" {
+                    pending.push((entry.path(), path));
+                }
+            } else if file_type.is_file() {
+                let content = fs::read(entry.path()).map_err(|e| e.to_string())?;
+                total += content.len();
+                if files.len() >= MAX_BINDING_FILES || total > MAX_BINDINGS_BYTES {
+                    return Err("Generated bindings exceed the size limit".to_string());
+                }
+                let content = String::from_utf8(content)
+                    .map_err(|_| format!("Generated file is not UTF-8: {}", path))?;
+                files.insert(path, content);
+            }
+        }
+    }
+
+    if files.is_empty() {
+        return Err("Bindings were not generated".to_string());
+    }
+    Ok(files)
+}
+
+/// Packs a file map into a zip archive.
+pub fn zip_files(files: &BTreeMap<String, String>) -> Result<Vec<u8>, String> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, content) in files {
+        zip.start_file(path.as_str(), SimpleFileOptions::default())
+            .map_err(|e| e.to_string())?;
+        zip.write_all(content.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    let cursor = zip.finish().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn reads_generated_package_without_symlinks() {
+        let workspace = TempDir::new().unwrap();
+        let root = workspace.path().join(BINDINGS_DIR);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("node_modules/dep")).unwrap();
+        fs::write(root.join("package.json"), "{}").unwrap();
+        fs::write(root.join("src/index.ts"), "export {}").unwrap();
+        fs::write(root.join("node_modules/dep/index.js"), "").unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", root.join("leak")).unwrap();
+
+        let files = read_bindings(workspace.path(), BindingsLanguage::Typescript).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec![
+                "bindings/typescript/package.json",
+                "bindings/typescript/src/index.ts"
+            ]
+        );
+
+        let archive = zip_files(&files).unwrap();
+        assert!(archive.starts_with(b"PK"));
+    }
+
+    #[test]
+    fn parses_languages() {
+        assert_eq!(
+            BindingsLanguage::parse(None).unwrap(),
+            BindingsLanguage::Typescript
+        );
+        assert_eq!(
+            BindingsLanguage::parse(Some("Rust")).unwrap(),
+            BindingsLanguage::Rust
+        );
+        assert!(BindingsLanguage::parse(Some("cobol")).is_err());
+        assert!(BindingsLanguage::Rust
+            .command()
+            .ends_with("> /workspace/bindings/lib.rs"));
+    }
+}
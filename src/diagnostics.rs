@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+
+/// A compiler or sandbox diagnostic in a shape the editor and MCP clients can
+/// consume directly. Locations are 1-based, as reported by rustc.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<DiagnosticSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rendered: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticSpan {
+    pub byte_start: u32,
+    pub byte_end: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn message(severity: &str, message: &str) -> Self {
+        Diagnostic {
+            severity: severity.to_string(),
+            message: message.trim().to_string(),
+            ..Diagnostic::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<RustcDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    code: Option<RustcCode>,
+    level: String,
+    #[serde(default)]
+    spans: Vec<RustcSpan>,
+    #[serde(default)]
+    children: Vec<RustcDiagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    byte_start: u32,
+    byte_end: u32,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    is_primary: bool,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+}
+
+/// One line of `cargo --message-format=json` output.
+pub enum CargoLine {
+    /// A rustc diagnostic, with the human-readable rendering rustc produced.
+    Diagnostic(Box<Diagnostic>),
+    /// Any other cargo JSON message (artifacts, build scripts, build-finished).
+    Message,
+    /// Not cargo JSON; plain log output.
+    Text,
+}
+
+pub fn parse_cargo_line(line: &str) -> CargoLine {
+    let trimmed = line.trim();
+    if !trimmed.starts_with("{\"reason\":") {
+        return CargoLine::Text;
+    }
+    let Ok(message) = serde_json::from_str::<CargoMessage>(trimmed) else {
+        return CargoLine::Text;
+    };
+    match (message.reason.as_str(), message.message) {
+        ("compiler-message", Some(diagnostic)) => {
+            CargoLine::Diagnostic(Box::new(convert_diagnostic(diagnostic)))
+        }
+        _ => CargoLine::Message,
+    }
+}
+
+fn convert_diagnostic(diagnostic: RustcDiagnostic) -> Diagnostic {
+    let primary = diagnostic
+        .spans
+        .iter()
+        .find(|span| span.is_primary)
+        .or_else(|| diagnostic.spans.first());
+    let suggestion = diagnostic
+        .spans
+        .iter()
+        .chain(
+            diagnostic
+                .children
+                .iter()
+                .flat_map(|child| child.spans.iter()),
+        )
+        .find_map(|span| span.suggested_replacement.clone());
+
+    Diagnostic {
+        severity: diagnostic.level.clone(),
+        message: diagnostic.message.clone(),
+        code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+        file: primary.map(|span| span.file_name.clone()),
+        line: primary.map(|span| span.line_start),
+        column: primary.map(|span| span.column_start),
+        end_line: primary.map(|span| span.line_end),
+        end_column: primary.map(|span| span.column_end),
+        span: primary.map(|span| DiagnosticSpan {
+            byte_start: span.byte_start,
+            byte_end: span.byte_end,
+            label: span.label.clone(),
+        }),
+        suggestion,
+        rendered: diagnostic.rendered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rewrites buffered cargo output for humans: JSON diagnostics are replaced by
+    /// their rendered text, other cargo JSON messages are dropped, and the parsed
+    /// diagnostics are returned alongside.
+    fn split_cargo_output(output: &str) -> (String, Vec<Diagnostic>) {
+        let mut text = String::with_capacity(output.len());
+        let mut diagnostics = Vec::new();
+        for line in output.split_inclusive('\n') {
+            match parse_cargo_line(line) {
+                CargoLine::Diagnostic(diagnostic) => {
+                    if let Some(rendered) = &diagnostic.rendered {
+                        text.push_str(rendered);
+                    }
+                    diagnostics.push(*diagnostic);
+                }
+                CargoLine::Message => {}
+                CargoLine::Text => text.push_str(line),
+            }
+        }
+        (text, diagnostics)
+    }
+
+    const UNRESOLVED: &str = r#"{"reason":"compiler-message","package_id":"path+file:///workspace/project#hello_world@0.0.0","manifest_path":"/workspace/project/Cargo.toml","target":{"kind":["cdylib"],"name":"hello_world"},"message":{"$message_type":"diagnostic","message":"cannot find value `nme` in this scope","code":{"code":"E0425","explanation":"..."},"level":"error","spans":[{"file_name":"src/lib.rs","byte_start":210,"byte_end":213,"line_start":12,"line_end":12,"column_start":21,"column_end":24,"is_primary":true,"text":[],"label":"not found in this scope","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"a local variable with a similar name exists","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":210,"byte_end":213,"line_start":12,"line_end":12,"column_start":21,"column_end":24,"is_primary":true,"text":[],"label":null,"suggested_replacement":"name","suggestion_applicability":"MaybeIncorrect","expansion":null}],"children":[],"rendered":null}],"rendered":"error[E0425]: cannot find value `nme` in this scope\n"}}"#;
+
+    #[test]
+    fn parses_rustc_diagnostic_with_suggestion() {
+        let CargoLine::Diagnostic(diagnostic) = parse_cargo_line(UNRESOLVED) else {
+            panic!("expected a diagnostic");
+        };
+        assert_eq!(diagnostic.severity, "error");
+        assert_eq!(diagnostic.code.as_deref(), Some("E0425"));
+        assert_eq!(diagnostic.file.as_deref(), Some("src/lib.rs"));
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(12), Some(21)));
+        assert_eq!(diagnostic.end_column, Some(24));
+        assert_eq!(diagnostic.suggestion.as_deref(), Some("name"));
+        assert_eq!(diagnostic.span.unwrap().byte_start, 210);
+    }
+
+    #[test]
+    fn split_output_replaces_json_with_rendered_text() {
+        let output = format!(
+            "   Compiling hello_world v0.0.0\n{}\n{}\nerror: could not compile\n",
+            UNRESOLVED, r#"{"reason":"build-finished","success":false}"#
+        );
+        let (text, diagnostics) = split_cargo_output(&output);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            text,
+            "   Compiling hello_world v0.0.0\nerror[E0425]: cannot find value `nme` in this scope\nerror: could not compile\n"
+        );
+    }
+}
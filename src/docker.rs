@@ -1,22 +1,236 @@
 use std::{
-    collections::HashMap,
-    fs,
-    path::{Component, Path},
-    process::Stdio,
+    collections::{HashMap, VecDeque},
+    fmt, fs,
+    future::Future,
+    path::{Component, Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 use bytes::Bytes;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
-use tokio::{io::AsyncReadExt, sync::mpsc};
+use tokio::{
+    io::AsyncReadExt,
+    sync::{mpsc, oneshot, Notify},
+    time,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    attestation::{VerifyTarget, VERIFY_TOOLCHAIN},
+    bindings::BindingsLanguage,
+    executor::{CacheLayer, ContainerSpec, Executor, CACHE_SCOPE, EXECUTOR},
+    hardening::{job_workspace, share_workspace},
+    limits::{limits_for, CommandKind, SandboxLimits},
+    lint::{denied_message, lint_files, LintMode, LintViolation, LINT_CONFIG},
+    manifest::{check_manifests, rename_package, POLICY},
+    mirror::{self, MIRROR},
+    models::TestSelector,
+    workspace::{workspace_contracts, WorkspaceContract},
+};
+
+/// Contract build run in the sandbox. The JSON cargo pass surfaces rustc
+/// diagnostics as structured messages; `stellar contract build` then reuses the
+/// compiled dependencies to produce the final artifact.
+pub const BUILD_COMMAND: &str =
+    "cargo build --target wasm32v1-none --release --message-format=json && stellar contract build";
+
+const MAX_TEST_FILTER_LEN: usize = 256;
+/// Number of recent sandbox startups the reported median is taken over.
+const BOOT_WINDOW: usize = 100;
+/// Written by every job's shell before anything else, so the run can tell
+/// backend startup from the job itself. Stripped from the output.
+const START_MARKER: &[u8] = b"<<<SOROPG_JOB_STARTED>>>\n";
+
+/// Extra steps of a contract build, run in the sandbox on the built WASM.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BuildOptions {
+    /// Also write a `stellar contract optimize` pass next to the artifact, see
+    /// [`optimized_filename`].
+    pub optimize: bool,
+    /// Generate client bindings under `/workspace/bindings`.
+    pub bindings: Option<BindingsLanguage>,
+    /// Build with the pinned verification toolchain and collect what an
+    /// attestation against the target needs.
+    pub verify: Option<VerifyTarget>,
+    /// Write the submitted `Cargo.lock` and run cargo with `--locked`.
+    pub locked: bool,
+    /// Client the job runs for, which picks its cargo layer, see
+    /// [`crate::executor::CacheScope`].
+    pub tenant: Option<String>,
+}
+
+/// Directory of a job workspace that builds copy their artifacts to,
+/// `/workspace/out` in the sandbox.
+pub const OUTPUT_DIR: &str = "out";
+
+/// Host path of an artifact a job wrote to its output directory.
+pub fn artifact_path(workspace: &Path, filename: &str) -> PathBuf {
+    workspace.join(OUTPUT_DIR).join(filename)
+}
+
+/// Host filename of the optimized WASM written next to `output_filename`.
+pub fn optimized_filename(output_filename: &str) -> String {
+    let stem = output_filename
+        .strip_suffix(".wasm")
+        .unwrap_or(output_filename);
+    format!("{}.optimized.wasm", stem)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputChannel {
+    Stdout,
+    Stderr,
+    /// Written by the server once a run is over, never by the sandbox: the
+    /// run's [`RunTimings`] as JSON.
+    Timings,
+}
+
+impl OutputChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputChannel::Stdout => "stdout",
+            OutputChannel::Stderr => "stderr",
+            OutputChannel::Timings => "timings",
+        }
+    }
+}
+
+pub type OutputChunk = (OutputChannel, Bytes);
+
+/// Why a container was killed before it exited on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillReason {
+    Timeout(Duration),
+    OutputLimit(usize),
+}
+
+impl KillReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KillReason::Timeout(_) => "timeout",
+            KillReason::OutputLimit(_) => "output-limit",
+        }
+    }
+}
+
+impl fmt::Display for KillReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KillReason::Timeout(limit) => write!(f, "timed out after {}s", limit.as_secs()),
+            KillReason::OutputLimit(limit) => write!(f, "output exceeded {} bytes", limit),
+        }
+    }
+}
+
+/// Failure of a sandbox run. `exit_code` is set when the container ran and
+/// exited unsuccessfully; setup errors leave it empty. `workspace` keeps the
+/// job directory alive so callers can read reports written before the failure.
+#[derive(Debug)]
+pub struct SandboxError {
+    pub message: String,
+    pub exit_code: Option<i32>,
+    pub workspace: Option<TempDir>,
+    pub killed: Option<KillReason>,
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for SandboxError {
+    fn from(message: String) -> Self {
+        SandboxError {
+            message,
+            exit_code: None,
+            workspace: None,
+            killed: None,
+        }
+    }
+}
+
+/// Where the time of a sandbox run went: `boot` until the job's shell
+/// started, `build` for the job itself, both measured on the host. `warm` runs
+/// went to pre-started containers. `boot_p50` is the median startup of recent
+/// runs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTimings {
+    pub boot_ms: u64,
+    pub build_ms: u64,
+    pub warm: bool,
+    pub boot_p50_ms: u64,
+    #[serde(skip)]
+    runs: u32,
+}
+
+/// Startup durations of the most recent sandbox runs, in milliseconds.
+static BOOT_TIMES: Lazy<Mutex<VecDeque<u64>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(BOOT_WINDOW)));
+
+fn record_boot(boot_ms: u64) {
+    if let Ok(mut times) = BOOT_TIMES.lock() {
+        if times.len() == BOOT_WINDOW {
+            times.pop_front();
+        }
+        times.push_back(boot_ms);
+    }
+}
+
+/// Median startup of the last [`BOOT_WINDOW`] sandbox runs.
+fn boot_p50_ms() -> u64 {
+    let Ok(times) = BOOT_TIMES.lock() else {
+        return 0;
+    };
+    let mut sorted: Vec<u64> = times.iter().copied().collect();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).copied().unwrap_or(0)
+}
+
+impl RunTimings {
+    fn add(&mut self, boot: Duration, build: Duration, warm: bool) {
+        let boot_ms = boot.as_millis() as u64;
+        record_boot(boot_ms);
+        self.boot_ms += boot_ms;
+        self.build_ms += build.as_millis() as u64;
+        self.warm = (self.runs == 0 || self.warm) && warm;
+        self.runs += 1;
+        self.boot_p50_ms = boot_p50_ms();
+    }
+}
 
 struct PreparedDockerRun {
     tmp: TempDir,
+    /// Networked step downloading what the job needs before it runs offline.
+    fetch_command: String,
     final_command: String,
     output_filename: String,
     target_dir: String,
+    /// Content lint findings of a job allowed to run.
+    lint: Vec<LintViolation>,
 }
 
+/// Seeds a writable cargo layer from the read-only shared cache: the
+/// registry index once, and links to downloaded crates not yet in the layer.
+/// Cargo unpacks and builds into the layer only.
+const SEED_CARGO_LAYER: &str = concat!(
+    "if [ -d /mnt/cargo-base/registry ] && [ ! /mnt/cargo-base -ef /mnt/cargo ]; then ",
+    "mkdir -p /mnt/cargo/registry; ",
+    "[ -d /mnt/cargo/registry/index ] || cp -rT /mnt/cargo-base/registry/index /mnt/cargo/registry/index 2>/dev/null || true; ",
+    "cp -rsTn /mnt/cargo-base/registry/cache /mnt/cargo/registry/cache 2>/dev/null || true; ",
+    "fi;"
+);
+
 fn with_rustup_bootstrap(command: &str, trace: bool) -> String {
     let set_flags = if trace {
         "set -e; set -o pipefail; set -x"
@@ -24,11 +238,92 @@ fn with_rustup_bootstrap(command: &str, trace: bool) -> String {
         "set -e; set -o pipefail"
     };
     format!(
-        "{}; mkdir -p /mnt/cargo/bin; [ -x /mnt/cargo/bin/rustup ] || ln -sf /usr/local/cargo/bin/rustup /mnt/cargo/bin/rustup; export PATH=/mnt/cargo/bin:$PATH; target_dir=\"$CARGO_TARGET_DIR\"; [ -n \"$target_dir\" ] || target_dir=/mnt/cargo/target; if [ -x /opt/scout-prebuilt/scout-driver ] && [ -x /opt/scout-prebuilt/detector-helper ]; then mkdir -p \"$target_dir/release\"; cp -f /opt/scout-prebuilt/scout-driver \"$target_dir/release/scout-driver\"; cp -f /opt/scout-prebuilt/detector-helper \"$target_dir/release/detector-helper\"; fi; {}",
-        set_flags, command
+        "{}; umask 0000; {} mkdir -p /mnt/cargo/bin; [ -x /mnt/cargo/bin/rustup ] || ln -sf /usr/local/cargo/bin/rustup /mnt/cargo/bin/rustup; export PATH=/mnt/cargo/bin:$PATH; target_dir=\"$CARGO_TARGET_DIR\"; [ -n \"$target_dir\" ] || target_dir=/mnt/cargo/target; if [ -x /opt/scout-prebuilt/scout-driver ] && [ -x /opt/scout-prebuilt/detector-helper ]; then mkdir -p \"$target_dir/release\"; cp -fu /opt/scout-prebuilt/scout-driver \"$target_dir/release/scout-driver\"; cp -fu /opt/scout-prebuilt/detector-helper \"$target_dir/release/detector-helper\"; fi; {}",
+        set_flags, SEED_CARGO_LAYER, command
+    )
+}
+
+fn is_safe_test_filter(filter: &str) -> bool {
+    !filter.is_empty()
+        && filter.len() <= MAX_TEST_FILTER_LEN
+        && filter
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Adds `--locked` to the cargo and `stellar contract build` steps of a
+/// sandbox command.
+fn locked_command(command: &str) -> String {
+    command
+        .replace("cargo build ", "cargo build --locked ")
+        .replace("cargo test ", "cargo test --locked ")
+        .replace("stellar contract build", "stellar contract build --locked")
+}
+
+/// The `Cargo.lock` cargo resolved for a sandbox run, if it wrote one.
+pub fn resolved_lockfile(workspace: &Path) -> Option<String> {
+    fs::read_to_string(workspace.join("project").join("Cargo.lock")).ok()
+}
+
+/// `stellar` CLI network arguments for a network name. Only alphanumeric
+/// characters of `network` are kept.
+pub fn network_args(network: &str) -> String {
+    let network: String = network.chars().filter(|c| c.is_alphanumeric()).collect();
+    match network.as_str() {
+        "public" => "--network mainnet --rpc-url https://mainnet.sorobanrpc.com --network-passphrase \"Public Global Stellar Network ; September 2015\"".to_string(),
+        "futurenet" => "--network futurenet --rpc-url https://rpc-futurenet.stellar.org --network-passphrase \"Test SDF Future Network ; October 2022\"".to_string(),
+        _ => format!("--network {}", network),
+    }
+}
+
+/// Scout writes its SARIF report here, next to the project directory.
+pub const AUDIT_REPORT_FILE: &str = "scout-report.sarif";
+
+/// Fetches the dependencies of Scout's detectors, which it compiles while
+/// auditing, so audits can run offline like builds.
+const DETECTORS_FETCH: &str = concat!(
+    "{ [ ! -d /scout-audit/nightly ] || find /scout-audit/nightly -name Cargo.lock -not -path '*/target/*' | ",
+    "while read -r lock; do (cd \"${lock%/*}\" && cargo fetch); done; }"
+);
+
+/// Builds the sandbox Scout command. The verbose cargo log is only requested
+/// when the caller opted into the raw log.
+pub fn audit_command(raw_log: bool) -> String {
+    format!(
+        "CARGO_BUILD_JOBS=1 cargo scout-audit --debug{} --scout-source /scout-audit --local-detectors /scout-audit/nightly --output-format sarif --output-path /workspace/{}",
+        if raw_log { " -v" } else { "" },
+        AUDIT_REPORT_FILE
     )
 }
 
+/// Builds the sandbox test command. The image defaults to nightly, so libtest
+/// can report each test as a JSON event alongside cargo's JSON diagnostics.
+///
+/// The filter is spliced into a `bash -c` string, so it is restricted to Rust
+/// path characters and single-quoted on top of that.
+pub fn test_command(selector: &TestSelector) -> Result<String, String> {
+    let mut command = "cargo test --message-format=json".to_string();
+    if let Some(filter) = selector.filter.as_deref().map(str::trim) {
+        if !filter.is_empty() {
+            if !is_safe_test_filter(filter) {
+                return Err(format!(
+                    "Invalid test filter: only letters, digits, '_' and '::' are allowed (max {} characters)",
+                    MAX_TEST_FILTER_LEN
+                ));
+            }
+            command.push_str(&format!(" '{}'", filter));
+        }
+    }
+    command.push_str(" -- -Z unstable-options --format json --report-time --show-output");
+    if selector.exact.unwrap_or(false) {
+        command.push_str(" --exact");
+    }
+    if selector.ignored.unwrap_or(false) {
+        command.push_str(" --ignored");
+    }
+    Ok(command)
+}
+
 fn is_safe_relative_path(path: &str) -> bool {
     if path.is_empty() {
         return false;
@@ -65,7 +360,7 @@ fn is_safe_relative_path(path: &str) -> bool {
     saw_segment
 }
 
-fn legacy_workspace_path(path: &str, files: &HashMap<String, String>) -> String {
+pub(crate) fn legacy_workspace_path(path: &str, files: &HashMap<String, String>) -> String {
     match path {
         "lib.rs" if !files.contains_key("src/lib.rs") => "src/lib.rs".to_string(),
         "test.rs" if !files.contains_key("src/test.rs") => "src/test.rs".to_string(),
@@ -87,49 +382,6 @@ fn write_project_file(project: &Path, relative_path: &str, content: &str) -> Res
     fs::write(&full_path, content).map_err(|e| format!("Failed to write {}: {}", relative_path, e))
 }
 
-fn is_safe_file_content(content: &str) -> bool {
-    // Basic checks for suspicious content
-    let suspicious_patterns = [
-        "std::process::",
-        "Command::",
-        "system(",
-        "exec(",
-        "/bin/",
-        "/usr/bin/",
-        "unsafe",
-        "#[no_mangle]",
-        "extern \"C\"",
-        "asm!",
-        "include_str!",
-        "include_bytes!",
-        "env!",
-        "std::env::",
-        "std::fs::",
-        "std::net::",
-        "tokio::process::",
-        "tokio::fs::",
-        "actix_web::",
-        "reqwest::",
-        "hyper::",
-    ];
-
-    // Allow legitimate Soroban patterns but block system access
-    if content.contains("soroban_sdk")
-        || content.contains("stellar")
-        || content.contains("#[contract]")
-    {
-        // This is likely legitimate Soroban code, but still check for dangerous patterns
-        return !suspicious_patterns
-            .iter()
-            .any(|&pattern| content.contains(pattern));
-    }
-
-    // For non-Soroban files, be more restrictive
-    !suspicious_patterns
-        .iter()
-        .any(|&pattern| content.contains(pattern))
-}
-
 fn to_snake_case(s: &str) -> String {
     let mut result = String::new();
     let mut prev_is_lowercase = false;
@@ -179,13 +431,30 @@ fn build_wasm_copy_command(
     target_dir: &str,
     expected_wasm_filename: &str,
     output_filename: &str,
+    options: &BuildOptions,
 ) -> String {
+    let optimize_step = if options.optimize {
+        format!(
+            " && stellar contract optimize --wasm \"$wasm_path\" --wasm-out \"/workspace/out/{}\"",
+            optimized_filename(output_filename)
+        )
+    } else {
+        String::new()
+    };
+    let bindings_step = match options.bindings {
+        Some(language) => format!(" && {}", language.command()),
+        None => String::new(),
+    };
+    let toolchain = match &options.verify {
+        Some(_) => format!("export RUSTUP_TOOLCHAIN={}; ", VERIFY_TOOLCHAIN),
+        None => String::new(),
+    };
     format!(
         concat!(
-            "build_log=\"$(mktemp)\"; ",
+            "{toolchain}build_log=\"$(mktemp)\"; ",
             "release_dir=\"{target_dir}/wasm32v1-none/release\"; ",
             "cd /workspace/project; ",
-            "{command} 2>&1 | tee \"$build_log\"; ",
+            "{{ {command}; }} 2>&1 | tee \"$build_log\"; ",
             "wasm_path=\"$release_dir/{expected_wasm_filename}.wasm\"; ",
             "if [ ! -f \"$wasm_path\" ]; then ",
             "wasm_path=\"$(sed -n 's/^[[:space:]]*Wasm File: //p' \"$build_log\" | tail -n 1)\"; ",
@@ -204,29 +473,86 @@ fn build_wasm_copy_command(
             "fi; ",
             "[ -n \"$wasm_path\" ] && [ -f \"$wasm_path\" ]; ",
             "ls -la \"$wasm_path\"; ",
-            "cp \"$wasm_path\" \"/host-tmp/{output_filename}\""
+            "cp \"$wasm_path\" \"/workspace/out/{output_filename}\"{optimize_step}{bindings_step}"
         ),
         command = command,
         target_dir = target_dir,
         expected_wasm_filename = expected_wasm_filename,
         output_filename = output_filename,
+        optimize_step = optimize_step,
+        bindings_step = bindings_step,
+        toolchain = toolchain,
     )
 }
 
+/// Sandbox command of a Cargo workspace project. `target` in the project
+/// points at the cargo target directory, so `contractimport!` paths such as
+/// `../../target/wasm32v1-none/release/token.wasm` resolve. Builds copy every
+/// contract member's WASM to the output directory.
+fn workspace_build_command(
+    command: &str,
+    target_dir: &str,
+    contracts: &[WorkspaceContract],
+    build_id: Option<&str>,
+    is_build: bool,
+) -> String {
+    let mut script = format!(
+        "cd /workspace/project; ln -sfn \"{target_dir}\" target; {command}",
+        target_dir = target_dir,
+        command = command
+    );
+    if is_build {
+        script.push_str(&format!(
+            "; release_dir=\"{}/wasm32v1-none/release\"",
+            target_dir
+        ));
+        for contract in contracts {
+            script.push_str(&format!(
+                "; cp \"$release_dir/{}.wasm\" \"/workspace/out/{}\"",
+                contract.wasm_name(),
+                contract.output_filename(build_id)
+            ));
+        }
+    }
+    script
+}
+
+/// Whether `files` pass the checks that stop a job before it runs: the
+/// Cargo.toml policy and content lint in deny mode, and the crate mirror.
+/// Cached builds are only served when this holds, since the policies may
+/// have changed since they were built.
+pub fn passes_policy(files: &HashMap<String, String>) -> bool {
+    if POLICY.mode == LintMode::Deny && !check_manifests(files).is_empty() {
+        return false;
+    }
+    if LINT_CONFIG.mode == LintMode::Deny
+        && !lint_files(
+            files
+                .iter()
+                .map(|(path, content)| (path.as_str(), content.as_str())),
+        )
+        .is_empty()
+    {
+        return false;
+    }
+    match &*MIRROR {
+        Some(Ok(mirror)) => mirror.check(files).is_ok(),
+        Some(Err(_)) => false,
+        None => true,
+    }
+}
+
 fn prepare_docker_run(
     code: String,
     files: Option<HashMap<String, String>>,
     command: &str,
     build_id: Option<String>,
+    options: &BuildOptions,
 ) -> Result<PreparedDockerRun, String> {
-    let tmp = TempDir::new().map_err(|e| e.to_string())?;
+    let tmp = job_workspace()?;
     let project = tmp.path().join("project");
     fs::create_dir(&project).map_err(|e| e.to_string())?;
-
-    // Validate main code content
-    if !is_safe_file_content(&code) {
-        return Err("Unsafe code content detected in main file".to_string());
-    }
+    fs::create_dir(tmp.path().join(OUTPUT_DIR)).map_err(|e| e.to_string())?;
 
     // Extract contract name from lib.rs code
     //let contract_name = extract_contract_name(&code).unwrap_or_else(|| "project".to_string());
@@ -241,17 +567,62 @@ fn prepare_docker_run(
         .get("Cargo.toml")
         .ok_or_else(|| "Cargo.toml file is required but not provided".to_string())?;
 
-    if !is_safe_file_content(custom_cargo) {
-        return Err("Unsafe content detected in Cargo.toml".to_string());
+    let lint = lint_files(
+        files_map
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_str())),
+    );
+    if LINT_CONFIG.mode == LintMode::Deny && !lint.is_empty() {
+        return Err(denied_message("Content lint", &lint));
+    }
+    let mirror = match &*MIRROR {
+        Some(Ok(mirror)) => {
+            mirror.check(&files_map)?;
+            true
+        }
+        Some(Err(e)) => return Err(e.clone()),
+        None => false,
+    };
+
+    let is_build = command.contains("build");
+    let contracts = workspace_contracts(&files_map)?;
+    let command = match &contracts {
+        Some(_) if options.optimize || options.bindings.is_some() || options.verify.is_some() => {
+            return Err(
+                "Optimize, bindings and verification builds support single-contract projects only"
+                    .to_string(),
+            )
+        }
+        // Integration tests `contractimport!` the members' WASMs, so build
+        // them before testing.
+        Some(_) if command.starts_with("cargo test") => {
+            format!("stellar contract build && {}", command)
+        }
+        Some(_) => command.to_string(),
+        None => {
+            let package_name = to_snake_case(&contract_name);
+            eprintln!("Setting package name in Cargo.toml: {}", package_name);
+            let updated_cargo = rename_package(custom_cargo, &package_name)?;
+            write_project_file(&project, "Cargo.toml", &updated_cargo)?;
+            command.to_string()
+        }
+    };
+    if contracts.is_some() {
+        write_project_file(&project, "Cargo.toml", custom_cargo)?;
     }
 
-    let package_name = to_snake_case(&contract_name);
-    eprintln!("Setting package name in Cargo.toml: {}", package_name);
-    let updated_cargo = custom_cargo.replace(
-        "name = \"project\"",
-        &format!("name = \"{}\"", package_name),
-    );
-    write_project_file(&project, "Cargo.toml", &updated_cargo)?;
+    // Lockfiles are only honored on request, so unlocked builds keep
+    // resolving the newest compatible dependencies.
+    let command = if options.locked {
+        let lockfile = files_map
+            .get("Cargo.lock")
+            .ok_or_else(|| "Locked builds need a Cargo.lock file".to_string())?;
+        write_project_file(&project, "Cargo.lock", lockfile)?;
+        locked_command(&command)
+    } else {
+        command
+    };
+    let command = command.as_str();
 
     let mut filenames: Vec<_> = files_map.keys().cloned().collect();
     filenames.sort();
@@ -266,16 +637,20 @@ fn prepare_docker_run(
             .get(&filename)
             .ok_or_else(|| format!("Missing content for {}", filename))?;
 
-        if !is_safe_file_content(content) {
-            return Err(format!("Unsafe content detected in file: {}", filename));
-        }
-
         let target_path = legacy_workspace_path(&filename, &files_map);
         write_project_file(&project, &target_path, content)?;
     }
 
     // Use build_id to create unique target directory and output filename if provided
-    let (target_dir, output_filename) = if let Some(id) = build_id {
+    let (target_dir, output_filename) = if let Some(contracts) = &contracts {
+        (
+            match &build_id {
+                Some(id) => format!("/mnt/cargo/target-{}", &id[..12]),
+                None => "/mnt/cargo/target".to_string(),
+            },
+            contracts[0].output_filename(build_id.as_deref()),
+        )
+    } else if let Some(id) = build_id.as_deref() {
         (
             format!("/mnt/cargo/target-{}", &id[..12]), // Use first 12 chars of hash
             format!("{}-{}.wasm", contract_name, &id[..8]), // Use contract name and first 8 chars of hash
@@ -289,7 +664,18 @@ fn prepare_docker_run(
 
     let mut final_command =
         with_rustup_bootstrap(&format!("cd /workspace/project && {}", command), false);
-    if command.contains("build") {
+    if let Some(contracts) = &contracts {
+        final_command = with_rustup_bootstrap(
+            &workspace_build_command(
+                command,
+                &target_dir,
+                contracts,
+                build_id.as_deref(),
+                is_build,
+            ),
+            is_build,
+        );
+    } else if is_build {
         // Prefer the conventional contract-name artifact, then fall back to
         // the path reported by `stellar contract build`, then probe the
         // release directory for the newest wasm artifact.
@@ -297,123 +683,53 @@ fn prepare_docker_run(
         eprintln!("Expected WASM file: {}.wasm", wasm_filename);
         eprintln!("Target directory: {}", target_dir);
         final_command = with_rustup_bootstrap(
-            &build_wasm_copy_command(command, &target_dir, &wasm_filename, &output_filename),
+            &build_wasm_copy_command(
+                command,
+                &target_dir,
+                &wasm_filename,
+                &output_filename,
+                options,
+            ),
             true,
         )
     }
 
+    let mut fetch_command = format!(
+        "cd /workspace/project && cargo fetch{}",
+        if options.locked { " --locked" } else { "" }
+    );
+    if command.contains("scout-audit") {
+        fetch_command = format!("{} && {}", fetch_command, DETECTORS_FETCH);
+    }
+    if mirror {
+        final_command = format!("{}; {}", mirror::setup_command(), final_command);
+    }
+    share_workspace(tmp.path())?;
+
     Ok(PreparedDockerRun {
         tmp,
+        fetch_command: with_rustup_bootstrap(&fetch_command, false),
         final_command,
         output_filename,
         target_dir,
+        lint,
     })
 }
 
-pub async fn run_in_docker_no_files(command: &str) -> Result<(Vec<u8>, TempDir), String> {
-    let tmp = TempDir::new().map_err(|e| e.to_string())?;
-    let final_command = with_rustup_bootstrap(command, false);
-
-    let output = tokio::process::Command::new("docker")
-        .args([
-            "run",
-            "--rm",
-            "--memory=2G",
-            "--cpus=2",
-            "-v",
-            &format!("{}:/workspace", tmp.path().display()),
-            "-v",
-            "cargo-cache:/mnt/cargo",
-            "-e",
-            "CARGO_HOME=/mnt/cargo",
-            "wasm_sandbox:latest",
-            "bash",
-            "-c",
-            &final_command,
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let mut combined_output = output.stdout;
-    if !output.stderr.is_empty() {
-        combined_output.extend_from_slice(&output.stderr);
-    }
-
-    if !output.status.success() {
-        return Err(format!(
-            "Docker exited with status: {}\n{}",
-            output.status,
-            String::from_utf8_lossy(&combined_output)
-        ));
-    }
-
-    Ok((combined_output, tmp))
+/// Stops reading container output once the byte cap is reached.
+struct OutputBudget {
+    used: AtomicUsize,
+    limit: usize,
+    exceeded: Notify,
 }
 
-#[allow(dead_code)]
-pub async fn run_in_docker_with_files(
-    code: String,
-    files: Option<HashMap<String, String>>,
-    command: &str,
-) -> Result<(Vec<u8>, TempDir, String), String> {
-    run_in_docker_with_files_and_id(code, files, command, None).await
-}
-
-#[allow(dead_code)]
-pub async fn run_in_docker_with_files_and_id(
-    code: String,
-    files: Option<HashMap<String, String>>,
-    command: &str,
-    build_id: Option<String>,
-) -> Result<(Vec<u8>, TempDir, String), String> {
-    let prepared = prepare_docker_run(code, files, command, build_id)?;
-    let output = tokio::process::Command::new("docker")
-        .args([
-            "run",
-            "--rm",
-            "--memory=2G",
-            "--cpus=2",
-            "-v",
-            &format!("{}:/workspace", prepared.tmp.path().display()),
-            "-v",
-            "cargo-cache:/mnt/cargo",
-            "-v",
-            "/tmp:/host-tmp",
-            "-e",
-            "CARGO_HOME=/mnt/cargo",
-            "-e",
-            &format!("CARGO_TARGET_DIR={}", prepared.target_dir),
-            "wasm_sandbox:latest",
-            "bash",
-            "-c",
-            &prepared.final_command,
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let mut combined_output = output.stdout;
-    if !output.stderr.is_empty() {
-        combined_output.extend_from_slice(&output.stderr);
-    }
-
-    if !output.status.success() {
-        return Err(format!(
-            "Docker exited with status: {}\n{}",
-            output.status,
-            String::from_utf8_lossy(&combined_output)
-        ));
-    }
-
-    Ok((combined_output, prepared.tmp, prepared.output_filename))
-}
-
-async fn stream_pipe<R>(mut reader: R, tx: mpsc::UnboundedSender<Bytes>) -> Result<(), String>
+async fn stream_pipe<R>(
+    mut reader: R,
+    channel: OutputChannel,
+    tx: mpsc::UnboundedSender<OutputChunk>,
+    budget: Arc<OutputBudget>,
+    mut started: Option<oneshot::Sender<time::Instant>>,
+) -> Result<(), String>
 where
     R: tokio::io::AsyncRead + Unpin,
 {
@@ -423,47 +739,115 @@ where
         if size == 0 {
             break;
         }
-        if tx.send(Bytes::copy_from_slice(&buf[..size])).is_err() {
+        let mut chunk = &buf[..size];
+        if let Some(started) = started.take() {
+            if let Some(rest) = chunk.strip_prefix(START_MARKER) {
+                let _ = started.send(time::Instant::now());
+                chunk = rest;
+            }
+        }
+        if budget.used.fetch_add(chunk.len(), Ordering::Relaxed) + chunk.len() > budget.limit {
+            budget.exceeded.notify_one();
+            break;
+        }
+        if !chunk.is_empty() && tx.send((channel, Bytes::copy_from_slice(chunk))).is_err() {
             break;
         }
     }
     Ok(())
 }
 
-pub async fn run_in_docker_with_files_and_id_stream(
-    code: String,
-    files: Option<HashMap<String, String>>,
-    command: &str,
-    build_id: Option<String>,
-    tx: mpsc::UnboundedSender<Bytes>,
-) -> Result<(TempDir, String), String> {
-    let prepared = prepare_docker_run(code, files, command, build_id)?;
-
-    let mut child = tokio::process::Command::new("docker")
-        .args([
-            "run",
-            "--rm",
-            "--memory=2G",
-            "--cpus=2",
-            "-v",
-            &format!("{}:/workspace", prepared.tmp.path().display()),
-            "-v",
-            "cargo-cache:/mnt/cargo",
-            "-v",
-            "/tmp:/host-tmp",
-            "-e",
-            "CARGO_HOME=/mnt/cargo",
-            "-e",
-            &format!("CARGO_TARGET_DIR={}", prepared.target_dir),
-            "wasm_sandbox:latest",
-            "bash",
-            "-c",
-            &prepared.final_command,
-        ])
+async fn kill_job(job: &str, kill: Option<tokio::process::Command>) {
+    let Some(mut kill) = kill else {
+        return;
+    };
+    let result = kill
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+    if let Err(e) = result {
+        warn!(job=%job, error=%e, "failed to kill sandbox job");
+    }
+}
+
+/// Kills the job if the run is dropped before it finished, e.g. because the
+/// client disconnected or the job was cancelled.
+struct JobGuard<'a> {
+    executor: &'a dyn Executor,
+    job: String,
+    kill: Option<tokio::process::Command>,
+    finished: bool,
+}
+
+impl JobGuard<'_> {
+    /// Tells the executor the job is over, returning the command stopping
+    /// it in case it is still running.
+    fn finish(&mut self, success: bool) -> Option<tokio::process::Command> {
+        self.finished = true;
+        self.executor.finish(&self.job, success);
+        self.kill.take()
+    }
+}
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let Some(kill) = self.finish(false) else {
+            return;
+        };
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let job = std::mem::take(&mut self.job);
+            runtime.spawn(async move {
+                warn!(job=%job, "killing abandoned sandbox job");
+                kill_job(&job, Some(kill)).await;
+            });
+        }
+    }
+}
+
+/// Runs a job on `executor` under `limits`, streaming its output to `tx` and
+/// adding its durations to `timings`. The job gets a generated name so the
+/// backend can kill it when it runs past `deadline`, the end of the job's
+/// wall-clock limit, or its output cap, or when the run is dropped.
+async fn run_container(
+    executor: &dyn Executor,
+    spec: ContainerSpec<'_>,
+    limits: &SandboxLimits,
+    deadline: time::Instant,
+    tx: mpsc::UnboundedSender<OutputChunk>,
+    timings: &mut RunTimings,
+) -> Result<ExitStatus, SandboxError> {
+    let job = format!("soropg-{}", Uuid::new_v4().simple());
+    let begun = time::Instant::now();
+    let command = format!(
+        "printf '{}' >&2; {}",
+        String::from_utf8_lossy(START_MARKER).replace('\n', "\\n"),
+        spec.command
+    );
+    let spec = ContainerSpec {
+        command: &command,
+        ..spec
+    };
+    let mut command = executor.command(&job, &spec, limits);
+    // Guards the job from here on, so whatever `command` reserved is given
+    // back even when the command fails to start.
+    let mut guard = JobGuard {
+        executor,
+        job: job.clone(),
+        kill: None,
+        finished: false,
+    };
+    let warm = executor.is_warm(&job);
+    let mut child = command
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| format!("Failed to start {} sandbox: {}", executor.name(), e))?;
+    guard.kill = executor.kill_command(&job, child.id());
 
     let stdout = child
         .stdout
@@ -474,17 +858,462 @@ pub async fn run_in_docker_with_files_and_id_stream(
         .take()
         .ok_or_else(|| "Failed to capture stderr".to_string())?;
 
-    let stdout_task = tokio::spawn(stream_pipe(stdout, tx.clone()));
-    let stderr_task = tokio::spawn(stream_pipe(stderr, tx.clone()));
+    let budget = Arc::new(OutputBudget {
+        used: AtomicUsize::new(0),
+        limit: limits.max_output_bytes,
+        exceeded: Notify::new(),
+    });
+    let (started_tx, mut started_rx) = oneshot::channel();
+    let stdout_task = tokio::spawn(stream_pipe(
+        stdout,
+        OutputChannel::Stdout,
+        tx.clone(),
+        budget.clone(),
+        None,
+    ));
+    let stderr_task = tokio::spawn(stream_pipe(
+        stderr,
+        OutputChannel::Stderr,
+        tx.clone(),
+        budget.clone(),
+        Some(started_tx),
+    ));
+    let mut record_timings = |timings: &mut RunTimings| {
+        let started = started_rx
+            .try_recv()
+            .unwrap_or_else(|_| time::Instant::now());
+        timings.add(started - begun, started.elapsed(), warm);
+    };
+
+    let killed = tokio::select! {
+        status = child.wait() => {
+            let status = status.map_err(|e| e.to_string())?;
+            guard.finish(status.success());
+            let _ = stdout_task.await;
+            let _ = stderr_task.await;
+            record_timings(timings);
+            return Ok(status);
+        }
+        _ = time::sleep_until(deadline) => KillReason::Timeout(limits.timeout),
+        _ = budget.exceeded.notified() => KillReason::OutputLimit(limits.max_output_bytes),
+    };
+
+    warn!(job=%job, reason=%killed, "killing sandbox job");
+    kill_job(&job, guard.finish(false)).await;
+    if time::timeout(Duration::from_secs(10), child.wait())
+        .await
+        .is_err()
+    {
+        let _ = child.kill().await;
+    }
+    stdout_task.abort();
+    stderr_task.abort();
+    record_timings(timings);
+
+    Err(SandboxError {
+        message: format!("Sandbox {}", killed),
+        exit_code: None,
+        workspace: None,
+        killed: Some(killed),
+    })
+}
+
+/// Drives a sandbox run while buffering its output, stdout first, as the
+/// non-streaming callers expect.
+async fn collect_output<T>(
+    run: impl Future<Output = T>,
+    mut rx: mpsc::UnboundedReceiver<OutputChunk>,
+) -> (T, Vec<u8>) {
+    let collect = async {
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        while let Some((channel, bytes)) = rx.recv().await {
+            match channel {
+                OutputChannel::Stdout => stdout.extend_from_slice(&bytes),
+                OutputChannel::Stderr => stderr.extend_from_slice(&bytes),
+                OutputChannel::Timings => {}
+            }
+        }
+        stdout.extend_from_slice(&stderr);
+        stdout
+    };
+    tokio::join!(run, collect)
+}
 
-    let status = child.wait().await.map_err(|e| e.to_string())?;
+fn discard_layer(layer: Option<CacheLayer>) {
+    if let Some(layer) = layer.filter(|layer| layer.discard) {
+        EXECUTOR.discard_cache(&layer.name);
+    }
+}
 
-    let _ = stdout_task.await;
-    let _ = stderr_task.await;
+pub async fn run_in_docker_no_files(command: &str) -> Result<(Vec<u8>, TempDir), String> {
+    let tmp = job_workspace()?;
+    share_workspace(tmp.path())?;
+    let final_command = with_rustup_bootstrap(command, false);
+    let layer = CACHE_SCOPE.layer(None);
+    let spec = ContainerSpec {
+        workspace: tmp.path(),
+        cache: layer.as_ref(),
+        env: Vec::new(),
+        command: &final_command,
+        offline: false,
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel::<OutputChunk>();
+    let limits = limits_for(CommandKind::Interface);
+    let (status, combined_output) = collect_output(
+        run_container(
+            &**EXECUTOR,
+            spec,
+            limits,
+            time::Instant::now() + limits.timeout,
+            tx,
+            &mut RunTimings::default(),
+        ),
+        rx,
+    )
+    .await;
+    discard_layer(layer);
+    let status = status.map_err(|e| e.to_string())?;
 
     if !status.success() {
-        return Err(format!("Docker exited with status: {}", status));
+        return Err(format!(
+            "Docker exited with status: {}\n{}",
+            status,
+            String::from_utf8_lossy(&combined_output)
+        ));
+    }
+
+    Ok((combined_output, tmp))
+}
+
+pub async fn run_in_docker_with_files_and_id_stream(
+    code: String,
+    files: Option<HashMap<String, String>>,
+    command: &str,
+    build_id: Option<String>,
+    options: BuildOptions,
+    kind: CommandKind,
+    tx: mpsc::UnboundedSender<OutputChunk>,
+) -> Result<(TempDir, String), SandboxError> {
+    let manifest = files.as_ref().map(check_manifests).unwrap_or_default();
+    let level = match POLICY.mode {
+        LintMode::Deny => "error",
+        _ => "warning",
+    };
+    for violation in &manifest {
+        let line = format!("{}\n", violation.cargo_message(level));
+        let _ = tx.send((OutputChannel::Stdout, Bytes::from(line)));
+    }
+    if POLICY.mode == LintMode::Deny && !manifest.is_empty() {
+        return Err(denied_message("Cargo.toml policy", &manifest).into());
+    }
+    let prepared = prepare_docker_run(code, files, command, build_id, &options)?;
+    for violation in &prepared.lint {
+        let line = format!("{}\n", violation.cargo_message("warning"));
+        let _ = tx.send((OutputChannel::Stdout, Bytes::from(line)));
+    }
+    let limits = limits_for(kind);
+    let layer = CACHE_SCOPE.layer(options.tenant.as_deref());
+    let spec = |command| ContainerSpec {
+        workspace: prepared.tmp.path(),
+        cache: layer.as_ref(),
+        env: vec![format!("CARGO_TARGET_DIR={}", prepared.target_dir)],
+        command,
+        offline: false,
+    };
+
+    let exited = |status: ExitStatus, step: &str| {
+        if status.success() {
+            Ok(())
+        } else {
+            Err(SandboxError {
+                message: format!("{} exited with status: {}", step, status),
+                exit_code: status.code(),
+                workspace: None,
+                killed: None,
+            })
+        }
+    };
+
+    // Offline jobs download their dependencies in a networked container
+    // first; online jobs run the same fetch before the job itself. With a
+    // crate mirror there is nothing to download and every job runs offline.
+    // Both containers run against one deadline, so the fetch counts against
+    // the job's timeout.
+    let mirrored = MIRROR.is_some();
+    let offline = limits.offline || mirrored;
+    let deadline = time::Instant::now() + limits.timeout;
+    let mut timings = RunTimings::default();
+    let fetched = if limits.offline && !mirrored {
+        let fetch = spec(&prepared.fetch_command);
+        run_container(
+            &**EXECUTOR,
+            fetch,
+            limits,
+            deadline,
+            tx.clone(),
+            &mut timings,
+        )
+        .await
+        .and_then(|status| exited(status, "Dependency fetch"))
+    } else {
+        Ok(())
+    };
+    let result = match fetched {
+        Ok(()) => {
+            let command = if offline {
+                prepared.final_command.clone()
+            } else {
+                format!("{}\n{}", prepared.fetch_command, prepared.final_command)
+            };
+            let job = ContainerSpec {
+                offline,
+                ..spec(&command)
+            };
+            run_container(&**EXECUTOR, job, limits, deadline, tx.clone(), &mut timings)
+                .await
+                .and_then(|status| exited(status, "Docker"))
+        }
+        Err(error) => Err(error),
+    };
+    if timings.runs > 0 {
+        if let Ok(json) = serde_json::to_vec(&timings) {
+            let _ = tx.send((OutputChannel::Timings, Bytes::from(json)));
+        }
+    }
+    discard_layer(layer);
+
+    match result {
+        Ok(()) => Ok((prepared.tmp, prepared.output_filename)),
+        Err(mut error) => {
+            error.workspace = Some(prepared.tmp);
+            Err(error)
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalExecutor;
+
+    #[test]
+    fn test_command_applies_selector() {
+        let selector = TestSelector {
+            filter: Some("test::test_transfer".to_string()),
+            exact: Some(true),
+            ignored: Some(true),
+        };
+        let command = test_command(&selector).unwrap();
+        assert!(command.starts_with("cargo test --message-format=json 'test::test_transfer' -- "));
+        assert!(command.ends_with("--show-output --exact --ignored"));
+        assert!(!test_command(&TestSelector::default())
+            .unwrap()
+            .contains("--exact"));
+    }
+
+    #[test]
+    fn test_command_rejects_shell_in_filter() {
+        for filter in ["x; rm -rf /", "$(id)", "a'b", "x y", "`id`", "a\nb"] {
+            let selector = TestSelector {
+                filter: Some(filter.to_string()),
+                ..TestSelector::default()
+            };
+            assert!(test_command(&selector).is_err(), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn build_copies_optimized_wasm_on_request() {
+        let plain = build_wasm_copy_command(
+            BUILD_COMMAND,
+            "/mnt/cargo/target",
+            "hello",
+            "hello-12345678.wasm",
+            &BuildOptions::default(),
+        );
+        assert!(!plain.contains("stellar contract optimize"));
+
+        let optimized = build_wasm_copy_command(
+            BUILD_COMMAND,
+            "/mnt/cargo/target",
+            "hello",
+            "hello-12345678.wasm",
+            &BuildOptions {
+                optimize: true,
+                ..BuildOptions::default()
+            },
+        );
+        assert!(optimized.ends_with(
+            "&& stellar contract optimize --wasm \"$wasm_path\" --wasm-out \"/workspace/out/hello-12345678.optimized.wasm\""
+        ));
+    }
+
+    #[test]
+    fn locked_builds_write_the_submitted_lockfile() {
+        let code = "#[contract]\npub struct Hello;".to_string();
+        let mut files = HashMap::from([
+            (
+                "Cargo.toml".to_string(),
+                "[package]\nname = \"project\"\n".to_string(),
+            ),
+            ("src/lib.rs".to_string(), code.clone()),
+        ]);
+        let locked = BuildOptions {
+            locked: true,
+            ..BuildOptions::default()
+        };
+        assert!(prepare_docker_run(
+            code.clone(),
+            Some(files.clone()),
+            BUILD_COMMAND,
+            None,
+            &locked
+        )
+        .is_err());
+
+        files.insert("Cargo.lock".to_string(), "version = 4\n".to_string());
+        let unlocked = prepare_docker_run(
+            code.clone(),
+            Some(files.clone()),
+            BUILD_COMMAND,
+            None,
+            &BuildOptions::default(),
+        )
+        .unwrap();
+        assert!(resolved_lockfile(unlocked.tmp.path()).is_none());
+        assert!(!unlocked.final_command.contains("--locked"));
+
+        let prepared = prepare_docker_run(code, Some(files), BUILD_COMMAND, None, &locked).unwrap();
+        assert_eq!(
+            resolved_lockfile(prepared.tmp.path()).as_deref(),
+            Some("version = 4\n")
+        );
+        assert!(prepared
+            .final_command
+            .contains("cargo build --locked --target wasm32v1-none"));
+        assert!(prepared
+            .final_command
+            .contains("stellar contract build --locked"));
+    }
+
+    #[test]
+    fn prepare_lints_sources_and_fetches_before_building() {
+        let code = "/// Never needs `unsafe`.\n#[contract]\npub struct Hello;\nconst V: &str = env!(\"CARGO_PKG_VERSION\");\n".to_string();
+        let files = HashMap::from([
+            (
+                "Cargo.toml".to_string(),
+                "[package]\nname = \"project\"\n".to_string(),
+            ),
+            ("src/lib.rs".to_string(), code.clone()),
+        ]);
+        let prepared = prepare_docker_run(
+            code,
+            Some(files),
+            BUILD_COMMAND,
+            None,
+            &BuildOptions::default(),
+        )
+        .unwrap();
+
+        let found: Vec<_> = prepared
+            .lint
+            .iter()
+            .map(|violation| (violation.rule, violation.line, violation.column))
+            .collect();
+        assert_eq!(found, vec![("environment", 4, 17)]);
+        assert!(prepared
+            .fetch_command
+            .ends_with("cd /workspace/project && cargo fetch"));
+    }
+
+    fn local_limits(timeout: Duration) -> SandboxLimits {
+        SandboxLimits {
+            timeout,
+            ..limits_for(CommandKind::Build).clone()
+        }
+    }
+
+    #[actix_web::test]
+    async fn local_executor_runs_jobs_in_the_workspace() {
+        let workspace = TempDir::new().unwrap();
+        let cargo_home = TempDir::new().unwrap();
+        fs::write(workspace.path().join("input.txt"), "hello\n").unwrap();
+        let executor = LocalExecutor::new(cargo_home.path().to_path_buf());
+        let spec = ContainerSpec {
+            workspace: workspace.path(),
+            cache: None,
+            env: vec!["GREETING=/workspace/input.txt".to_string()],
+            command: "cat \"$GREETING\"; echo \"$CARGO_HOME\" > /workspace/out.txt; echo oops >&2",
+            offline: true,
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let limits = local_limits(Duration::from_secs(30));
+        let mut timings = RunTimings::default();
+        let (status, output) = collect_output(
+            run_container(
+                &executor,
+                spec,
+                &limits,
+                time::Instant::now() + limits.timeout,
+                tx,
+                &mut timings,
+            ),
+            rx,
+        )
+        .await;
+        assert!(status.unwrap().success());
+        assert_eq!(String::from_utf8_lossy(&output), "hello\noops\n");
+        assert_eq!(timings.runs, 1);
+        assert!(!timings.warm);
+        assert!(!BOOT_TIMES.lock().unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(workspace.path().join("out.txt")).unwrap(),
+            format!("{}\n", cargo_home.path().display())
+        );
+    }
+
+    #[actix_web::test]
+    async fn local_executor_kills_jobs_past_their_timeout() {
+        let workspace = TempDir::new().unwrap();
+        let executor = LocalExecutor::new(workspace.path().to_path_buf());
+        let spec = || ContainerSpec {
+            workspace: workspace.path(),
+            cache: None,
+            env: Vec::new(),
+            command: "sleep 30 & wait",
+            offline: false,
+        };
 
-    Ok((prepared.tmp, prepared.output_filename))
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let started = std::time::Instant::now();
+        let limits = local_limits(Duration::from_millis(200));
+        let deadline = time::Instant::now() + limits.timeout;
+        let error = run_container(
+            &executor,
+            spec(),
+            &limits,
+            deadline,
+            tx.clone(),
+            &mut RunTimings::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.killed.map(KillReason::as_str), Some("timeout"));
+
+        // A later step of the same job gets what is left of the deadline.
+        let error = run_container(
+            &executor,
+            spec(),
+            &limits,
+            deadline,
+            tx,
+            &mut RunTimings::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.killed.map(KillReason::as_str), Some("timeout"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
 }
@@ -0,0 +1,513 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use tokio::{process::Command, time};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    hardening::HARDENING,
+    limits::SandboxLimits,
+    mirror::{MIRROR, MIRROR_MOUNT},
+    pool::WarmPool,
+};
+
+const DEFAULT_IMAGE: &str = "wasm_sandbox:latest";
+const DEFAULT_CARGO_CACHE: &str = "cargo-cache";
+const DEFAULT_POOL_MAX_JOBS: u32 = 20;
+
+/// Workspace, environment and shell command of one sandbox job. Commands see
+/// the sandbox layout: the workspace at `/workspace`, the writable cargo home
+/// at `/mnt/cargo`, when it is a layer of its own the shared cache read-only
+/// at `/mnt/cargo-base`, and any crate mirror read-only at `/mnt/mirror`.
+pub struct ContainerSpec<'a> {
+    /// Host directory mounted at `/workspace`.
+    pub workspace: &'a Path,
+    /// Writable cargo layer of the job, see [`CacheScope`]. `None` writes to
+    /// the shared cache.
+    pub cache: Option<&'a CacheLayer>,
+    /// `KEY=value` pairs on top of `CARGO_HOME`.
+    pub env: Vec<String>,
+    pub command: &'a str,
+    /// Run without network access.
+    pub offline: bool,
+}
+
+/// A way of running sandbox jobs. The process a backend starts streams the
+/// job's output and exits with its status; `job` is a unique name the backend
+/// can use to stop the job later.
+pub trait Executor: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Command starting `spec` under `limits`. Whatever the backend reserves
+    /// for the job is given back by [`Executor::finish`], which callers make
+    /// sure to call even when the command never runs.
+    fn command(&self, job: &str, spec: &ContainerSpec<'_>, limits: &SandboxLimits) -> Command;
+
+    /// Command stopping a running job, given its name and the pid of the
+    /// process [`Executor::command`] started.
+    fn kill_command(&self, job: &str, pid: Option<u32>) -> Option<Command>;
+
+    /// Whether `job` went to a pre-started container.
+    fn is_warm(&self, _job: &str) -> bool {
+        false
+    }
+
+    /// Called once `job` exited, was killed or failed to start.
+    fn finish(&self, _job: &str, _success: bool) {}
+
+    /// Starts whatever the backend keeps ready ahead of jobs.
+    fn warm_up(&self) {}
+
+    /// Removes a cargo layer no job needs anymore.
+    fn discard_cache(&self, _layer: &str) {}
+}
+
+/// Which jobs share a writable cargo home. Outside `Shared`, the shared cache
+/// is mounted read-only and seeds a writable layer of the tenant or job, so a
+/// build script cannot poison the registry or target directories of others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheScope {
+    Shared,
+    Tenant,
+    Job,
+}
+
+/// Writable cargo layer of one job.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheLayer {
+    pub name: String,
+    /// Removed once the job is done.
+    pub discard: bool,
+}
+
+impl CacheScope {
+    /// Reads `SOROPG_CARGO_CACHE_SCOPE`: `shared`, `tenant` (the default) or
+    /// `job`.
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        match lookup("SOROPG_CARGO_CACHE_SCOPE")
+            .map(|value| value.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("tenant") => CacheScope::Tenant,
+            Some("shared") => CacheScope::Shared,
+            Some("job") => CacheScope::Job,
+            Some(other) => {
+                warn!(value=%other, "ignoring invalid SOROPG_CARGO_CACHE_SCOPE");
+                CacheScope::Tenant
+            }
+        }
+    }
+
+    /// Layer of a job run for `tenant`, the MCP token hash or
+    /// [`client_key`](crate::queue::client_key) of the client. Jobs without a
+    /// tenant get a layer of their own.
+    pub fn layer(self, tenant: Option<&str>) -> Option<CacheLayer> {
+        match (self, tenant) {
+            (CacheScope::Shared, _) => None,
+            (CacheScope::Tenant, Some(tenant)) => {
+                let digest = hex::encode(Sha256::digest(tenant.as_bytes()));
+                Some(CacheLayer {
+                    name: format!("tenant-{}", &digest[..16]),
+                    discard: false,
+                })
+            }
+            _ => Some(CacheLayer {
+                name: format!("job-{}", Uuid::new_v4().simple()),
+                discard: true,
+            }),
+        }
+    }
+
+    pub fn is_isolated(self) -> bool {
+        self != CacheScope::Shared
+    }
+}
+
+/// The configured cargo cache scope.
+pub static CACHE_SCOPE: Lazy<CacheScope> =
+    Lazy::new(|| CacheScope::from_lookup(|key| env::var(key).ok()));
+
+pub(crate) async fn run_quietly(mut command: Command) -> bool {
+    command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+/// Runs jobs in containers of the sandbox image through a Docker-compatible
+/// CLI.
+pub struct ContainerCli {
+    pub(crate) program: &'static str,
+    image: String,
+    cargo_cache: String,
+}
+
+impl ContainerCli {
+    pub fn new(program: &'static str, image: String, cargo_cache: String) -> Self {
+        ContainerCli {
+            program,
+            image,
+            cargo_cache,
+        }
+    }
+
+    /// Volume of a writable cargo layer.
+    pub(crate) fn layer_volume(&self, layer: &str) -> String {
+        format!("{}-{}", self.cargo_cache, layer)
+    }
+
+    /// `run` arguments up to the image: name, limits, isolation, mounts and
+    /// environment. `workspace` is a host directory and where it is mounted.
+    pub(crate) fn run_args(
+        &self,
+        name: &str,
+        limits: &SandboxLimits,
+        offline: bool,
+        workspace: Option<(&Path, &str)>,
+        cache: Option<&str>,
+        env: &[String],
+    ) -> Vec<String> {
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "--name".to_string(),
+            name.to_string(),
+        ];
+        args.extend(limits.docker_args());
+        args.extend(HARDENING.docker_args());
+        if offline {
+            args.extend([
+                "--network=none".to_string(),
+                "--env=CARGO_NET_OFFLINE=true".to_string(),
+            ]);
+        }
+        let mut volumes = Vec::new();
+        if let Some((workspace, target)) = workspace {
+            volumes.push(format!("{}:{}", workspace.display(), target));
+        }
+        match cache {
+            // The image's `/mnt/cargo` is empty, so a new layer starts empty
+            // and owned by the sandbox user.
+            Some(layer) => volumes.extend([
+                format!("{}:/mnt/cargo", self.layer_volume(layer)),
+                format!("{}:/mnt/cargo-base:ro", self.cargo_cache),
+            ]),
+            None => volumes.push(format!("{}:/mnt/cargo", self.cargo_cache)),
+        }
+        if let Some(Ok(mirror)) = &*MIRROR {
+            volumes.push(format!("{}:{}:ro", mirror.dir.display(), MIRROR_MOUNT));
+        }
+        for volume in volumes {
+            args.extend(["-v".to_string(), volume]);
+        }
+        for var in std::iter::once("CARGO_HOME=/mnt/cargo").chain(env.iter().map(String::as_str)) {
+            args.extend(["-e".to_string(), var.to_string()]);
+        }
+        args.push(self.image.clone());
+        args
+    }
+}
+
+impl Executor for ContainerCli {
+    fn name(&self) -> &'static str {
+        self.program
+    }
+
+    fn command(&self, job: &str, spec: &ContainerSpec<'_>, limits: &SandboxLimits) -> Command {
+        let mut args = self.run_args(
+            job,
+            limits,
+            spec.offline,
+            Some((spec.workspace, "/workspace")),
+            spec.cache.map(|layer| layer.name.as_str()),
+            &spec.env,
+        );
+        args.extend([
+            "bash".to_string(),
+            "-c".to_string(),
+            spec.command.to_string(),
+        ]);
+
+        let mut command = Command::new(self.program);
+        command.args(&args);
+        command
+    }
+
+    fn kill_command(&self, job: &str, _pid: Option<u32>) -> Option<Command> {
+        let mut command = Command::new(self.program);
+        command.args(["kill", job]);
+        Some(command)
+    }
+
+    fn discard_cache(&self, layer: &str) {
+        let program = self.program;
+        let volume = self.layer_volume(layer);
+        tokio::spawn(async move {
+            // A killed job's container may still be on its way out.
+            for _ in 0..5 {
+                let mut command = Command::new(program);
+                command.args(["volume", "rm", "--force", &volume]);
+                if run_quietly(command).await {
+                    return;
+                }
+                time::sleep(Duration::from_secs(1)).await;
+            }
+            warn!(volume=%volume, "failed to remove cargo cache layer");
+        });
+    }
+}
+
+/// Runs jobs as host processes, with the sandbox paths rewritten to host
+/// directories. Only the timeout and output cap apply, so this backend is
+/// meant for development and tests, not for untrusted code.
+pub struct LocalExecutor {
+    cargo_home: PathBuf,
+}
+
+impl LocalExecutor {
+    pub fn new(cargo_home: PathBuf) -> Self {
+        LocalExecutor { cargo_home }
+    }
+
+    fn host_paths(&self, spec: &ContainerSpec<'_>, text: &str) -> String {
+        // Both cargo mounts are the one directory, which skips layer seeding.
+        let cargo_home = self.cargo_home.display().to_string();
+        let text = match &*MIRROR {
+            Some(Ok(mirror)) => text.replace(MIRROR_MOUNT, &mirror.dir.display().to_string()),
+            _ => text.to_string(),
+        };
+        text.replace("/mnt/cargo-base", &cargo_home)
+            .replace("/mnt/cargo", &cargo_home)
+            .replace("/workspace", &spec.workspace.display().to_string())
+    }
+}
+
+impl Executor for LocalExecutor {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn command(&self, _job: &str, spec: &ContainerSpec<'_>, _limits: &SandboxLimits) -> Command {
+        let mut command = Command::new("bash");
+        command
+            .args(["-c", &self.host_paths(spec, spec.command)])
+            .current_dir(spec.workspace)
+            .env("CARGO_HOME", &self.cargo_home)
+            .stdin(Stdio::null())
+            // Its own process group, so a kill reaches cargo and rustc too.
+            .process_group(0);
+        for var in &spec.env {
+            if let Some((key, value)) = var.split_once('=') {
+                command.env(key, self.host_paths(spec, value));
+            }
+        }
+        if spec.offline {
+            command.env("CARGO_NET_OFFLINE", "true");
+        }
+        command
+    }
+
+    fn kill_command(&self, _job: &str, pid: Option<u32>) -> Option<Command> {
+        let mut command = Command::new("kill");
+        command.args(["-KILL", "--", &format!("-{}", pid?)]);
+        Some(command)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Backend {
+    Docker,
+    Podman,
+    Local,
+}
+
+/// Backend settings, read from `SOROPG_EXECUTOR` (`docker`, `podman` or
+/// `local`), `SOROPG_SANDBOX_IMAGE` and `SOROPG_CARGO_CACHE`, the cargo cache
+/// volume of container backends or directory of the local backend. Container
+/// backends keep `SOROPG_WARM_POOL_SIZE` warm containers per network mode,
+/// each reused for up to `SOROPG_WARM_POOL_MAX_JOBS` jobs.
+#[derive(Clone, Debug, PartialEq)]
+struct ExecutorConfig {
+    backend: Backend,
+    image: String,
+    cargo_cache: Option<String>,
+    pool_size: usize,
+    pool_max_jobs: u32,
+}
+
+impl ExecutorConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let setting = |key: &str| {
+            lookup(key)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let backend = match setting("SOROPG_EXECUTOR")
+            .map(|value| value.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("docker") => Backend::Docker,
+            Some("podman") => Backend::Podman,
+            Some("local") => Backend::Local,
+            Some(other) => {
+                warn!(value=%other, "ignoring invalid SOROPG_EXECUTOR");
+                Backend::Docker
+            }
+        };
+        fn parsed<T: std::str::FromStr>(key: &str, value: Option<String>, default: T) -> T {
+            match value.map(|raw| (raw.parse::<T>(), raw)) {
+                None => default,
+                Some((Ok(parsed), _)) => parsed,
+                Some((Err(_), raw)) => {
+                    warn!(key=%key, value=%raw, "ignoring invalid sandbox setting");
+                    default
+                }
+            }
+        }
+        ExecutorConfig {
+            backend,
+            image: setting("SOROPG_SANDBOX_IMAGE").unwrap_or_else(|| DEFAULT_IMAGE.to_string()),
+            cargo_cache: setting("SOROPG_CARGO_CACHE"),
+            pool_size: parsed("SOROPG_WARM_POOL_SIZE", setting("SOROPG_WARM_POOL_SIZE"), 0),
+            pool_max_jobs: parsed(
+                "SOROPG_WARM_POOL_MAX_JOBS",
+                setting("SOROPG_WARM_POOL_MAX_JOBS"),
+                DEFAULT_POOL_MAX_JOBS,
+            ),
+        }
+    }
+
+    fn executor(self) -> Box<dyn Executor> {
+        let container = |program| {
+            let cli = ContainerCli::new(
+                program,
+                self.image.clone(),
+                self.cargo_cache
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_CARGO_CACHE.to_string()),
+            );
+            if self.pool_size > 0 {
+                Box::new(WarmPool::new(cli, self.pool_size, self.pool_max_jobs))
+                    as Box<dyn Executor>
+            } else {
+                Box::new(cli)
+            }
+        };
+        match self.backend {
+            Backend::Docker => container("docker"),
+            Backend::Podman => container("podman"),
+            Backend::Local => {
+                warn!("running sandbox jobs as local processes without isolation");
+                let cargo_home = self
+                    .cargo_cache
+                    .clone()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| env::temp_dir().join("soropg-cargo"));
+                Box::new(LocalExecutor::new(cargo_home))
+            }
+        }
+    }
+}
+
+/// The configured sandbox backend.
+pub static EXECUTOR: Lazy<Box<dyn Executor>> =
+    Lazy::new(|| ExecutorConfig::from_lookup(|key| env::var(key).ok()).executor());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::{limits_for, CommandKind};
+    use std::collections::HashMap;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn container_backends_run_the_configured_image() {
+        let env = HashMap::from([
+            ("SOROPG_EXECUTOR", "Podman"),
+            ("SOROPG_SANDBOX_IMAGE", "registry.local/sandbox:1"),
+            ("SOROPG_CARGO_CACHE", "soropg-cache"),
+        ]);
+        let executor =
+            ExecutorConfig::from_lookup(|key| env.get(key).map(|value| value.to_string()))
+                .executor();
+        assert_eq!(executor.name(), "podman");
+
+        let spec = ContainerSpec {
+            workspace: Path::new("/srv/job"),
+            cache: Some(&CacheLayer {
+                name: "tenant-1".to_string(),
+                discard: false,
+            }),
+            env: vec!["CARGO_TARGET_DIR=/mnt/cargo/target".to_string()],
+            command: "cargo test",
+            offline: true,
+        };
+        let command = executor.command("soropg-1", &spec, limits_for(CommandKind::Test));
+        assert_eq!(command.as_std().get_program(), "podman");
+        let args = args(&command);
+        assert!(!args.iter().any(|arg| arg.starts_with("/tmp:")));
+        for expected in [
+            "--network=none",
+            "/srv/job:/workspace",
+            "soropg-cache-tenant-1:/mnt/cargo",
+            "soropg-cache:/mnt/cargo-base:ro",
+            "CARGO_TARGET_DIR=/mnt/cargo/target",
+        ] {
+            assert!(args.iter().any(|arg| arg == expected), "{}", expected);
+        }
+        assert_eq!(
+            args[args.len() - 4..],
+            ["registry.local/sandbox:1", "bash", "-c", "cargo test"]
+        );
+    }
+
+    #[test]
+    fn cache_scopes_pick_writable_layers() {
+        let scope = |value: &str| CacheScope::from_lookup(|_| Some(value.to_string()));
+        assert_eq!(scope("Shared").layer(Some("10.0.0.1")), None);
+
+        let tenant = scope("").layer(Some("10.0.0.1")).unwrap();
+        assert!(tenant.name.starts_with("tenant-") && !tenant.discard);
+        assert_eq!(scope("tenant").layer(Some("10.0.0.1")), Some(tenant));
+        assert!(scope("tenant").layer(None).unwrap().discard);
+
+        let job = scope("job").layer(Some("10.0.0.1")).unwrap();
+        assert!(job.name.starts_with("job-") && job.discard);
+        assert_ne!(scope("job").layer(Some("10.0.0.1")), Some(job));
+    }
+
+    #[test]
+    fn local_backend_rewrites_sandbox_paths() {
+        let executor = LocalExecutor::new(PathBuf::from("/var/cache/cargo"));
+        let spec = ContainerSpec {
+            workspace: Path::new("/srv/job"),
+            cache: None,
+            env: Vec::new(),
+            command: "cd /workspace/project && ls /mnt/cargo-base /mnt/cargo/bin",
+            offline: false,
+        };
+        let command = executor.command("soropg-1", &spec, limits_for(CommandKind::Build));
+        assert_eq!(
+            args(&command)[1],
+            "cd /srv/job/project && ls /var/cache/cargo /var/cache/cargo/bin"
+        );
+        assert!(args(&executor.kill_command("soropg-1", Some(42)).unwrap())
+            .contains(&"-42".to_string()));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use tempfile::TempDir;
+use tracing::warn;
+
+/// Unprivileged account of the sandbox image, see `Dockerfile.sandbox`.
+const DEFAULT_USER: &str = "1000:1000";
+const TMPFS: &str = "/tmp:rw,nosuid,nodev,size=1g";
+
+/// Container isolation applied to every sandbox job on top of its resource
+/// limits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SandboxHardening {
+    /// Mount the image read-only; only the workspace, the cargo cache and a
+    /// tmpfs at `/tmp` are writable.
+    pub read_only: bool,
+    /// Docker `--user` value, `None` to run as the image's root user.
+    pub user: Option<String>,
+    /// Seccomp profile path, `None` for Docker's default profile.
+    pub seccomp: Option<String>,
+}
+
+impl SandboxHardening {
+    /// Reads `SOROPG_SANDBOX_READ_ONLY`, `SOROPG_SANDBOX_USER` and
+    /// `SOROPG_SANDBOX_SECCOMP`. Invalid values are logged and replaced by the
+    /// default.
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let setting = |key: &str| {
+            lookup(key)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let read_only = match setting("SOROPG_SANDBOX_READ_ONLY").map(|v| v.parse::<bool>()) {
+            None => true,
+            Some(Ok(read_only)) => read_only,
+            Some(Err(_)) => {
+                warn!(
+                    key = "SOROPG_SANDBOX_READ_ONLY",
+                    "ignoring invalid sandbox setting"
+                );
+                true
+            }
+        };
+        let user = match setting("SOROPG_SANDBOX_USER") {
+            None => Some(DEFAULT_USER.to_string()),
+            Some(user) if user == "root" => None,
+            Some(user) if is_valid_user(&user) => Some(user),
+            Some(user) => {
+                warn!(key = "SOROPG_SANDBOX_USER", value=%user, "ignoring invalid sandbox setting");
+                Some(DEFAULT_USER.to_string())
+            }
+        };
+        let seccomp = match setting("SOROPG_SANDBOX_SECCOMP") {
+            Some(profile) if profile == "unconfined" => {
+                warn!(
+                    "refusing to run the sandbox without seccomp, using Docker's default profile"
+                );
+                None
+            }
+            profile => profile,
+        };
+        SandboxHardening {
+            read_only,
+            user,
+            seccomp,
+        }
+    }
+
+    /// `docker run` flags applying this isolation.
+    pub fn docker_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--cap-drop=ALL".to_string(),
+            "--security-opt=no-new-privileges".to_string(),
+            // Tools that write under `$HOME`, such as the stellar CLI config.
+            "--env=HOME=/tmp".to_string(),
+        ];
+        if let Some(profile) = &self.seccomp {
+            args.push(format!("--security-opt=seccomp={}", profile));
+        }
+        if self.read_only {
+            args.push("--read-only".to_string());
+            args.push(format!("--tmpfs={}", TMPFS));
+        }
+        if let Some(user) = &self.user {
+            args.push(format!("--user={}", user));
+        }
+        args
+    }
+}
+
+fn is_valid_user(user: &str) -> bool {
+    let mut parts = user.split(':');
+    let valid = |part: Option<&str>| {
+        part.is_some_and(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+    };
+    valid(parts.next())
+        && parts.next().is_none_or(|group| valid(Some(group)))
+        && parts.next().is_none()
+}
+
+pub static HARDENING: Lazy<SandboxHardening> =
+    Lazy::new(|| SandboxHardening::from_lookup(|key| env::var(key).ok()));
+
+/// Host directory of the job workspaces, `SOROPG_SANDBOX_JOB_DIR` or
+/// `soropg-jobs` in the system temp directory.
+pub static JOB_ROOT: Lazy<PathBuf> = Lazy::new(|| {
+    env::var("SOROPG_SANDBOX_JOB_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join("soropg-jobs"))
+});
+
+/// Creates a job workspace under [`JOB_ROOT`]. The root can be traversed but
+/// not listed by other users, and workspace names are unguessable.
+pub fn job_workspace() -> Result<TempDir, String> {
+    fs::create_dir_all(&*JOB_ROOT).map_err(|e| e.to_string())?;
+    fs::set_permissions(&*JOB_ROOT, fs::Permissions::from_mode(0o711))
+        .map_err(|e| e.to_string())?;
+    tempfile::Builder::new()
+        .prefix("job-")
+        .rand_bytes(24)
+        .tempdir_in(&*JOB_ROOT)
+        .map_err(|e| e.to_string())
+}
+
+/// Opens a host workspace to the unprivileged sandbox user, which does not
+/// own the directories the server created.
+pub fn share_workspace(path: &Path) -> Result<(), String> {
+    if HARDENING.user.is_none() {
+        return Ok(());
+    }
+    let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+    let mode = if metadata.is_dir() { 0o777 } else { 0o666 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|e| e.to_string())?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
+            share_workspace(&entry.map_err(|e| e.to_string())?.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn hardens_by_default_and_rejects_weakening() {
+        let defaults = SandboxHardening::from_lookup(|_| None);
+        assert_eq!(
+            defaults.docker_args(),
+            vec![
+                "--cap-drop=ALL",
+                "--security-opt=no-new-privileges",
+                "--env=HOME=/tmp",
+                "--read-only",
+                "--tmpfs=/tmp:rw,nosuid,nodev,size=1g",
+                "--user=1000:1000",
+            ]
+        );
+
+        let env = HashMap::from([
+            ("SOROPG_SANDBOX_USER", "1000; rm -rf /"),
+            ("SOROPG_SANDBOX_SECCOMP", "unconfined"),
+            ("SOROPG_SANDBOX_READ_ONLY", "false"),
+        ]);
+        let configured =
+            SandboxHardening::from_lookup(|key| env.get(key).map(|value| value.to_string()));
+        assert_eq!(configured.user.as_deref(), Some(DEFAULT_USER));
+        assert_eq!(configured.seccomp, None);
+        assert!(!configured.read_only);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Cursor,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use stellar_xdr::curr::{Limited, Limits, ReadXdr, ScEnvMetaEntry, ScMetaEntry, ScSpecEntry};
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+const SPEC_SECTION: &str = "contractspecv0";
+const ENV_META_SECTION: &str = "contractenvmetav0";
+const META_SECTION: &str = "contractmetav0";
+/// Nesting limit for decoding spec types, well above what contracts use.
+const XDR_DEPTH_LIMIT: u32 = 500;
+
+/// Payload size of one WASM section. Custom sections are named
+/// `custom:{name}`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SectionSize {
+    pub name: String,
+    pub size: usize,
+}
+
+/// Contract spec entries grouped by kind, each in the stellar-xdr JSON form.
+#[derive(Serialize, Default, Debug)]
+pub struct ContractSpec {
+    pub functions: Vec<Value>,
+    pub structs: Vec<Value>,
+    pub unions: Vec<Value>,
+    pub enums: Vec<Value>,
+    pub errors: Vec<Value>,
+    pub events: Vec<Value>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmInspection {
+    pub sha256: String,
+    pub size: usize,
+    pub sections: Vec<SectionSize>,
+    pub spec: ContractSpec,
+    pub env_meta: Vec<Value>,
+    /// `contractmeta!` key/value pairs, such as `rsver` and `rssdkver`.
+    pub meta: BTreeMap<String, String>,
+}
+
+fn read_leb128(bytes: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let mut value: usize = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes
+            .get(*pos)
+            .ok_or_else(|| "Truncated WASM module".to_string())?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Invalid LEB128 length in WASM module".to_string())
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+fn decode_entries<T: ReadXdr>(section: &str, payload: &[u8]) -> Result<Vec<T>, String> {
+    let mut reader = Limited::new(
+        Cursor::new(payload),
+        Limits {
+            depth: XDR_DEPTH_LIMIT,
+            len: payload.len(),
+        },
+    );
+    T::read_xdr_iter(&mut reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid {} section: {}", section, e))
+}
+
+fn to_json(value: &impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Decodes the contract spec and metadata custom sections of a built
+/// contract, and measures every section.
+pub fn inspect_wasm(wasm: &[u8]) -> Result<WasmInspection, String> {
+    if wasm.len() < 8 || &wasm[..4] != WASM_MAGIC {
+        return Err("Not a WASM module".to_string());
+    }
+
+    let mut sections = Vec::new();
+    let mut custom: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_leb128(wasm, &mut pos)?;
+        let end = pos
+            .checked_add(size)
+            .filter(|end| *end <= wasm.len())
+            .ok_or_else(|| "Truncated WASM section".to_string())?;
+
+        if id == 0 {
+            let mut name_pos = pos;
+            let name_len = read_leb128(wasm, &mut name_pos)?;
+            let name_end = name_pos
+                .checked_add(name_len)
+                .filter(|name_end| *name_end <= end)
+                .ok_or_else(|| "Invalid custom section name".to_string())?;
+            let name = String::from_utf8_lossy(&wasm[name_pos..name_end]).to_string();
+            // Sections with the same name are concatenated, as the SDK emits
+            // one per `contractmeta!` entry.
+            custom
+                .entry(name.clone())
+                .or_default()
+                .extend_from_slice(&wasm[name_end..end]);
+            sections.push(SectionSize {
+                name: format!("custom:{}", name),
+                size,
+            });
+        } else {
+            sections.push(SectionSize {
+                name: section_name(id).to_string(),
+                size,
+            });
+        }
+        pos = end;
+    }
+
+    let mut spec = ContractSpec::default();
+    if let Some(payload) = custom.get(SPEC_SECTION) {
+        for entry in decode_entries::<ScSpecEntry>(SPEC_SECTION, payload)? {
+            match &entry {
+                ScSpecEntry::FunctionV0(function) => spec.functions.push(to_json(function)),
+                ScSpecEntry::UdtStructV0(udt) => spec.structs.push(to_json(udt)),
+                ScSpecEntry::UdtUnionV0(udt) => spec.unions.push(to_json(udt)),
+                ScSpecEntry::UdtEnumV0(udt) => spec.enums.push(to_json(udt)),
+                ScSpecEntry::UdtErrorEnumV0(udt) => spec.errors.push(to_json(udt)),
+                ScSpecEntry::EventV0(event) => spec.events.push(to_json(event)),
+            }
+        }
+    }
+
+    let env_meta = match custom.get(ENV_META_SECTION) {
+        Some(payload) => decode_entries::<ScEnvMetaEntry>(ENV_META_SECTION, payload)?
+            .iter()
+            .map(to_json)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut meta = BTreeMap::new();
+    if let Some(payload) = custom.get(META_SECTION) {
+        for ScMetaEntry::ScMetaV0(entry) in decode_entries::<ScMetaEntry>(META_SECTION, payload)? {
+            meta.insert(
+                entry.key.to_utf8_string_lossy(),
+                entry.val.to_utf8_string_lossy(),
+            );
+        }
+    }
+
+    Ok(WasmInspection {
+        sha256: hex::encode(Sha256::digest(wasm)),
+        size: wasm.len(),
+        sections,
+        spec,
+        env_meta,
+        meta,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_xdr::curr::{
+        ScMetaV0, ScSpecFunctionInputV0, ScSpecFunctionV0, ScSpecTypeDef, WriteXdr,
+    };
+
+    fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut body = vec![name.len() as u8];
+        body.extend_from_slice(name.as_bytes());
+        body.extend_from_slice(payload);
+        let mut section = vec![0, body.len() as u8];
+        section.extend(body);
+        section
+    }
+
+    #[test]
+    fn decodes_spec_and_meta_sections() {
+        let function = ScSpecEntry::FunctionV0(ScSpecFunctionV0 {
+            doc: "".try_into().unwrap(),
+            name: "hello".try_into().unwrap(),
+            inputs: vec![ScSpecFunctionInputV0 {
+                doc: "".try_into().unwrap(),
+                name: "to".try_into().unwrap(),
+                type_: ScSpecTypeDef::Symbol,
+            }]
+            .try_into()
+            .unwrap(),
+            outputs: vec![ScSpecTypeDef::U32].try_into().unwrap(),
+        });
+        let meta = |key: &str, val: &str| {
+            ScMetaEntry::ScMetaV0(ScMetaV0 {
+                key: key.try_into().unwrap(),
+                val: val.try_into().unwrap(),
+            })
+            .to_xdr(Limits::none())
+            .unwrap()
+        };
+
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend([1, 1, 0]);
+        wasm.extend(custom_section(
+            SPEC_SECTION,
+            &function.to_xdr(Limits::none()).unwrap(),
+        ));
+        wasm.extend(custom_section(META_SECTION, &meta("rsver", "1.89.0")));
+        wasm.extend(custom_section(META_SECTION, &meta("rssdkver", "23.0.1")));
+
+        let inspection = inspect_wasm(&wasm).unwrap();
+        assert_eq!(inspection.size, wasm.len());
+        assert_eq!(
+            inspection.sections[0],
+            SectionSize {
+                name: "type".to_string(),
+                size: 1
+            }
+        );
+        assert_eq!(inspection.sections[1].name, "custom:contractspecv0");
+        assert_eq!(inspection.spec.functions.len(), 1);
+        assert_eq!(inspection.spec.functions[0]["name"], "hello");
+        assert_eq!(inspection.spec.functions[0]["inputs"][0]["type_"], "symbol");
+        assert_eq!(inspection.meta["rssdkver"], "23.0.1");
+        assert!(inspection.env_meta.is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_modules() {
+        assert!(inspect_wasm(b"not wasm").is_err());
+        assert!(inspect_wasm(b"\0asm\x01\0\0\0\x0a\x7f").is_err());
+    }
+}
@@ -0,0 +1,598 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::limits::CommandKind;
+
+const DEFAULT_JOB_DIR: &str = "job-history";
+const DEFAULT_JOB_HISTORY_MAX: usize = 500;
+
+/// Sandbox jobs, running and finished, shared by every route.
+pub static JOBS: Lazy<JobRegistry> = Lazy::new(|| {
+    let root = env::var("SOROPG_JOB_DIR")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_JOB_DIR.to_string());
+    let max_jobs = env::var("SOROPG_JOB_HISTORY_MAX")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_JOB_HISTORY_MAX);
+    JobRegistry::open(root, max_jobs)
+});
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+/// WASM produced by a build job. The bytes live in the artifact cache.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobArtifact {
+    pub filename: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Response stored for a job that produces a document instead of a stream.
+/// The body lives next to the record as `{id}.result`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobResult {
+    pub status: u16,
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRecord {
+    pub id: String,
+    pub kind: CommandKind,
+    pub input_hash: Option<String>,
+    pub status: JobStatus,
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+    /// Why the job was stopped early: `timeout`, `output-limit`, `cancelled`,
+    /// `disconnected`, or `interrupted` by a server restart.
+    pub killed: Option<String>,
+    pub artifact: Option<JobArtifact>,
+    #[serde(default)]
+    pub optimized_artifact: Option<JobArtifact>,
+    /// Every contract of a Cargo workspace build, `artifact` included.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contracts: Vec<JobArtifact>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<JobResult>,
+    pub log_size: u64,
+}
+
+/// How much of a job's log has been written, and whether more may follow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LogProgress {
+    pub written: u64,
+    pub done: bool,
+}
+
+/// A job's log on disk, plus a way to follow it while the job runs.
+pub struct JobLog {
+    pub record: JobRecord,
+    pub path: PathBuf,
+    pub progress: Option<watch::Receiver<LogProgress>>,
+}
+
+struct ActiveJob {
+    token: CancellationToken,
+    record: Mutex<JobRecord>,
+    log: Mutex<Option<File>>,
+    progress: watch::Sender<LogProgress>,
+    /// Time spent waiting for a sandbox slot, once admitted.
+    queue_wait: Mutex<Option<Duration>>,
+    created: Instant,
+}
+
+/// Job history kept on disk as `{id}.json`, `{id}.log` and `{id}.result`, so finished jobs
+/// can be fetched again after the stream ended or the server restarted.
+/// Only the newest `max_jobs` finished jobs are kept.
+pub struct JobRegistry {
+    root: PathBuf,
+    max_jobs: usize,
+    running: Mutex<HashMap<String, Arc<ActiveJob>>>,
+    /// Finished jobs by creation time, oldest first.
+    finished: Mutex<Vec<(u64, String)>>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn is_valid_id(id: &str) -> bool {
+    Uuid::parse_str(id).is_ok()
+}
+
+/// Reads a job log from `offset` to its current end.
+pub fn read_log(path: &Path, offset: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+impl JobRegistry {
+    pub fn open(root: impl Into<PathBuf>, max_jobs: usize) -> Self {
+        let root = root.into();
+        let mut finished = Vec::new();
+
+        if let Err(e) = fs::create_dir_all(&root) {
+            warn!(dir=%root.display(), error=%e, "failed to create job history directory");
+        }
+        if let Ok(entries) = fs::read_dir(&root) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let record = fs::read(&path)
+                    .ok()
+                    .and_then(|raw| serde_json::from_slice::<JobRecord>(&raw).ok());
+                match record {
+                    Some(mut record) if is_valid_id(&record.id) => {
+                        // Jobs still running when the server stopped never finish.
+                        if !record.status.is_finished() {
+                            record.status = JobStatus::Failed;
+                            record.killed = Some("interrupted".to_string());
+                            if let Ok(raw) = serde_json::to_vec(&record) {
+                                let _ = fs::write(&path, raw);
+                            }
+                        }
+                        finished.push((record.created_at, record.id));
+                    }
+                    _ => {
+                        warn!(path=%path.display(), "discarding unreadable job record");
+                        let _ = fs::remove_file(&path);
+                    }
+                }
+            }
+        }
+        finished.sort();
+
+        let registry = JobRegistry {
+            root,
+            max_jobs,
+            running: Mutex::new(HashMap::new()),
+            finished: Mutex::new(finished),
+        };
+        registry.prune();
+        registry
+    }
+
+    fn entry_path(&self, id: &str, extension: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", id, extension))
+    }
+
+    fn persist(&self, record: &JobRecord) {
+        let path = self.entry_path(&record.id, "json");
+        let result = serde_json::to_vec(record)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| fs::write(&path, raw).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!(job=%record.id, error=%e, "failed to store job record");
+        }
+    }
+
+    /// Registers a new queued job. It stays cancellable until the handle is
+    /// dropped.
+    pub fn start(&'static self, kind: CommandKind, input_hash: Option<String>) -> JobHandle {
+        let id = Uuid::new_v4().to_string();
+        let record = JobRecord {
+            id: id.clone(),
+            kind,
+            input_hash,
+            status: JobStatus::Queued,
+            created_at: now_secs(),
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            exit_code: None,
+            killed: None,
+            artifact: None,
+            optimized_artifact: None,
+            contracts: Vec::new(),
+            result: None,
+            log_size: 0,
+        };
+        let log = match File::create(self.entry_path(&id, "log")) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                warn!(job=%id, error=%e, "failed to create job log");
+                None
+            }
+        };
+        self.persist(&record);
+
+        let (progress, _) = watch::channel(LogProgress {
+            written: 0,
+            done: false,
+        });
+        let job = Arc::new(ActiveJob {
+            token: CancellationToken::new(),
+            record: Mutex::new(record),
+            log: Mutex::new(log),
+            progress,
+            queue_wait: Mutex::new(None),
+            created: Instant::now(),
+        });
+        if let Ok(mut running) = self.running.lock() {
+            running.insert(id.clone(), job.clone());
+        }
+        JobHandle {
+            id,
+            job,
+            registry: self,
+        }
+    }
+
+    /// Requests cancellation of a running job. Returns `false` for unknown or
+    /// finished jobs.
+    pub fn cancel(&self, id: &str) -> bool {
+        let job = match self.running.lock() {
+            Ok(running) => running.get(id).cloned(),
+            Err(_) => None,
+        };
+        match job {
+            Some(job) => {
+                job.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Current record of a running or finished job.
+    pub fn get(&self, id: &str) -> Option<JobRecord> {
+        self.log(id).map(|log| log.record)
+    }
+
+    /// Record and log location of a job. Running jobs also carry a receiver
+    /// that changes whenever the log grows.
+    pub fn log(&self, id: &str) -> Option<JobLog> {
+        if !is_valid_id(id) {
+            return None;
+        }
+        let active = self
+            .running
+            .lock()
+            .ok()
+            .and_then(|running| running.get(id).cloned());
+        let (record, progress) = match active {
+            Some(job) => {
+                let mut record = job.record.lock().ok()?.clone();
+                record.log_size = job.progress.borrow().written;
+                (record, Some(job.progress.subscribe()))
+            }
+            None => {
+                let raw = fs::read(self.entry_path(id, "json")).ok()?;
+                (serde_json::from_slice::<JobRecord>(&raw).ok()?, None)
+            }
+        };
+        Some(JobLog {
+            record,
+            path: self.entry_path(id, "log"),
+            progress,
+        })
+    }
+
+    /// Stored result body of a job, if it recorded one.
+    pub fn read_result(&self, id: &str) -> Option<Vec<u8>> {
+        if !is_valid_id(id) {
+            return None;
+        }
+        fs::read(self.entry_path(id, "result")).ok()
+    }
+
+    fn prune(&self) {
+        let Ok(mut finished) = self.finished.lock() else {
+            return;
+        };
+        let excess = finished.len().saturating_sub(self.max_jobs);
+        for (_, id) in finished.drain(..excess) {
+            for extension in ["json", "log", "result"] {
+                let _ = fs::remove_file(self.entry_path(&id, extension));
+            }
+        }
+    }
+}
+
+/// A queued or running job. Dropping the handle unregisters it; a job that
+/// never reported an outcome is recorded as disconnected.
+pub struct JobHandle {
+    pub id: String,
+    job: Arc<ActiveJob>,
+    registry: &'static JobRegistry,
+}
+
+impl JobHandle {
+    /// Resolves once the job was cancelled through the registry.
+    pub async fn cancelled(&self) {
+        self.job.token.cancelled().await
+    }
+
+    /// Marks the job as running once it got its sandbox slot.
+    pub fn admitted(&self) {
+        if let Ok(mut queue_wait) = self.job.queue_wait.lock() {
+            *queue_wait = Some(self.job.created.elapsed());
+        }
+        if let Ok(mut record) = self.job.record.lock() {
+            record.status = JobStatus::Running;
+            record.started_at = Some(now_secs());
+            self.registry.persist(&record);
+        }
+    }
+
+    /// How long the job waited for its sandbox slot.
+    pub fn queue_wait(&self) -> Duration {
+        self.job
+            .queue_wait
+            .lock()
+            .ok()
+            .and_then(|queue_wait| *queue_wait)
+            .unwrap_or_default()
+    }
+
+    /// Writes the job's log and outcome to its history entry.
+    pub fn recorder(&self) -> JobRecorder {
+        JobRecorder {
+            job: self.job.clone(),
+            registry: self.registry,
+        }
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        self.recorder().finish(
+            JobStatus::Cancelled,
+            None,
+            Some("disconnected"),
+            Duration::ZERO,
+        );
+        if let Ok(mut running) = self.registry.running.lock() {
+            running.remove(&self.id);
+        }
+    }
+}
+
+fn job_artifact(filename: &str, wasm: &[u8]) -> JobArtifact {
+    JobArtifact {
+        filename: filename.to_string(),
+        sha256: hex::encode(Sha256::digest(wasm)),
+        size: wasm.len() as u64,
+    }
+}
+
+#[derive(Clone)]
+pub struct JobRecorder {
+    job: Arc<ActiveJob>,
+    registry: &'static JobRegistry,
+}
+
+impl JobRecorder {
+    pub fn append(&self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let Ok(mut log) = self.job.log.lock() else {
+            return;
+        };
+        if let Some(file) = log.as_mut() {
+            if let Err(e) = file.write_all(bytes) {
+                warn!(error=%e, "failed to append to job log");
+                return;
+            }
+        }
+        self.job.progress.send_modify(|progress| {
+            progress.written += bytes.len() as u64;
+        });
+    }
+
+    pub fn artifact(&self, filename: &str, wasm: &[u8], optimized: bool) {
+        let artifact = job_artifact(filename, wasm);
+        if let Ok(mut record) = self.job.record.lock() {
+            if optimized {
+                record.optimized_artifact = Some(artifact);
+            } else {
+                record.artifact = Some(artifact);
+            }
+        }
+    }
+
+    /// Records one contract of a Cargo workspace build.
+    pub fn contract(&self, filename: &str, wasm: &[u8]) {
+        if let Ok(mut record) = self.job.record.lock() {
+            record.contracts.push(job_artifact(filename, wasm));
+        }
+    }
+
+    /// Stores the response `GET /jobs/{id}/result` serves once the job ends.
+    pub fn result(&self, status: u16, content_type: &str, filename: Option<&str>, body: &[u8]) {
+        let Ok(mut record) = self.job.record.lock() else {
+            return;
+        };
+        if let Err(e) = fs::write(self.registry.entry_path(&record.id, "result"), body) {
+            warn!(job=%record.id, error=%e, "failed to store job result");
+            return;
+        }
+        record.result = Some(JobResult {
+            status,
+            content_type: content_type.to_string(),
+            filename: filename.map(str::to_string),
+            size: body.len() as u64,
+        });
+    }
+
+    /// Records the job's outcome. Only the first outcome counts.
+    pub fn finish(
+        &self,
+        status: JobStatus,
+        exit_code: Option<i32>,
+        killed: Option<&str>,
+        duration: Duration,
+    ) {
+        let Ok(mut record) = self.job.record.lock() else {
+            return;
+        };
+        if record.status.is_finished() {
+            return;
+        }
+        record.status = status;
+        record.exit_code = exit_code;
+        record.killed = killed.map(str::to_string);
+        record.finished_at = Some(now_secs());
+        record.duration_ms = Some(duration.as_millis() as u64);
+        record.log_size = self.job.progress.borrow().written;
+        if let Ok(mut log) = self.job.log.lock() {
+            *log = None;
+        }
+        self.registry.persist(&record);
+        if let Ok(mut finished) = self.registry.finished.lock() {
+            finished.push((record.created_at, record.id.clone()));
+        }
+        drop(record);
+
+        self.registry.prune();
+        self.job
+            .progress
+            .send_modify(|progress| progress.done = true);
+    }
+}
+
+/// Registry backed by a throwaway directory, for tests.
+#[cfg(test)]
+pub fn scratch_registry() -> &'static JobRegistry {
+    let dir = tempfile::TempDir::new().unwrap().keep();
+    Box::leak(Box::new(JobRegistry::open(dir, 16)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn cancel_reaches_running_job_only() {
+        let jobs = scratch_registry();
+        let job = jobs.start(CommandKind::Build, None);
+        let id = job.id.clone();
+        assert!(jobs.cancel(&id));
+        job.cancelled().await;
+
+        drop(job);
+        assert!(!jobs.cancel(&id));
+    }
+
+    #[test]
+    fn finished_jobs_survive_restarts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let jobs: &'static JobRegistry = Box::leak(Box::new(JobRegistry::open(dir.path(), 2)));
+
+        let job = jobs.start(CommandKind::Build, Some("abc".to_string()));
+        let id = job.id.clone();
+        job.admitted();
+        let recorder = job.recorder();
+        recorder.append(b"Compiling\n");
+        recorder.append(b"Finished\n");
+        recorder.artifact("hello.wasm", b"\0asm", false);
+        recorder.finish(JobStatus::Succeeded, Some(0), None, Duration::from_secs(2));
+        drop(job);
+
+        let interrupted = jobs.start(CommandKind::Test, None);
+        std::mem::forget(interrupted);
+
+        let reopened = JobRegistry::open(dir.path(), 2);
+        let log = reopened.log(&id).expect("stored job");
+        assert_eq!(log.record.status, JobStatus::Succeeded);
+        assert_eq!(log.record.log_size, 19);
+        assert_eq!(log.record.artifact.unwrap().size, 4);
+        assert!(log.progress.is_none());
+        assert_eq!(read_log(&log.path, 10).unwrap(), b"Finished\n");
+
+        let statuses: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| {
+                serde_json::from_slice::<JobRecord>(&fs::read(entry.path()).unwrap()).ok()
+            })
+            .map(|record| (record.status, record.killed))
+            .collect();
+        assert!(statuses.contains(&(JobStatus::Failed, Some("interrupted".to_string()))));
+        assert!(reopened.get("../escape").is_none());
+    }
+
+    #[test]
+    fn results_are_stored_and_pruned() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let jobs: &'static JobRegistry = Box::leak(Box::new(JobRegistry::open(dir.path(), 1)));
+
+        let job = jobs.start(CommandKind::Audit, None);
+        let id = job.id.clone();
+        let recorder = job.recorder();
+        recorder.result(200, "application/sarif+json", None, b"{}");
+        recorder.finish(JobStatus::Succeeded, Some(0), None, Duration::ZERO);
+        drop(job);
+
+        let result = jobs.get(&id).unwrap().result.unwrap();
+        assert_eq!(result.status, 200);
+        assert_eq!(result.size, 2);
+        assert_eq!(jobs.read_result(&id).unwrap(), b"{}");
+
+        let newer = jobs.start(CommandKind::Audit, None);
+        newer
+            .recorder()
+            .finish(JobStatus::Succeeded, Some(0), None, Duration::ZERO);
+        drop(newer);
+        assert!(jobs.read_result(&id).is_none());
+    }
+}
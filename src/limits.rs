@@ -0,0 +1,200 @@
+use std::{env, time::Duration};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Kind of sandbox job; each kind has its own resource limits.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandKind {
+    Build,
+    Test,
+    Audit,
+    Interface,
+}
+
+impl CommandKind {
+    fn env_prefix(self) -> &'static str {
+        match self {
+            CommandKind::Build => "SOROPG_BUILD",
+            CommandKind::Test => "SOROPG_TEST",
+            CommandKind::Audit => "SOROPG_AUDIT",
+            CommandKind::Interface => "SOROPG_INTERFACE",
+        }
+    }
+}
+
+/// Resource limits applied to one sandbox container.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SandboxLimits {
+    /// Docker `--memory` value, e.g. `2G`.
+    pub memory: String,
+    /// Docker `--cpus` value.
+    pub cpus: f64,
+    /// Docker `--pids-limit` value.
+    pub pids: u32,
+    /// Wall-clock time before the container is killed.
+    pub timeout: Duration,
+    /// Combined stdout and stderr bytes before the container is killed.
+    pub max_output_bytes: usize,
+    /// Sandbox slots a job of this kind occupies in the queue.
+    pub weight: u32,
+    /// Fetch dependencies in a separate container, then run the job with
+    /// `--network none`.
+    pub offline: bool,
+}
+
+impl SandboxLimits {
+    fn defaults(kind: CommandKind) -> Self {
+        // Interface lookups query the network's RPC, so they stay online.
+        let (timeout_secs, output_mb, weight, offline) = match kind {
+            CommandKind::Build => (300, 16, 1, true),
+            CommandKind::Test => (300, 16, 1, true),
+            CommandKind::Audit => (600, 32, 2, true),
+            CommandKind::Interface => (60, 1, 1, false),
+        };
+        SandboxLimits {
+            memory: "2G".to_string(),
+            cpus: 2.0,
+            pids: 512,
+            timeout: Duration::from_secs(timeout_secs),
+            max_output_bytes: output_mb * 1024 * 1024,
+            weight,
+            offline,
+        }
+    }
+
+    /// Reads `SOROPG_{KIND}_{SETTING}`, then `SOROPG_SANDBOX_{SETTING}`, for
+    /// each setting. Invalid values are logged and replaced by the default.
+    fn from_lookup(kind: CommandKind, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = SandboxLimits::defaults(kind);
+        let setting = |name: &str| {
+            [kind.env_prefix(), "SOROPG_SANDBOX"]
+                .iter()
+                .find_map(|prefix| {
+                    let key = format!("{}_{}", prefix, name);
+                    lookup(&key)
+                        .filter(|value| !value.trim().is_empty())
+                        .map(|value| (key, value.trim().to_string()))
+                })
+        };
+        fn parsed<T: std::str::FromStr>(
+            value: Option<(String, String)>,
+            valid: impl Fn(&T) -> bool,
+        ) -> Option<T> {
+            let (key, raw) = value?;
+            match raw.parse::<T>() {
+                Ok(parsed) if valid(&parsed) => Some(parsed),
+                _ => {
+                    warn!(key=%key, value=%raw, "ignoring invalid sandbox limit");
+                    None
+                }
+            }
+        }
+
+        let memory = match setting("MEMORY") {
+            Some((_, value)) if is_valid_memory(&value) => value,
+            Some((key, value)) => {
+                warn!(key=%key, value=%value, "ignoring invalid sandbox limit");
+                defaults.memory
+            }
+            None => defaults.memory,
+        };
+        SandboxLimits {
+            memory,
+            cpus: parsed(setting("CPUS"), |cpus: &f64| *cpus > 0.0).unwrap_or(defaults.cpus),
+            pids: parsed(setting("PIDS"), |pids: &u32| *pids > 0).unwrap_or(defaults.pids),
+            timeout: parsed(setting("TIMEOUT_SECS"), |secs: &u64| *secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            max_output_bytes: parsed(setting("OUTPUT_MB"), |mb: &usize| *mb > 0)
+                .map(|mb| mb.saturating_mul(1024 * 1024))
+                .unwrap_or(defaults.max_output_bytes),
+            weight: parsed(setting("WEIGHT"), |weight: &u32| *weight > 0)
+                .unwrap_or(defaults.weight),
+            offline: parsed(setting("OFFLINE"), |_: &bool| true).unwrap_or(defaults.offline),
+        }
+    }
+
+    /// `docker run` flags enforcing these limits.
+    pub fn docker_args(&self) -> Vec<String> {
+        vec![
+            format!("--memory={}", self.memory),
+            format!("--cpus={}", self.cpus),
+            format!("--pids-limit={}", self.pids),
+        ]
+    }
+}
+
+fn is_valid_memory(value: &str) -> bool {
+    let digits = value.trim_end_matches(['b', 'k', 'm', 'g', 'B', 'K', 'M', 'G']);
+    !digits.is_empty()
+        && value.len() - digits.len() <= 1
+        && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+struct LimitTable {
+    build: SandboxLimits,
+    test: SandboxLimits,
+    audit: SandboxLimits,
+    interface: SandboxLimits,
+}
+
+static LIMITS: Lazy<LimitTable> = Lazy::new(|| {
+    let load = |kind| SandboxLimits::from_lookup(kind, |key| env::var(key).ok());
+    LimitTable {
+        build: load(CommandKind::Build),
+        test: load(CommandKind::Test),
+        audit: load(CommandKind::Audit),
+        interface: load(CommandKind::Interface),
+    }
+});
+
+/// Configured limits for a kind of sandbox job.
+pub fn limits_for(kind: CommandKind) -> &'static SandboxLimits {
+    match kind {
+        CommandKind::Build => &LIMITS.build,
+        CommandKind::Test => &LIMITS.test,
+        CommandKind::Audit => &LIMITS.audit,
+        CommandKind::Interface => &LIMITS.interface,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn kind_settings_override_sandbox_settings() {
+        let env = HashMap::from([
+            ("SOROPG_SANDBOX_MEMORY", "1G"),
+            ("SOROPG_TEST_MEMORY", "512m"),
+            ("SOROPG_TEST_TIMEOUT_SECS", "30"),
+            ("SOROPG_SANDBOX_PIDS", "not-a-number"),
+            ("SOROPG_BUILD_MEMORY", "2G; rm -rf /"),
+            ("SOROPG_TEST_OFFLINE", "false"),
+        ]);
+        let lookup = |key: &str| env.get(key).map(|value| value.to_string());
+
+        let test = SandboxLimits::from_lookup(CommandKind::Test, lookup);
+        assert_eq!(test.memory, "512m");
+        assert_eq!(test.timeout, Duration::from_secs(30));
+        assert_eq!(test.pids, 512);
+        assert!(!test.offline);
+
+        let build = SandboxLimits::from_lookup(CommandKind::Build, lookup);
+        assert_eq!(build.memory, "2G");
+        assert_eq!(build.timeout, Duration::from_secs(300));
+        assert!(build.offline);
+
+        let audit = SandboxLimits::from_lookup(CommandKind::Audit, lookup);
+        assert_eq!(audit.memory, "1G");
+        assert!(audit.offline);
+        assert_eq!(
+            audit.docker_args(),
+            vec!["--memory=1G", "--cpus=2", "--pids-limit=512"]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use std::{env, ops::Range};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::json;
+use tracing::warn;
+
+/// What the content lint does with its findings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintMode {
+    Off,
+    /// Report violations as warnings and run the job anyway.
+    Warn,
+    /// Reject jobs with violations.
+    Deny,
+}
+
+/// A pattern the content lint flags in submitted Rust sources.
+struct LintRule {
+    id: &'static str,
+    patterns: &'static [&'static str],
+    message: &'static str,
+}
+
+const RULES: &[LintRule] = &[
+    LintRule {
+        id: "process",
+        patterns: &["std::process::", "tokio::process::", "Command::new"],
+        message: "spawns host processes",
+    },
+    LintRule {
+        id: "filesystem",
+        patterns: &["std::fs::", "tokio::fs::"],
+        message: "accesses the filesystem",
+    },
+    LintRule {
+        id: "network",
+        patterns: &["std::net::", "reqwest::", "hyper::", "actix_web::"],
+        message: "opens network connections",
+    },
+    LintRule {
+        id: "environment",
+        patterns: &["std::env::", "env!", "option_env!"],
+        message: "reads the build environment",
+    },
+    LintRule {
+        id: "include",
+        patterns: &["include_str!", "include_bytes!"],
+        message: "embeds files from outside the source tree",
+    },
+    LintRule {
+        id: "ffi",
+        patterns: &["extern \"", "#[no_mangle]"],
+        message: "declares foreign functions or symbols",
+    },
+    LintRule {
+        id: "asm",
+        patterns: &["asm!", "global_asm!"],
+        message: "uses inline assembly",
+    },
+    LintRule {
+        id: "unsafe",
+        patterns: &["unsafe"],
+        message: "uses unsafe code",
+    },
+];
+
+const CONTENT_LINT: &str = "content-lint";
+
+/// Build scripts run arbitrary code on the build host, so they are flagged as
+/// a whole rather than by pattern.
+const BUILD_SCRIPT_RULE: &str = "build-script";
+
+/// Lint settings, read from `SOROPG_CONTENT_LINT` (`off`, `warn` or `deny`)
+/// and `SOROPG_CONTENT_LINT_ALLOW`, a comma-separated list of rule ids to skip.
+pub struct LintConfig {
+    pub mode: LintMode,
+    allow: Vec<String>,
+}
+
+impl LintConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let mode = match lookup("SOROPG_CONTENT_LINT")
+            .map(|value| value.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("warn") => LintMode::Warn,
+            Some("off") => LintMode::Off,
+            Some("deny") => LintMode::Deny,
+            Some(other) => {
+                warn!(value=%other, "ignoring invalid SOROPG_CONTENT_LINT");
+                LintMode::Warn
+            }
+        };
+        let allow = lookup("SOROPG_CONTENT_LINT_ALLOW")
+            .unwrap_or_default()
+            .split(',')
+            .map(|rule| rule.trim().to_string())
+            .filter(|rule| !rule.is_empty())
+            .collect();
+        LintConfig { mode, allow }
+    }
+
+    fn allows(&self, rule: &str) -> bool {
+        self.allow.iter().any(|allowed| allowed == rule)
+    }
+}
+
+pub static LINT_CONFIG: Lazy<LintConfig> =
+    Lazy::new(|| LintConfig::from_lookup(|key| env::var(key).ok()));
+
+/// One lint finding, of the content lint or another check of the submitted
+/// files. Locations are 1-based; columns count characters.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LintViolation {
+    /// Check that reported the finding, the prefix of its diagnostic code.
+    #[serde(skip)]
+    tool: &'static str,
+    pub path: String,
+    pub line: u32,
+    pub column: u32,
+    pub rule: &'static str,
+    pub message: String,
+    #[serde(skip)]
+    byte_start: usize,
+    #[serde(skip)]
+    byte_end: usize,
+}
+
+impl LintViolation {
+    /// A finding of `tool` covering `bytes` of the file `path`.
+    pub(crate) fn at(
+        tool: &'static str,
+        path: &str,
+        content: &str,
+        bytes: Range<usize>,
+        rule: &'static str,
+        message: String,
+    ) -> Self {
+        let (line, column) = location(content, bytes.start);
+        LintViolation {
+            tool,
+            path: path.to_string(),
+            line,
+            column,
+            rule,
+            message,
+            byte_start: bytes.start,
+            byte_end: bytes.end,
+        }
+    }
+
+    fn render(&self, level: &str) -> String {
+        format!(
+            "{}[{}::{}]: {}\n --> {}:{}:{}\n",
+            level, self.tool, self.rule, self.message, self.path, self.line, self.column
+        )
+    }
+
+    /// The violation as a `cargo --message-format=json` compiler message, so
+    /// it reaches clients through the same diagnostics path as rustc output.
+    pub fn cargo_message(&self, level: &str) -> String {
+        let end_column = self.column as usize + (self.byte_end - self.byte_start);
+        json!({
+            "reason": "compiler-message",
+            "message": {
+                "message": self.message,
+                "code": { "code": format!("{}::{}", self.tool, self.rule) },
+                "level": level,
+                "spans": [{
+                    "file_name": self.path,
+                    "byte_start": self.byte_start,
+                    "byte_end": self.byte_end,
+                    "line_start": self.line,
+                    "line_end": self.line,
+                    "column_start": self.column,
+                    "column_end": end_column,
+                    "is_primary": true,
+                    "label": null,
+                    "suggested_replacement": null,
+                }],
+                "children": [],
+                "rendered": self.render(level),
+            }
+        })
+        .to_string()
+    }
+}
+
+/// Error returned for a job a check such as the content lint rejects in
+/// `deny` mode.
+pub fn denied_message(check: &str, violations: &[LintViolation]) -> String {
+    let mut message = format!(
+        "{} rejected the submission with {} violation(s)",
+        check,
+        violations.len()
+    );
+    for violation in violations {
+        message.push_str(&format!(
+            "\n{}:{}:{}: [{}] {}",
+            violation.path, violation.line, violation.column, violation.rule, violation.message
+        ));
+    }
+    message
+}
+
+/// Lints submitted files under the configured rules. Only Rust sources are
+/// inspected, with comments and string contents ignored.
+pub fn lint_files<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<LintViolation> {
+    lint_with(&LINT_CONFIG, files)
+}
+
+fn lint_with<'a>(
+    config: &LintConfig,
+    files: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<LintViolation> {
+    if config.mode == LintMode::Off {
+        return Vec::new();
+    }
+    let mut violations = Vec::new();
+    for (path, content) in files {
+        if !path.ends_with(".rs") {
+            continue;
+        }
+        if (path == "build.rs" || path.ends_with("/build.rs")) && !config.allows(BUILD_SCRIPT_RULE)
+        {
+            violations.push(LintViolation::at(
+                CONTENT_LINT,
+                path,
+                content,
+                0..0,
+                BUILD_SCRIPT_RULE,
+                "build scripts run arbitrary code at build time".to_string(),
+            ));
+        }
+        let code = mask_comments_and_strings(content);
+        for rule in RULES.iter().filter(|rule| !config.allows(rule.id)) {
+            for pattern in rule.patterns {
+                for (start, _) in code.match_indices(pattern) {
+                    if !is_token_match(&code, start, pattern) {
+                        continue;
+                    }
+                    violations.push(LintViolation::at(
+                        CONTENT_LINT,
+                        path,
+                        content,
+                        start..start + pattern.len(),
+                        rule.id,
+                        format!(
+                            "`{}` {}",
+                            pattern.trim_end_matches(['"', ' ']),
+                            rule.message
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    violations.sort_by(|a, b| {
+        (&a.path, a.line, a.column, a.rule).cmp(&(&b.path, b.line, b.column, b.rule))
+    });
+    violations
+}
+
+fn is_ident_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// Rejects matches inside longer identifiers, e.g. `unsafe` in `unsafe_op`.
+fn is_token_match(code: &str, start: usize, pattern: &str) -> bool {
+    let bytes = code.as_bytes();
+    let end = start + pattern.len();
+    let starts_ident = is_ident_byte(pattern.as_bytes()[0]);
+    let ends_ident = is_ident_byte(pattern.as_bytes()[pattern.len() - 1]);
+    let joins_before = starts_ident && start > 0 && is_ident_byte(bytes[start - 1]);
+    let joins_after = ends_ident && end < bytes.len() && is_ident_byte(bytes[end]);
+    !joins_before && !joins_after
+}
+
+fn location(content: &str, offset: usize) -> (u32, u32) {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line as u32, column as u32)
+}
+
+/// Blanks out comments and the contents of string and character literals,
+/// byte for byte, so pattern offsets still index into the original source.
+fn mask_comments_and_strings(content: &str) -> String {
+    let bytes = content.as_bytes();
+    let mut masked = bytes.to_vec();
+    fn blank(masked: &mut [u8], from: usize, to: usize) {
+        for byte in &mut masked[from..to] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end = content[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                blank(&mut masked, i, end);
+                i = end;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut depth = 0;
+                let mut j = i;
+                while j < bytes.len() {
+                    if bytes[j..].starts_with(b"/*") {
+                        depth += 1;
+                        j += 2;
+                    } else if bytes[j..].starts_with(b"*/") {
+                        depth -= 1;
+                        j += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        j += 1;
+                    }
+                }
+                blank(&mut masked, i, j);
+                i = j;
+            }
+            b'r' if (i == 0 || !is_ident_byte(bytes[i - 1]))
+                && matches!(bytes.get(i + 1), Some(b'"') | Some(b'#')) =>
+            {
+                let hashes = bytes[i + 1..].iter().take_while(|&&b| b == b'#').count();
+                let open = i + 1 + hashes;
+                if bytes.get(open) != Some(&b'"') {
+                    i += 1;
+                    continue;
+                }
+                let close = format!("\"{}", "#".repeat(hashes));
+                let end = content[open + 1..]
+                    .find(&close)
+                    .map_or(bytes.len(), |n| open + 1 + n);
+                blank(&mut masked, open + 1, end);
+                i = (end + close.len()).min(bytes.len());
+            }
+            b'"' => {
+                let mut j = i + 1;
+                while j < bytes.len() && bytes[j] != b'"' {
+                    j += if bytes[j] == b'\\' { 2 } else { 1 };
+                }
+                let end = j.min(bytes.len());
+                blank(&mut masked, i + 1, end);
+                i = end + 1;
+            }
+            b'\'' => {
+                // Character literals; a quote without a closing one is a
+                // lifetime or label and is left alone.
+                let len = match bytes.get(i + 1) {
+                    Some(b'\\') => content[i + 2..].find('\'').map(|n| n + 2),
+                    Some(_) => content[i + 1..]
+                        .chars()
+                        .next()
+                        .map(char::len_utf8)
+                        .filter(|&n| bytes.get(i + 1 + n) == Some(&b'\'')),
+                    None => None,
+                };
+                match len {
+                    Some(len) => {
+                        blank(&mut masked, i + 1, i + 1 + len);
+                        i += len + 2;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    // Only whole multi-byte characters inside comments or literals are
+    // replaced, so the result is still valid UTF-8.
+    String::from_utf8(masked).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(mode: &str, allow: &str) -> LintConfig {
+        LintConfig::from_lookup(|key| match key {
+            "SOROPG_CONTENT_LINT" => Some(mode.to_string()),
+            "SOROPG_CONTENT_LINT_ALLOW" => Some(allow.to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn reports_exact_locations_outside_comments_and_strings() {
+        let source = "//! Never uses unsafe or env! here.\nuse soroban_sdk::{contract, symbol_short};\n/* std::fs::read */\nfn a() { let _ = \"std::process::exit\"; }\nfn b() { let s = 'é'; unsafe { core::hint::unreachable_unchecked() } }\nfn c() { let unsafe_flag = env!(\"HOME\"); }\n";
+        let violations = lint_with(&config("warn", ""), [("src/lib.rs", source)]);
+        let found: Vec<_> = violations
+            .iter()
+            .map(|v| (v.rule, v.line, v.column))
+            .collect();
+        assert_eq!(found, vec![("unsafe", 5, 23), ("environment", 6, 28)]);
+        assert!(violations[0]
+            .cargo_message("warning")
+            .contains("\"file_name\":\"src/lib.rs\""));
+    }
+
+    #[test]
+    fn honors_mode_and_allow_list() {
+        let files = [
+            (
+                "build.rs",
+                "fn main() { std::process::Command::new(\"sh\"); }",
+            ),
+            ("README.md", "unsafe"),
+        ];
+        assert!(lint_with(&config("off", ""), files).is_empty());
+
+        let rules: Vec<_> = lint_with(&config("deny", "process"), files)
+            .into_iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(rules, vec!["build-script"]);
+        assert_eq!(config("deny", "").mode, LintMode::Deny);
+        assert_eq!(config("bogus", "").mode, LintMode::Warn);
+    }
+}
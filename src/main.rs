@@ -12,10 +12,26 @@
                         '\ /'
                           '
 */
+mod artifacts;
+mod attestation;
+mod bindings;
+mod diagnostics;
 mod docker;
+mod executor;
+mod hardening;
+mod inspect;
+mod jobs;
+mod limits;
+mod lint;
+mod manifest;
+mod mirror;
 mod models;
+mod pool;
+mod queue;
 mod routes;
-mod semaphore;
+mod scout;
+mod test_results;
+mod workspace;
 
 use actix_files::Files;
 use actix_web::{middleware::Logger, web, App, HttpServer};
@@ -42,12 +58,49 @@ async fn main() -> std::io::Result<()> {
         }
     }
 
+    // --build-mirror [ALLOWLIST] vendors the allowlisted crates into
+    // SOROPG_CRATE_MIRROR and exits.
+    if let Some(i) = args.iter().position(|arg| arg == "--build-mirror") {
+        let Some(dir) = env::var("SOROPG_CRATE_MIRROR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+        else {
+            eprintln!("Set SOROPG_CRATE_MIRROR to the directory of the crate mirror");
+            std::process::exit(1);
+        };
+        let allowlist = args
+            .get(i + 1)
+            .filter(|arg| !arg.starts_with("--"))
+            .map(String::as_str)
+            .unwrap_or("frontend/templates/Cargo.toml");
+        match mirror::build(dir.as_ref(), allowlist.as_ref()) {
+            Ok(()) => {
+                println!("Crate mirror written to {}", dir);
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(Err(e)) = &*mirror::MIRROR {
+        eprintln!("Crate mirror unavailable, sandbox jobs will fail: {}", e);
+    }
+    if !manifest::POLICY.enforces() {
+        eprintln!(
+            "Cargo.toml policy only reports findings; set SOROPG_MANIFEST_POLICY=deny and SOROPG_MANIFEST_ALLOWLIST to block crates outside an allowlist"
+        );
+    }
+
     println!("Starting server at http://127.0.0.1:{}", port);
 
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    executor::EXECUTOR.warm_up();
+
     let mcp_state = web::Data::new(routes::mcp::McpState::default());
     let ai_state = web::Data::new(routes::ai::AiState::default());
 
@@ -59,8 +112,17 @@ async fn main() -> std::io::Result<()> {
             .service(routes::compile::compile)
             .service(routes::test::test)
             .service(routes::scout_audit::scout_audit)
+            .service(routes::scout_audit::scout_audit_sarif)
+            .service(routes::bindings::bindings)
             .service(routes::interface::interface)
             .service(routes::friendbot::friendbot)
+            .service(routes::artifacts::artifact)
+            .service(routes::inspect::inspect_artifact)
+            .service(routes::inspect::inspect_upload)
+            .service(routes::jobs::get_job)
+            .service(routes::jobs::job_log)
+            .service(routes::jobs::job_result)
+            .service(routes::jobs::cancel_job)
             .service(routes::mcp::browser_heartbeat)
             .service(routes::mcp::browser_changes)
             .service(routes::mcp::list_projects)
@@ -0,0 +1,736 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    ops::Range,
+    path::{Component, Path},
+};
+
+use once_cell::sync::Lazy;
+use semver::{Comparator, Op, Version, VersionReq};
+use toml_edit::{DocumentMut, ImDocument, Item, TableLike};
+use tracing::warn;
+
+use crate::lint::{LintMode, LintViolation};
+
+const MANIFEST_POLICY: &str = "manifest-policy";
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// A value a required release profile setting may take.
+enum Setting {
+    Str(&'static str),
+    Bool(bool),
+    Int(i64),
+}
+
+/// Release profile settings a contract project's root manifest must have,
+/// with the values accepted for each.
+const RELEASE_PROFILE: &[(&str, &[Setting])] = &[
+    ("opt-level", &[Setting::Str("z"), Setting::Str("s")]),
+    ("overflow-checks", &[Setting::Bool(true)]),
+    ("panic", &[Setting::Str("abort")]),
+    ("codegen-units", &[Setting::Int(1)]),
+    ("lto", &[Setting::Bool(true), Setting::Str("fat")]),
+];
+
+impl Setting {
+    fn matches(&self, item: &Item) -> bool {
+        match self {
+            Setting::Str(expected) => item.as_str() == Some(*expected),
+            Setting::Bool(expected) => item.as_bool() == Some(*expected),
+            Setting::Int(expected) => item.as_integer() == Some(*expected),
+        }
+    }
+
+    fn render(&self) -> String {
+        match self {
+            Setting::Str(value) => format!("\"{}\"", value),
+            Setting::Bool(value) => value.to_string(),
+            Setting::Int(value) => value.to_string(),
+        }
+    }
+}
+
+/// Manifest policy settings, read from `SOROPG_MANIFEST_POLICY` (`off`,
+/// `warn` or `deny`) and `SOROPG_MANIFEST_ALLOWLIST`, a TOML file whose
+/// `[crates]` table maps the crates projects may depend on to the version
+/// ranges allowed for them.
+pub struct ManifestPolicy {
+    pub mode: LintMode,
+    /// `None` allows any crate.
+    crates: Option<BTreeMap<String, VersionReq>>,
+}
+
+impl ManifestPolicy {
+    fn from_lookup(
+        lookup: impl Fn(&str) -> Option<String>,
+        read: impl Fn(&str) -> Result<String, String>,
+    ) -> Self {
+        let mode = match lookup("SOROPG_MANIFEST_POLICY")
+            .map(|value| value.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("warn") => LintMode::Warn,
+            Some("off") => LintMode::Off,
+            Some("deny") => LintMode::Deny,
+            Some(other) => {
+                warn!(value=%other, "ignoring invalid SOROPG_MANIFEST_POLICY");
+                LintMode::Warn
+            }
+        };
+        let crates = lookup("SOROPG_MANIFEST_ALLOWLIST")
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| {
+                read(path.trim())
+                    .and_then(|raw| parse_allowlist(&raw))
+                    .unwrap_or_else(|e| {
+                        // An unreadable allowlist allows nothing rather than
+                        // everything.
+                        warn!(path=%path, error=%e, "failed to load the crate allowlist");
+                        BTreeMap::new()
+                    })
+            });
+        ManifestPolicy { mode, crates }
+    }
+
+    /// Whether the policy blocks crates outside an allowlist. The default,
+    /// `warn` without an allowlist, only reports findings.
+    pub fn enforces(&self) -> bool {
+        self.mode == LintMode::Deny && self.crates.is_some()
+    }
+}
+
+fn parse_allowlist(raw: &str) -> Result<BTreeMap<String, VersionReq>, String> {
+    let table: toml::Table = raw.parse().map_err(|e| format!("{}", e))?;
+    let Some(crates) = table.get("crates").and_then(toml::Value::as_table) else {
+        return Err("missing [crates] table".to_string());
+    };
+    crates
+        .iter()
+        .map(|(name, range)| {
+            let range = range
+                .as_str()
+                .ok_or_else(|| format!("{}: version range must be a string", name))?;
+            let range = VersionReq::parse(range).map_err(|e| format!("{}: {}", name, e))?;
+            Ok((name.clone(), range))
+        })
+        .collect()
+}
+
+pub static POLICY: Lazy<ManifestPolicy> = Lazy::new(|| {
+    ManifestPolicy::from_lookup(
+        |key| env::var(key).ok(),
+        |path| fs::read_to_string(path).map_err(|e| e.to_string()),
+    )
+});
+
+/// Checks every `Cargo.toml` of a submission against the configured policy.
+pub fn check_manifests(files: &HashMap<String, String>) -> Vec<LintViolation> {
+    check_with(&POLICY, files)
+}
+
+fn check_with(policy: &ManifestPolicy, files: &HashMap<String, String>) -> Vec<LintViolation> {
+    if policy.mode == LintMode::Off {
+        return Vec::new();
+    }
+    let mut violations = Vec::new();
+    for (path, content) in files {
+        if path != "Cargo.toml" && !path.ends_with("/Cargo.toml") {
+            continue;
+        }
+        let mut check = ManifestCheck {
+            policy,
+            files,
+            path,
+            content,
+            dir: path.strip_suffix("Cargo.toml").unwrap_or_default(),
+            violations: &mut violations,
+        };
+        match ImDocument::parse(content.as_str()) {
+            Ok(document) => check.document(document.as_table()),
+            Err(e) => check.report(
+                e.span().unwrap_or(0..0),
+                "syntax",
+                format!("invalid manifest: {}", e.message()),
+            ),
+        }
+    }
+    violations.sort_by(|a, b| {
+        (&a.path, a.line, a.column, a.rule).cmp(&(&b.path, b.line, b.column, b.rule))
+    });
+    violations
+}
+
+struct ManifestCheck<'a> {
+    policy: &'a ManifestPolicy,
+    files: &'a HashMap<String, String>,
+    path: &'a str,
+    content: &'a str,
+    /// Directory of the manifest relative to the project root, with a
+    /// trailing slash unless it is the root.
+    dir: &'a str,
+    violations: &'a mut Vec<LintViolation>,
+}
+
+/// Span of `key` in `table`, falling back to the value and then the start of
+/// the file.
+fn key_span(table: &dyn TableLike, key: &str) -> Range<usize> {
+    table
+        .get_key_value(key)
+        .and_then(|(key, item)| key.span().or_else(|| item.span()))
+        .unwrap_or(0..0)
+}
+
+fn item_span(item: &Item) -> Range<usize> {
+    item.span().unwrap_or(0..0)
+}
+
+impl ManifestCheck<'_> {
+    fn report(&mut self, span: Range<usize>, rule: &'static str, message: String) {
+        self.violations.push(LintViolation::at(
+            MANIFEST_POLICY,
+            self.path,
+            self.content,
+            span,
+            rule,
+            message,
+        ));
+    }
+
+    fn document(&mut self, root: &toml_edit::Table) {
+        for key in ["patch", "replace"] {
+            if root.contains_key(key) {
+                self.report(
+                    key_span(root, key),
+                    "patch",
+                    format!("`[{}]` overrides where dependencies come from", key),
+                );
+            }
+        }
+
+        let package = root.get("package").and_then(Item::as_table_like);
+        if let Some(package) = package {
+            match package.get("build") {
+                Some(build) if build.as_bool() == Some(false) => {}
+                Some(build) => self.report(
+                    item_span(build),
+                    "build-script",
+                    "build scripts run arbitrary code at build time".to_string(),
+                ),
+                None if self.files.contains_key(&format!("{}build.rs", self.dir)) => self.report(
+                    key_span(root, "package"),
+                    "build-script",
+                    format!(
+                        "`{}build.rs` runs as a build script; remove it or set `build = false`",
+                        self.dir
+                    ),
+                ),
+                None => {}
+            }
+        }
+
+        for dependency in dependencies(root) {
+            self.dependency(&dependency);
+        }
+
+        // Profiles and the contract's crate type only matter at the root.
+        if self.dir.is_empty() {
+            if package.is_some() {
+                self.crate_type(root);
+            }
+            self.release_profile(root);
+        }
+    }
+
+    fn dependency(&mut self, dependency: &Dependency<'_>) {
+        let key = dependency.key;
+        if let Some(source) = dependency.foreign_source() {
+            self.report(
+                dependency.key_span(),
+                "source",
+                format!(
+                    "`{}` comes from a {} source; only crates.io is allowed",
+                    key, source
+                ),
+            );
+            return;
+        }
+        if let Some(path) = dependency.field("path") {
+            match path.as_str().and_then(|path| self.project_path(path)) {
+                None => self.report(
+                    item_span(path),
+                    "source",
+                    format!("path dependency `{}` points outside the project", key),
+                ),
+                // With an allowlist, local code must be a crate of the
+                // submission, whose manifest the policy checks as well.
+                Some(dir) if self.policy.crates.is_some() && !self.files.contains_key(&dir) => self
+                    .report(
+                        item_span(path),
+                        "source",
+                        format!(
+                            "path dependency `{}` is not a crate of the project; `{}` is missing",
+                            key, dir
+                        ),
+                    ),
+                Some(_) => {}
+            }
+            return;
+        }
+        // Inherited dependencies are checked in `[workspace.dependencies]`.
+        if dependency.field("workspace").is_some() {
+            return;
+        }
+
+        let Some(crates) = &self.policy.crates else {
+            return;
+        };
+        let name = dependency.name();
+        let Some(allowed) = crates.get(name) else {
+            self.report(
+                dependency.key_span(),
+                "dependency",
+                format!("`{}` is not an allowed crate", name),
+            );
+            return;
+        };
+        let version = match dependency.item.as_table_like() {
+            Some(_) => dependency.field("version"),
+            None => Some(dependency.item),
+        };
+        // Without a version requirement cargo takes any version.
+        let requirement = version.and_then(Item::as_str);
+        if !VersionReq::parse(requirement.unwrap_or("*")).is_ok_and(|requirement| {
+            VersionRange::of(&requirement).within(&VersionRange::of(allowed))
+        }) {
+            let message = match requirement {
+                Some(requirement) => format!(
+                    "`{}` version `{}` is outside the allowed range `{}`",
+                    name, requirement, allowed
+                ),
+                None => format!(
+                    "`{}` has no version requirement; it must be within `{}`",
+                    name, allowed
+                ),
+            };
+            self.report(
+                version
+                    .map(item_span)
+                    .unwrap_or_else(|| dependency.key_span()),
+                "dependency",
+                message,
+            );
+        }
+    }
+
+    /// Manifest a path dependency of this manifest points to, relative to
+    /// the project root. `None` if the path leaves the project.
+    fn project_path(&self, path: &str) -> Option<String> {
+        let mut parts: Vec<&str> = self
+            .dir
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_str()?),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    parts.pop()?;
+                }
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+        parts.push("Cargo.toml");
+        Some(parts.join("/"))
+    }
+
+    fn crate_type(&mut self, root: &toml_edit::Table) {
+        let lib = root.get("lib").and_then(Item::as_table_like);
+        let is_cdylib = lib
+            .and_then(|lib| lib.get("crate-type"))
+            .and_then(Item::as_array)
+            .is_some_and(|types| types.iter().any(|kind| kind.as_str() == Some("cdylib")));
+        if !is_cdylib {
+            let span = match lib {
+                Some(_) => key_span(root, "lib"),
+                None => key_span(root, "package"),
+            };
+            self.report(
+                span,
+                "crate-type",
+                "contracts must set `crate-type = [\"cdylib\"]` under `[lib]`".to_string(),
+            );
+        }
+    }
+
+    fn release_profile(&mut self, root: &toml_edit::Table) {
+        let profile = root
+            .get("profile")
+            .and_then(|profile| profile.get("release"))
+            .and_then(Item::as_table_like);
+        let header = match profile {
+            Some(_) => root
+                .get("profile")
+                .and_then(Item::as_table_like)
+                .map(|profile| key_span(profile, "release"))
+                .unwrap_or(0..0),
+            None => 0..0,
+        };
+        for (key, accepted) in RELEASE_PROFILE {
+            let expected = accepted
+                .iter()
+                .map(Setting::render)
+                .collect::<Vec<_>>()
+                .join(" or ");
+            match profile.and_then(|profile| profile.get(key)) {
+                Some(value) if accepted.iter().any(|setting| setting.matches(value)) => {}
+                Some(value) => self.report(
+                    item_span(value),
+                    "release-profile",
+                    format!("`{}` must be {} in `[profile.release]`", key, expected),
+                ),
+                None => self.report(
+                    header.clone(),
+                    "release-profile",
+                    format!("`[profile.release]` must set `{} = {}`", key, expected),
+                ),
+            }
+        }
+    }
+}
+
+/// An entry of one of a manifest's dependency tables.
+pub struct Dependency<'a> {
+    table: &'a dyn TableLike,
+    pub key: &'a str,
+    item: &'a Item,
+}
+
+impl<'a> Dependency<'a> {
+    fn field(&self, name: &str) -> Option<&'a Item> {
+        self.item.as_table_like().and_then(|spec| spec.get(name))
+    }
+
+    fn key_span(&self) -> Range<usize> {
+        key_span(self.table, self.key)
+    }
+
+    /// Name of the crate, which `package` renames.
+    pub fn name(&self) -> &'a str {
+        self.field("package")
+            .and_then(Item::as_str)
+            .unwrap_or(self.key)
+    }
+
+    /// The key naming where the dependency comes from when it is neither
+    /// crates.io nor a local path.
+    pub fn foreign_source(&self) -> Option<&'static str> {
+        ["git", "registry", "registry-index"]
+            .into_iter()
+            .find(|source| self.field(source).is_some())
+    }
+
+    /// Whether the dependency is a path dependency or inherited from the
+    /// workspace, where it is declared again.
+    pub fn is_local(&self) -> bool {
+        self.field("path").is_some() || self.field("workspace").is_some()
+    }
+}
+
+/// Dependencies of every table of a manifest: the regular, dev and build
+/// tables, their target-specific variants and `[workspace.dependencies]`.
+pub fn dependencies(root: &toml_edit::Table) -> Vec<Dependency<'_>> {
+    let mut tables: Vec<&dyn TableLike> = DEPENDENCY_TABLES
+        .iter()
+        .filter_map(|key| root.get(key).and_then(Item::as_table_like))
+        .collect();
+    if let Some(targets) = root.get("target").and_then(Item::as_table_like) {
+        for (_, target) in targets.iter() {
+            let Some(target) = target.as_table_like() else {
+                continue;
+            };
+            tables.extend(
+                DEPENDENCY_TABLES
+                    .iter()
+                    .filter_map(|key| target.get(key).and_then(Item::as_table_like)),
+            );
+        }
+    }
+    if let Some(workspace) = root
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(Item::as_table_like)
+    {
+        tables.push(workspace);
+    }
+    tables
+        .into_iter()
+        .flat_map(|table| {
+            table
+                .iter()
+                .map(move |(key, item)| Dependency { table, key, item })
+        })
+        .collect()
+}
+
+/// The versions a requirement admits, from `lower` up to `upper` exclusive.
+/// Pre-release rules are left out: a range is as wide as its comparators.
+#[derive(Debug, PartialEq, Eq)]
+struct VersionRange {
+    lower: Version,
+    /// `None` admits every later version.
+    upper: Option<Version>,
+}
+
+impl VersionRange {
+    fn of(requirement: &VersionReq) -> Self {
+        let mut range = VersionRange {
+            lower: Version::new(0, 0, 0),
+            upper: None,
+        };
+        for comparator in &requirement.comparators {
+            let (lower, upper) = comparator_bounds(comparator);
+            if let Some(lower) = lower {
+                range.lower = range.lower.max(lower);
+            }
+            if let Some(upper) = upper {
+                range.upper = Some(match range.upper {
+                    Some(current) => current.min(upper),
+                    None => upper,
+                });
+            }
+        }
+        range
+    }
+
+    /// Whether every version of this range is in `other`.
+    fn within(&self, other: &VersionRange) -> bool {
+        if self
+            .upper
+            .as_ref()
+            .is_some_and(|upper| *upper <= self.lower)
+        {
+            // Admits nothing, which cargo reports on its own.
+            return true;
+        }
+        self.lower >= other.lower
+            && match (&self.upper, &other.upper) {
+                (_, None) => true,
+                (Some(upper), Some(limit)) => upper <= limit,
+                (None, Some(_)) => false,
+            }
+    }
+}
+
+/// Inclusive lower and exclusive upper bound of one comparator.
+fn comparator_bounds(comparator: &Comparator) -> (Option<Version>, Option<Version>) {
+    let (major, minor, patch) = (comparator.major, comparator.minor, comparator.patch);
+    let mut floor = Version::new(major, minor.unwrap_or(0), patch.unwrap_or(0));
+    floor.pre = comparator.pre.clone();
+    // Next version past everything the comparator's own digits match.
+    let next = match (minor, patch) {
+        (None, _) => Version::new(major + 1, 0, 0),
+        (Some(minor), None) => Version::new(major, minor + 1, 0),
+        (Some(minor), Some(patch)) => Version::new(major, minor, patch + 1),
+    };
+    match comparator.op {
+        Op::Exact | Op::Wildcard => (Some(floor), Some(next)),
+        Op::Greater => (Some(next), None),
+        Op::GreaterEq => (Some(floor), None),
+        Op::Less => (None, Some(floor)),
+        Op::LessEq => (None, Some(next)),
+        Op::Tilde => {
+            let upper = match minor {
+                Some(minor) => Version::new(major, minor + 1, 0),
+                None => Version::new(major + 1, 0, 0),
+            };
+            (Some(floor), Some(upper))
+        }
+        Op::Caret => {
+            let upper = match (major, minor, patch) {
+                (0, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+                (0, Some(minor), _) => Version::new(0, minor + 1, 0),
+                _ => Version::new(major + 1, 0, 0),
+            };
+            (Some(floor), Some(upper))
+        }
+        // Operators added to semver later: no bound rather than a wrong one.
+        _ => (None, None),
+    }
+}
+
+/// Renames the root package from the template's `project`, leaving the rest
+/// of the manifest as submitted.
+pub fn rename_package(manifest: &str, name: &str) -> Result<String, String> {
+    let mut document = manifest
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("Invalid Cargo.toml: {}", e))?;
+    if let Some(package) = document
+        .get_mut("package")
+        .and_then(Item::as_table_like_mut)
+    {
+        if package.get("name").and_then(Item::as_str) == Some("project") {
+            package.insert("name", toml_edit::value(name));
+        }
+    }
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTRACT: &str = r#"[package]
+name = "project"
+version = "0.1.0"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+soroban-sdk = "26.1.0"
+
+[profile.release]
+opt-level = "z"
+overflow-checks = true
+panic = "abort"
+codegen-units = 1
+lto = true
+"#;
+
+    fn policy(allowlist: Option<&str>) -> ManifestPolicy {
+        let allowlist = allowlist.map(str::to_string);
+        ManifestPolicy::from_lookup(
+            |key| match key {
+                "SOROPG_MANIFEST_ALLOWLIST" => {
+                    allowlist.as_ref().map(|_| "crates.toml".to_string())
+                }
+                _ => None,
+            },
+            |_| Ok(allowlist.clone().unwrap_or_default()),
+        )
+    }
+
+    fn rules(violations: &[LintViolation]) -> Vec<(String, u32, &'static str)> {
+        violations
+            .iter()
+            .map(|violation| (violation.path.clone(), violation.line, violation.rule))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_contracts_within_the_policy() {
+        let policy = policy(Some("[crates]\nsoroban-sdk = \">=26, <27\"\n"));
+        let files = HashMap::from([("Cargo.toml".to_string(), CONTRACT.to_string())]);
+        assert_eq!(check_with(&policy, &files), Vec::new());
+    }
+
+    #[test]
+    fn reports_violations_at_their_manifest_lines() {
+        let policy = policy(Some("[crates]\nsoroban-sdk = \">=26, <27\"\n"));
+        let root = CONTRACT
+            .replace(
+                "soroban-sdk = \"26.1.0\"",
+                "soroban-sdk = \"25\"\nrand = \"0.8\"\nshared = { path = \"../../shared\" }",
+            )
+            .replace("panic = \"abort\"\n", "")
+            .replace("crate-type = [\"cdylib\"]", "crate-type = [\"rlib\"]")
+            + "\n[patch.crates-io]\nsoroban-sdk = { git = \"https://example.com/sdk\" }\n";
+        let files = HashMap::from([
+            ("Cargo.toml".to_string(), root),
+            ("build.rs".to_string(), "fn main() {}".to_string()),
+            (
+                "tools/Cargo.toml".to_string(),
+                "[package]\nname = \"tools\"\nbuild = \"gen.rs\"\n\n[dependencies]\nhelper = { git = \"https://example.com/helper\" }\nlocal = { path = \"../shared\" }\n".to_string(),
+            ),
+            (
+                "shared/Cargo.toml".to_string(),
+                "[package]\nname = \"shared\"\n".to_string(),
+            ),
+        ]);
+        let violations = check_with(&policy, &files);
+        assert_eq!(
+            rules(&violations),
+            [
+                ("Cargo.toml".to_string(), 1, "build-script"),
+                ("Cargo.toml".to_string(), 5, "crate-type"),
+                ("Cargo.toml".to_string(), 9, "dependency"),
+                ("Cargo.toml".to_string(), 10, "dependency"),
+                ("Cargo.toml".to_string(), 11, "source"),
+                ("Cargo.toml".to_string(), 13, "release-profile"),
+                ("Cargo.toml".to_string(), 19, "patch"),
+                ("tools/Cargo.toml".to_string(), 3, "build-script"),
+                ("tools/Cargo.toml".to_string(), 6, "source"),
+            ]
+        );
+        assert_eq!(
+            violations[2].message,
+            "`soroban-sdk` version `25` is outside the allowed range `>=26, <27`"
+        );
+        assert_eq!(violations[2].column, 15);
+        assert!(violations[2]
+            .cargo_message("error")
+            .contains("\"code\":\"manifest-policy::dependency\""));
+    }
+
+    #[test]
+    fn allowlist_covers_unversioned_and_path_dependencies() {
+        let manifest = "[package]\nname = \"tools\"\n\n[dependencies]\nsoroban-sdk = { features = [\"testutils\"] }\nmember = { path = \"../member\" }\nvendored = { path = \"vendor/sdk\" }\n";
+        let files = HashMap::from([
+            ("tools/Cargo.toml".to_string(), manifest.to_string()),
+            (
+                "member/Cargo.toml".to_string(),
+                "[package]\nname = \"member\"\n".to_string(),
+            ),
+        ]);
+        let violations = check_with(&policy(Some("[crates]\nsoroban-sdk = \"26\"\n")), &files);
+        assert_eq!(
+            rules(&violations),
+            [
+                ("tools/Cargo.toml".to_string(), 5, "dependency"),
+                ("tools/Cargo.toml".to_string(), 7, "source"),
+            ]
+        );
+        assert_eq!(
+            violations[0].message,
+            "`soroban-sdk` has no version requirement; it must be within `^26`"
+        );
+        assert_eq!(
+            violations[1].message,
+            "path dependency `vendored` is not a crate of the project; `tools/vendor/sdk/Cargo.toml` is missing"
+        );
+
+        // Without an allowlist only the source of a dependency is checked.
+        assert_eq!(check_with(&policy(None), &files), Vec::new());
+    }
+
+    #[test]
+    fn requires_dependency_ranges_within_the_allowed_range() {
+        let allowed = VersionRange::of(&VersionReq::parse(">=26, <27").unwrap());
+        let within = |requirement: &str| {
+            VersionRange::of(&VersionReq::parse(requirement).unwrap()).within(&allowed)
+        };
+        for requirement in [
+            "26.1.0",
+            "~26.1",
+            "=26.1.0",
+            "26.*",
+            ">=26.1, <26.5",
+            "<=26.9.9, >26.0",
+        ] {
+            assert!(within(requirement), "{}", requirement);
+        }
+        // Open-ended requirements admit versions past the allowed range.
+        for requirement in [">=26", ">25", "*", "25", "<27", "<=27, >=26"] {
+            assert!(!within(requirement), "{}", requirement);
+        }
+    }
+
+    #[test]
+    fn renames_only_the_template_package() {
+        let renamed = rename_package(CONTRACT, "hello_world").unwrap();
+        assert!(renamed.starts_with("[package]\nname = \"hello_world\"\nversion"));
+        let custom = CONTRACT.replace("\"project\"", "\"mine\"");
+        assert_eq!(rename_package(&custom, "hello_world").unwrap(), custom);
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use once_cell::sync::Lazy;
+use toml_edit::ImDocument;
+
+use crate::manifest::dependencies;
+
+/// Allowlist manifest of a mirror: its dependencies are the crates jobs may
+/// use, and the vendor directory holds them with everything they depend on.
+const ALLOWLIST: &str = "Cargo.toml";
+const VENDOR: &str = "vendor";
+/// Where container backends mount the mirror, read-only.
+pub const MIRROR_MOUNT: &str = "/mnt/mirror";
+
+/// A local crate mirror that replaces crates.io in the sandbox, for
+/// deployments without network access.
+#[derive(Debug)]
+pub struct CrateMirror {
+    pub dir: PathBuf,
+    allowed: BTreeSet<String>,
+}
+
+impl CrateMirror {
+    pub fn load(dir: PathBuf) -> Result<Self, String> {
+        let allowlist = fs::read_to_string(dir.join(ALLOWLIST)).map_err(|e| {
+            format!(
+                "Failed to read the crate mirror allowlist in {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
+        if !dir.join(VENDOR).is_dir() {
+            return Err(format!(
+                "Crate mirror {} has no {} directory; build it with --build-mirror",
+                dir.display(),
+                VENDOR
+            ));
+        }
+        let manifest = ImDocument::parse(allowlist.as_str())
+            .map_err(|e| format!("Invalid crate mirror allowlist: {}", e))?;
+        let allowed = dependencies(manifest.as_table())
+            .into_iter()
+            .filter(|dependency| dependency.foreign_source().is_none() && !dependency.is_local())
+            .map(|dependency| dependency.name().to_string())
+            .collect();
+        Ok(CrateMirror { dir, allowed })
+    }
+
+    /// Dependencies of the submitted manifests the mirror cannot serve:
+    /// crates outside the allowlist, git dependencies and other registries.
+    /// Path dependencies within the project are left to cargo.
+    pub fn unlisted(&self, files: &HashMap<String, String>) -> Result<Vec<String>, String> {
+        let mut unlisted = BTreeSet::new();
+        for (path, content) in files {
+            if path != "Cargo.toml" && !path.ends_with("/Cargo.toml") {
+                continue;
+            }
+            let manifest = ImDocument::parse(content.as_str())
+                .map_err(|e| format!("Invalid {}: {}", path, e))?;
+            for dependency in dependencies(manifest.as_table()) {
+                let name = dependency.name();
+                if let Some(source) = dependency.foreign_source() {
+                    unlisted.insert(format!("{} ({})", name, source));
+                } else if !dependency.is_local() && !self.allowed.contains(name) {
+                    // Workspace-inherited dependencies are checked where
+                    // declared.
+                    unlisted.insert(name.to_string());
+                }
+            }
+        }
+        Ok(unlisted.into_iter().collect())
+    }
+
+    /// Rejects a job whose manifests need crates the mirror cannot serve.
+    pub fn check(&self, files: &HashMap<String, String>) -> Result<(), String> {
+        let unlisted = self.unlisted(files)?;
+        if unlisted.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Dependencies outside the offline crate mirror: {}\nAllowed crates: {}",
+            unlisted.join(", "),
+            self.allowed.iter().cloned().collect::<Vec<_>>().join(", ")
+        ))
+    }
+}
+
+/// Shell step pointing cargo at the mounted mirror and keeping it offline.
+/// The config sits above the project, so it applies to every cargo call of
+/// the job, including those of `stellar contract build`.
+pub fn setup_command() -> String {
+    format!(
+        concat!(
+            "mkdir -p /workspace/.cargo && printf '%s\\n' ",
+            "'[source.crates-io]' 'replace-with = \"soropg-mirror\"' ",
+            "'[source.soropg-mirror]' 'directory = \"{}/{}\"' ",
+            "'[net]' 'offline = true' > /workspace/.cargo/config.toml"
+        ),
+        MIRROR_MOUNT, VENDOR
+    )
+}
+
+/// Builds or refreshes the mirror in `dir` from the allowlist manifest at
+/// `allowlist`, with `cargo vendor`. Needs network access, so air-gapped
+/// deployments build the mirror elsewhere and copy the directory over.
+pub fn build(dir: &Path, allowlist: &Path) -> Result<(), String> {
+    let manifest = fs::read_to_string(allowlist)
+        .map_err(|e| format!("Failed to read {}: {}", allowlist.display(), e))?;
+    let project = tempfile::TempDir::new().map_err(|e| e.to_string())?;
+    fs::create_dir(project.path().join("src")).map_err(|e| e.to_string())?;
+    fs::write(project.path().join("src/lib.rs"), "").map_err(|e| e.to_string())?;
+    fs::write(project.path().join(ALLOWLIST), &manifest).map_err(|e| e.to_string())?;
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let status = Command::new("cargo")
+        .arg("vendor")
+        .arg("--versioned-dirs")
+        .arg("--manifest-path")
+        .arg(project.path().join(ALLOWLIST))
+        .arg(dir.join(VENDOR))
+        .status()
+        .map_err(|e| format!("Failed to run cargo vendor: {}", e))?;
+    if !status.success() {
+        return Err(format!("cargo vendor exited with status: {}", status));
+    }
+    fs::write(dir.join(ALLOWLIST), manifest).map_err(|e| e.to_string())
+}
+
+/// The crate mirror at `SOROPG_CRATE_MIRROR`, `None` when jobs fetch from
+/// crates.io. A configured mirror that fails to load fails every job rather
+/// than letting it reach the network.
+pub static MIRROR: Lazy<Option<Result<CrateMirror, String>>> = Lazy::new(|| {
+    env::var("SOROPG_CRATE_MIRROR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(|dir| CrateMirror::load(PathBuf::from(dir.trim())))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_dependencies_outside_the_allowlist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join(VENDOR)).unwrap();
+        fs::write(
+            dir.path().join(ALLOWLIST),
+            "[package]\nname = \"allowlist\"\n\n[dependencies]\nsoroban-sdk = \"26.1.0\"\nstellar-tokens = \"0.7.2\"\n",
+        )
+        .unwrap();
+        let mirror = CrateMirror::load(dir.path().to_path_buf()).unwrap();
+
+        let files = HashMap::from([
+            (
+                "Cargo.toml".to_string(),
+                concat!(
+                    "[workspace]\nmembers = [\"contracts/*\"]\n\n",
+                    "[workspace.dependencies]\nsoroban-sdk = \"26\"\n",
+                    "tokens = { package = \"stellar-tokens\", version = \"0.7\" }\n",
+                )
+                .to_string(),
+            ),
+            (
+                "contracts/token/Cargo.toml".to_string(),
+                concat!(
+                    "[dependencies]\nsoroban-sdk = { workspace = true }\n",
+                    "shared = { path = \"../shared\" }\nrand = \"0.8\"\n",
+                    "[target.'cfg(unix)'.dev-dependencies]\n",
+                    "proptest = { git = \"https://github.com/proptest-rs/proptest\" }\n",
+                )
+                .to_string(),
+            ),
+        ]);
+        assert_eq!(mirror.unlisted(&files).unwrap(), ["proptest (git)", "rand"]);
+        let error = mirror.check(&files).unwrap_err();
+        assert!(error
+            .starts_with("Dependencies outside the offline crate mirror: proptest (git), rand\n"));
+
+        fs::remove_dir(dir.path().join(VENDOR)).unwrap();
+        assert!(CrateMirror::load(dir.path().to_path_buf()).is_err());
+    }
+}
@@ -1,12 +1,65 @@
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
+use crate::{artifacts::locked_build_hash, workspace::workspace_contracts};
+
 pub const MAIN_SOURCE_CANDIDATES: &[&str] = &["src/lib.rs", "lib.rs"];
 
 #[derive(Deserialize)]
 pub struct CompileRequest {
     pub code: Option<String>,
     pub files: Option<HashMap<String, String>>,
+    #[serde(flatten)]
+    pub tests: TestSelector,
+    /// Stream the full sandbox log for audits instead of only the findings.
+    #[serde(rename = "rawLog")]
+    pub raw_log: Option<bool>,
+    /// Also produce a `stellar contract optimize` build of the WASM.
+    pub optimize: Option<bool>,
+    /// Client language for `/bindings`: `typescript`, `rust`, or `python`.
+    pub language: Option<String>,
+    /// Rebuild with the pinned toolchain and attest against a deployed WASM.
+    pub verify: Option<VerifyRequest>,
+    /// Build against the submitted `Cargo.lock` with `--locked`.
+    pub locked: Option<bool>,
+}
+
+/// Deployed WASM a `/compile` verification build is compared with: either
+/// its SHA-256, or a contract id and network to fetch it from.
+#[derive(Deserialize, Clone)]
+pub struct VerifyRequest {
+    pub sha256: Option<String>,
+    #[serde(rename = "contractId")]
+    pub contract_id: Option<String>,
+    pub network: Option<String>,
+}
+
+impl CompileRequest {
+    /// SHA-256 of the submitted sources, used as the build cache key and to
+    /// tie job history entries to their input. Locked builds get their own
+    /// key, see [`locked_build_hash`].
+    pub fn input_hash(&self) -> String {
+        let hash = match (&self.files, &self.code) {
+            (Some(files), _) => files_hash(files),
+            (None, Some(code)) => hex::encode(Sha256::digest(code.as_bytes())),
+            (None, None) => hex::encode(Sha256::digest(b"")),
+        };
+        if self.locked.unwrap_or(false) {
+            locked_build_hash(&hash)
+        } else {
+            hash
+        }
+    }
+}
+
+/// Narrows a test run to a subset of the suite, mirroring
+/// `cargo test [filter] -- [--exact] [--ignored]`.
+#[derive(Deserialize, Default, Clone)]
+pub struct TestSelector {
+    pub filter: Option<String>,
+    pub exact: Option<bool>,
+    pub ignored: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -15,8 +68,64 @@ pub struct InterfaceRequest {
     pub network: String,
 }
 
+/// Version of the [`files_hash`] encoding. Bumping it retires every cache
+/// entry keyed by an older encoding.
+const FILES_HASH_VERSION: &[u8] = b"soropg-files-v2";
+
+/// Hashes a file map in path order so equivalent maps give the same hash.
+/// Paths and contents are length-prefixed, so moving bytes between a path
+/// and its content, or between files, changes the hash.
+pub fn files_hash(files: &HashMap<String, String>) -> String {
+    let mut hash = Sha256::new();
+    hash.update(FILES_HASH_VERSION);
+    let mut entries: Vec<_> = files.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    for (path, content) in entries {
+        for field in [path, content] {
+            hash.update((field.len() as u64).to_be_bytes());
+            hash.update(field.as_bytes());
+        }
+    }
+    hex::encode(hash.finalize())
+}
+
 pub fn extract_main_source(files: &HashMap<String, String>) -> Option<String> {
     MAIN_SOURCE_CANDIDATES
         .iter()
         .find_map(|path| files.get(*path).cloned())
+        .or_else(|| {
+            // Workspaces have no root crate; use the first contract member.
+            let contracts = workspace_contracts(files).ok().flatten()?;
+            files
+                .get(&format!("{}/src/lib.rs", contracts.first()?.dir))
+                .cloned()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_hash_separates_paths_and_contents() {
+        let files = |entries: &[(&str, &str)]| -> HashMap<String, String> {
+            entries
+                .iter()
+                .map(|(path, content)| (path.to_string(), content.to_string()))
+                .collect()
+        };
+        let hash = files_hash(&files(&[("src/lib.rs", "ab"), ("src/x.rs", "")]));
+        assert_eq!(
+            hash,
+            files_hash(&files(&[("src/x.rs", ""), ("src/lib.rs", "ab")]))
+        );
+        assert_ne!(
+            hash,
+            files_hash(&files(&[("src/lib.rsa", "b"), ("src/x.rs", "")]))
+        );
+        assert_ne!(
+            files_hash(&files(&[("a", "bc")])),
+            files_hash(&files(&[("a", "b"), ("c", "")]))
+        );
+    }
 }
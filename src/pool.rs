@@ -0,0 +1,635 @@
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use tokio::process::Command;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    executor::{
+        run_quietly, CacheLayer, CacheScope, ContainerCli, ContainerSpec, Executor, CACHE_SCOPE,
+    },
+    hardening::JOB_ROOT,
+    limits::{limits_for, CommandKind, SandboxLimits},
+};
+
+const WARM_PREFIX: &str = "soropg-warm-";
+/// Prefix of the host directories warm containers mount, under the job root.
+const SLOT_PREFIX: &str = "warm-";
+/// Where a warm container mounts its slot directory. A job's workspace is
+/// moved into the slot for the job's duration, so a container only ever sees
+/// the workspace of the job it runs.
+const JOBS_MOUNT: &str = "/jobs";
+const SLOT_WORKSPACE: &str = "workspace";
+/// Run between jobs sharing a container: stops anything a job left running
+/// and clears the container's tmpfs.
+const RESET_COMMAND: &str =
+    "kill -KILL -1 2>/dev/null; find /tmp -mindepth 1 -delete 2>/dev/null; true";
+/// Also clears the container's cargo layer, so a job cannot leave poisoned
+/// sources or build outputs behind for the next one.
+const RESET_LAYER_COMMAND: &str =
+    "kill -KILL -1 2>/dev/null; find /tmp /mnt/cargo -mindepth 1 -delete 2>/dev/null; true";
+
+struct WarmContainer {
+    name: String,
+    /// Host directory mounted at [`JOBS_MOUNT`].
+    slot: PathBuf,
+    /// Cargo layer of the container, `None` when it writes to the shared
+    /// cache.
+    layer: Option<String>,
+    /// Whether `layer` is a tenant's layer rather than the container's own.
+    /// It outlives the container and is kept between jobs.
+    tenant: bool,
+    offline: bool,
+    jobs: u32,
+}
+
+impl WarmContainer {
+    /// Whether the container's cargo layer can stand in for a job's. The
+    /// shared cache only serves jobs writing to it. The container's own
+    /// layer is wiped between jobs, so it serves jobs whose layer would be
+    /// discarded afterwards. A tenant's container serves that tenant only.
+    fn serves(&self, cache: Option<&CacheLayer>) -> bool {
+        match (&self.layer, cache) {
+            (None, None) => true,
+            (Some(own), Some(layer)) if self.tenant => !layer.discard && *own == layer.name,
+            (Some(_), Some(layer)) => layer.discard,
+            _ => false,
+        }
+    }
+}
+
+/// A container running a job, and where the job's workspace came from.
+struct Busy {
+    container: WarmContainer,
+    workspace: PathBuf,
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<WarmContainer>,
+    /// Containers being started, indexed by `offline`.
+    starting: [usize; 2],
+    /// Tenant containers being started, by layer and `offline`.
+    adopting: Vec<(String, bool)>,
+    busy: HashMap<String, Busy>,
+}
+
+struct PoolInner {
+    cli: ContainerCli,
+    size: usize,
+    max_jobs: u32,
+    /// Limits the pool's containers run with; jobs with other limits get a
+    /// container of their own.
+    limits: SandboxLimits,
+    state: Mutex<PoolState>,
+}
+
+/// Keeps `size` pre-started containers per network mode and runs jobs in
+/// them with `exec`, so a job skips container startup. Under the tenant
+/// cache scope the containers mount the layer of a tenant that ran a job
+/// recently, up to `size` per network mode. A container is replaced after
+/// `max_jobs` jobs or after a job that failed or was killed. Jobs the pool
+/// cannot serve fall back to a container of their own.
+pub struct WarmPool {
+    inner: Arc<PoolInner>,
+}
+
+impl WarmPool {
+    pub fn new(cli: ContainerCli, size: usize, max_jobs: u32) -> Self {
+        WarmPool {
+            inner: Arc::new(PoolInner {
+                cli,
+                size,
+                max_jobs: max_jobs.max(1),
+                limits: limits_for(CommandKind::Build).clone(),
+                state: Mutex::new(PoolState::default()),
+            }),
+        }
+    }
+}
+
+/// Creates the slot directory of a warm container. The sandbox user may
+/// traverse it but not write to it, so only the workspace moved in is
+/// writable.
+fn create_slot(slot: &Path) -> Result<(), String> {
+    fs::create_dir_all(&*JOB_ROOT).map_err(|e| e.to_string())?;
+    fs::create_dir(slot).map_err(|e| e.to_string())?;
+    fs::set_permissions(slot, fs::Permissions::from_mode(0o711)).map_err(|e| e.to_string())
+}
+
+impl PoolInner {
+    fn program(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(self.cli.program);
+        command.args(args);
+        command
+    }
+
+    /// Starts containers until each network mode has `size` idle or starting.
+    /// Every job has a tenant layer under the tenant scope, so the pool then
+    /// only starts containers for tenants, see [`PoolInner::adopt`].
+    fn fill(self: &Arc<Self>) {
+        if *CACHE_SCOPE == CacheScope::Tenant {
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        for offline in [false, true] {
+            let idle = state
+                .idle
+                .iter()
+                .filter(|container| container.offline == offline && !container.tenant)
+                .count();
+            for _ in idle + state.starting[offline as usize]..self.size {
+                state.starting[offline as usize] += 1;
+                tokio::spawn(self.clone().start(offline, None));
+            }
+        }
+    }
+
+    /// Starts a container mounting the tenant layer of a job the pool could
+    /// not serve, so the tenant's next jobs run warm. Each network mode keeps
+    /// at most `size` tenant containers; the least recently used idle one
+    /// makes room.
+    fn adopt(self: &Arc<Self>, offline: bool, layer: &str) {
+        if self.size == 0 {
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let owned = |container: &WarmContainer| container.tenant && container.offline == offline;
+        let serving = state
+            .idle
+            .iter()
+            .chain(state.busy.values().map(|busy| &busy.container))
+            .any(|container| owned(container) && container.layer.as_deref() == Some(layer));
+        if serving || state.adopting.contains(&(layer.to_string(), offline)) {
+            return;
+        }
+        let tenants = state
+            .idle
+            .iter()
+            .filter(|container| owned(container))
+            .count()
+            + state
+                .busy
+                .values()
+                .filter(|busy| owned(&busy.container))
+                .count()
+            + state
+                .adopting
+                .iter()
+                .filter(|(_, mode)| *mode == offline)
+                .count();
+        if tenants >= self.size {
+            // Idle containers are returned to the back, so the first is the
+            // least recently used.
+            let Some(index) = state.idle.iter().position(owned) else {
+                return;
+            };
+            let container = state.idle.remove(index);
+            tokio::spawn(self.clone().retire(container));
+        }
+        state.adopting.push((layer.to_string(), offline));
+        tokio::spawn(self.clone().start(offline, Some(layer.to_string())));
+    }
+
+    async fn start(self: Arc<Self>, offline: bool, tenant: Option<String>) {
+        let id = Uuid::new_v4().simple().to_string();
+        let name = format!("{}{}", WARM_PREFIX, id);
+        let slot = JOB_ROOT.join(format!("{}{}", SLOT_PREFIX, id));
+        let layer = tenant
+            .clone()
+            .or_else(|| CACHE_SCOPE.is_isolated().then(|| format!("warm-{}", id)));
+        let started = match create_slot(&slot) {
+            Ok(()) => {
+                let mut args = self.cli.run_args(
+                    &name,
+                    &self.limits,
+                    offline,
+                    Some((&slot, JOBS_MOUNT)),
+                    layer.as_deref(),
+                    &[],
+                );
+                args.insert(1, "--detach".to_string());
+                args.extend(["sleep".to_string(), "infinity".to_string()]);
+                let mut command = Command::new(self.cli.program);
+                command.args(&args);
+                run_quietly(command).await
+            }
+            Err(e) => {
+                warn!(slot=%slot.display(), error=%e, "failed to create warm container slot");
+                false
+            }
+        };
+
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        match &tenant {
+            Some(tenant) => state
+                .adopting
+                .retain(|adopting| *adopting != (tenant.clone(), offline)),
+            None => state.starting[offline as usize] -= 1,
+        }
+        if started {
+            state.idle.push(WarmContainer {
+                name,
+                slot,
+                layer,
+                tenant: tenant.is_some(),
+                offline,
+                jobs: 0,
+            });
+        } else {
+            warn!(container=%name, "failed to start warm sandbox container");
+            let _ = fs::remove_dir_all(&slot);
+            if let (Some(layer), None) = (layer, tenant) {
+                self.cli.discard_cache(&layer);
+            }
+        }
+    }
+
+    fn checkout(
+        self: &Arc<Self>,
+        offline: bool,
+        cache: Option<&CacheLayer>,
+    ) -> Option<WarmContainer> {
+        let container = {
+            let mut state = self.state.lock().ok()?;
+            let index = state
+                .idle
+                .iter()
+                .position(|container| container.offline == offline && container.serves(cache));
+            index.map(|index| state.idle.remove(index))
+        };
+        match (container, cache) {
+            (Some(container), _) => {
+                self.fill();
+                Some(container)
+            }
+            (None, Some(layer)) if !layer.discard => {
+                self.adopt(offline, &layer.name);
+                None
+            }
+            (None, _) => None,
+        }
+    }
+
+    /// Moves the job's workspace into the slot of an idle container that can
+    /// serve it and returns the `exec` command running the job there.
+    fn dispatch(
+        self: &Arc<Self>,
+        job: &str,
+        spec: &ContainerSpec<'_>,
+        limits: &SandboxLimits,
+    ) -> Option<Command> {
+        if limits.docker_args() != self.limits.docker_args()
+            || !spec.workspace.starts_with(&*JOB_ROOT)
+        {
+            return None;
+        }
+        let container = self.checkout(spec.offline, spec.cache)?;
+        if let Err(e) = fs::rename(spec.workspace, container.slot.join(SLOT_WORKSPACE)) {
+            warn!(container=%container.name, error=%e, "failed to move a job into a warm container");
+            tokio::spawn(self.clone().retire(container));
+            return None;
+        }
+
+        let workspace = format!("{}/{}", JOBS_MOUNT, SLOT_WORKSPACE);
+        let mut args = vec![
+            "exec".to_string(),
+            "--workdir".to_string(),
+            workspace.clone(),
+        ];
+        for var in &spec.env {
+            args.extend(["--env".to_string(), var.replace("/workspace", &workspace)]);
+        }
+        args.extend([
+            container.name.clone(),
+            "bash".to_string(),
+            "-c".to_string(),
+            spec.command.replace("/workspace", &workspace),
+        ]);
+        if let Ok(mut state) = self.state.lock() {
+            state.busy.insert(
+                job.to_string(),
+                Busy {
+                    container,
+                    workspace: spec.workspace.to_path_buf(),
+                },
+            );
+        }
+
+        let mut command = Command::new(self.cli.program);
+        command.args(&args);
+        Some(command)
+    }
+
+    /// Takes back the container of a finished job and moves the job's
+    /// workspace back out of its slot. Returns the container and whether it
+    /// may run another job.
+    fn release(&self, job: &str, success: bool) -> Option<(WarmContainer, bool)> {
+        let Busy {
+            mut container,
+            workspace,
+        } = self.state.lock().ok()?.busy.remove(job)?;
+        let restored = match fs::rename(container.slot.join(SLOT_WORKSPACE), &workspace) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(container=%container.name, error=%e, "failed to move a job out of a warm container");
+                false
+            }
+        };
+        container.jobs += 1;
+        let reuse = success && restored && container.jobs < self.max_jobs;
+        Some((container, reuse))
+    }
+
+    /// Resets a container that finished a job and returns it to the pool.
+    async fn recycle(self: Arc<Self>, container: WarmContainer) {
+        let command = match (&container.layer, container.tenant) {
+            (Some(_), false) => RESET_LAYER_COMMAND,
+            _ => RESET_COMMAND,
+        };
+        let reset = self.program(&["exec", &container.name, "bash", "-c", command]);
+        if run_quietly(reset).await {
+            if let Ok(mut state) = self.state.lock() {
+                state.idle.push(container);
+            }
+            return;
+        }
+        self.retire(container).await;
+    }
+
+    async fn retire(self: Arc<Self>, container: WarmContainer) {
+        run_quietly(self.program(&["rm", "--force", &container.name])).await;
+        let _ = fs::remove_dir_all(&container.slot);
+        if let (Some(layer), false) = (&container.layer, container.tenant) {
+            self.cli.discard_cache(layer);
+        }
+        self.fill();
+    }
+
+    /// Removes warm containers, and then their slots and cargo layers, left
+    /// behind by an earlier server process.
+    async fn remove_stale(&self) {
+        let containers = format!("name={}", WARM_PREFIX);
+        self.remove_listed(&["ps", "--all"], &containers, &["rm", "--force"])
+            .await;
+        if let Ok(entries) = fs::read_dir(&*JOB_ROOT) {
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with(SLOT_PREFIX) {
+                    let _ = fs::remove_dir_all(entry.path());
+                }
+            }
+        }
+        let layers = format!("name={}", self.cli.layer_volume("warm-"));
+        self.remove_listed(&["volume", "ls"], &layers, &["volume", "rm"])
+            .await;
+    }
+
+    async fn remove_listed(&self, list: &[&str], filter: &str, remove: &[&str]) {
+        let mut args = list.to_vec();
+        args.extend(["--quiet", "--filter", filter]);
+        let Ok(output) = self.program(&args).output().await else {
+            return;
+        };
+        let ids = String::from_utf8_lossy(&output.stdout);
+        for id in ids.split_whitespace() {
+            let mut args = remove.to_vec();
+            args.push(id);
+            run_quietly(self.program(&args)).await;
+        }
+    }
+}
+
+impl Executor for WarmPool {
+    fn name(&self) -> &'static str {
+        self.inner.cli.program
+    }
+
+    fn command(&self, job: &str, spec: &ContainerSpec<'_>, limits: &SandboxLimits) -> Command {
+        self.inner
+            .dispatch(job, spec, limits)
+            .unwrap_or_else(|| self.inner.cli.command(job, spec, limits))
+    }
+
+    fn kill_command(&self, job: &str, pid: Option<u32>) -> Option<Command> {
+        let state = self.inner.state.lock().ok()?;
+        match state.busy.get(job) {
+            // Killing the exec client would leave the job running inside.
+            Some(busy) => Some(self.inner.program(&["kill", &busy.container.name])),
+            None => self.inner.cli.kill_command(job, pid),
+        }
+    }
+
+    fn is_warm(&self, job: &str) -> bool {
+        self.inner
+            .state
+            .lock()
+            .is_ok_and(|state| state.busy.contains_key(job))
+    }
+
+    fn finish(&self, job: &str, success: bool) {
+        let Some((container, reuse)) = self.inner.release(job, success) else {
+            return;
+        };
+        let inner = self.inner.clone();
+        if reuse {
+            tokio::spawn(inner.recycle(container));
+        } else {
+            tokio::spawn(inner.retire(container));
+        }
+    }
+
+    fn discard_cache(&self, layer: &str) {
+        self.inner.cli.discard_cache(layer);
+    }
+
+    fn warm_up(&self) {
+        info!(size = self.inner.size, "starting warm sandbox containers");
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            inner.remove_stale().await;
+            inner.fill();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardening::job_workspace;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn dispatches_jobs_to_idle_containers() {
+        let pool = WarmPool::new(
+            ContainerCli::new(
+                "docker",
+                "wasm_sandbox:latest".to_string(),
+                "cargo-cache".to_string(),
+            ),
+            0,
+            5,
+        );
+        let slot = tempfile::Builder::new()
+            .prefix(SLOT_PREFIX)
+            .tempdir_in(&*JOB_ROOT)
+            .unwrap();
+        pool.inner.state.lock().unwrap().idle.push(WarmContainer {
+            name: "soropg-warm-1".to_string(),
+            slot: slot.path().to_path_buf(),
+            layer: Some("warm-1".to_string()),
+            tenant: false,
+            offline: true,
+            jobs: 0,
+        });
+        let limits = limits_for(CommandKind::Build);
+        let (job_a, job_b) = (job_workspace().unwrap(), job_workspace().unwrap());
+        fs::write(job_b.path().join("lib.rs"), "").unwrap();
+        let tenant = CacheLayer {
+            name: "tenant-1".to_string(),
+            discard: false,
+        };
+        let own = CacheLayer {
+            name: "job-1".to_string(),
+            discard: true,
+        };
+        let spec = |workspace, cache, offline| ContainerSpec {
+            workspace,
+            cache,
+            env: vec!["REPORT=/workspace/report.json".to_string()],
+            command: "cd /workspace/project && cargo build",
+            offline,
+        };
+
+        // No idle container for online jobs, workspaces outside the job root
+        // cannot be moved into a slot, and tenant layers outlive the job, so
+        // only a container of that tenant serves it.
+        assert_eq!(
+            args(&pool.command("job-1", &spec(job_a.path(), Some(&own), false), limits))[0],
+            "run"
+        );
+        assert_eq!(
+            args(&pool.command(
+                "job-2",
+                &spec(Path::new("/srv/job"), Some(&own), true),
+                limits
+            ))[0],
+            "run"
+        );
+        assert_eq!(
+            args(&pool.command("job-2", &spec(job_b.path(), Some(&tenant), true), limits))[0],
+            "run"
+        );
+        assert!(!pool.is_warm("job-1"));
+
+        let command = pool.command("job-3", &spec(job_b.path(), Some(&own), true), limits);
+        assert_eq!(
+            args(&command),
+            [
+                "exec",
+                "--workdir",
+                "/jobs/workspace",
+                "--env",
+                "REPORT=/jobs/workspace/report.json",
+                "soropg-warm-1",
+                "bash",
+                "-c",
+                "cd /jobs/workspace/project && cargo build",
+            ]
+        );
+        assert!(slot.path().join("workspace/lib.rs").exists());
+        assert!(!job_b.path().exists());
+        assert!(pool.is_warm("job-3"));
+        assert_eq!(
+            args(&pool.kill_command("job-3", None).unwrap()),
+            ["kill", "soropg-warm-1"]
+        );
+
+        let (container, reuse) = pool.inner.release("job-3", false).unwrap();
+        assert_eq!(container.jobs, 1);
+        assert!(!reuse);
+        assert!(job_b.path().join("lib.rs").exists());
+        assert!(!slot.path().join("workspace").exists());
+        assert!(!pool.is_warm("job-3"));
+        assert!(pool.inner.state.lock().unwrap().idle.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn dispatches_tenant_jobs_to_their_containers() {
+        let pool = WarmPool::new(
+            ContainerCli::new(
+                "docker",
+                "wasm_sandbox:latest".to_string(),
+                "cargo-cache".to_string(),
+            ),
+            0,
+            5,
+        );
+        let slot = tempfile::Builder::new()
+            .prefix(SLOT_PREFIX)
+            .tempdir_in(&*JOB_ROOT)
+            .unwrap();
+        pool.inner.state.lock().unwrap().idle.push(WarmContainer {
+            name: "soropg-warm-2".to_string(),
+            slot: slot.path().to_path_buf(),
+            layer: Some("tenant-1".to_string()),
+            tenant: true,
+            offline: false,
+            jobs: 0,
+        });
+        let limits = limits_for(CommandKind::Build);
+        let (job_a, job_b) = (job_workspace().unwrap(), job_workspace().unwrap());
+        let layer = |name: &str, discard| CacheLayer {
+            name: name.to_string(),
+            discard,
+        };
+        let spec = |workspace, cache| ContainerSpec {
+            workspace,
+            cache,
+            env: Vec::new(),
+            command: "cargo build",
+            offline: false,
+        };
+
+        // Neither another tenant nor a job with a throwaway layer may see the
+        // tenant's cargo layer.
+        let other = layer("tenant-2", false);
+        let own = layer("job-1", true);
+        assert_eq!(
+            args(&pool.command("job-1", &spec(job_a.path(), Some(&other)), limits))[0],
+            "run"
+        );
+        assert_eq!(
+            args(&pool.command("job-1", &spec(job_a.path(), Some(&own)), limits))[0],
+            "run"
+        );
+
+        let tenant = layer("tenant-1", false);
+        let command = pool.command("job-2", &spec(job_b.path(), Some(&tenant)), limits);
+        assert_eq!(args(&command)[0], "exec");
+        assert!(pool.is_warm("job-2"));
+
+        let (container, reuse) = pool.inner.release("job-2", true).unwrap();
+        assert!(reuse);
+        assert!(container.tenant);
+        assert!(job_b.path().exists());
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    collections::VecDeque,
+    env, fmt,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{HttpRequest, HttpResponse};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::limits::{limits_for, CommandKind};
+
+const DEFAULT_SLOTS: u32 = 4;
+const DEFAULT_MAX_QUEUED: usize = 64;
+const DEFAULT_MAX_PER_CLIENT: usize = 3;
+
+/// Global sandbox job queue. Waiting clients learn their position instead of
+/// hanging on a semaphore.
+pub static QUEUE: Lazy<JobQueue> = Lazy::new(|| {
+    let setting = |key: &str, default: usize| {
+        env::var(key)
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(default)
+    };
+    JobQueue::new(
+        setting("SOROPG_SANDBOX_SLOTS", DEFAULT_SLOTS as usize) as u32,
+        setting("SOROPG_QUEUE_MAX", DEFAULT_MAX_QUEUED),
+        setting("SOROPG_QUEUE_MAX_PER_CLIENT", DEFAULT_MAX_PER_CLIENT),
+    )
+});
+
+/// Place of a waiting job. `position` is 1 for the next job to start.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuePosition {
+    pub position: usize,
+    pub eta_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    Waiting(QueuePosition),
+    Admitted,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum QueueError {
+    /// The client already has the maximum number of queued or running jobs.
+    ClientLimit(usize),
+    /// The queue is full.
+    Full,
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::ClientLimit(limit) => write!(
+                f,
+                "Too many jobs: at most {} may be queued or running per client",
+                limit
+            ),
+            QueueError::Full => f.write_str("Sandbox queue is full, try again shortly"),
+        }
+    }
+}
+
+impl QueueError {
+    pub fn into_response(self) -> HttpResponse {
+        match self {
+            QueueError::ClientLimit(_) => HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", "10"))
+                .body(self.to_string()),
+            QueueError::Full => HttpResponse::ServiceUnavailable()
+                .insert_header(("Retry-After", "30"))
+                .body(self.to_string()),
+        }
+    }
+}
+
+/// Reverse proxies whose `X-Forwarded-For` header is believed, from the
+/// comma-separated `SOROPG_TRUSTED_PROXIES`. Empty by default, so clients
+/// cannot pick their own key with a forged header.
+static TRUSTED_PROXIES: Lazy<Vec<IpAddr>> = Lazy::new(|| {
+    env::var("SOROPG_TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .filter_map(|proxy| match proxy.parse() {
+            Ok(addr) => Some(addr),
+            Err(_) => {
+                warn!(proxy = %proxy, "ignoring invalid SOROPG_TRUSTED_PROXIES entry");
+                None
+            }
+        })
+        .collect()
+});
+
+/// Fairness and cache key of an anonymous HTTP client: its peer address, or
+/// the address a trusted reverse proxy forwarded the request for.
+pub fn client_key(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr() else {
+        return "unknown".to_string();
+    };
+    let forwarded_for = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    forwarded_client(peer.ip(), &forwarded_for, &TRUSTED_PROXIES).to_string()
+}
+
+/// Walks `X-Forwarded-For` from the nearest hop while the hops are trusted
+/// proxies. The first other address is the client; a malformed hop stops the
+/// walk at the last trusted proxy.
+fn forwarded_client(peer: IpAddr, forwarded_for: &str, trusted: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        if !trusted.contains(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(addr) => client = addr,
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+struct Entry {
+    ticket: u64,
+    kind: CommandKind,
+    weight: u32,
+    client: String,
+    tx: watch::Sender<Slot>,
+}
+
+struct Running {
+    entry: Entry,
+    started: Instant,
+}
+
+struct QueueState {
+    next_ticket: u64,
+    used: u32,
+    waiting: VecDeque<Entry>,
+    running: Vec<Running>,
+    /// Moving average run time per kind, for ETAs.
+    averages: [Duration; 4],
+}
+
+/// FIFO queue over a fixed number of sandbox slots. Each kind of job takes
+/// `weight` slots, so a heavy audit counts as several builds. Jobs start in
+/// arrival order; a job that does not fit yet holds back the ones behind it
+/// so heavy jobs are not starved.
+pub struct JobQueue {
+    slots: u32,
+    max_queued: usize,
+    max_per_client: usize,
+    state: Mutex<QueueState>,
+}
+
+fn kind_index(kind: CommandKind) -> usize {
+    match kind {
+        CommandKind::Build => 0,
+        CommandKind::Test => 1,
+        CommandKind::Audit => 2,
+        CommandKind::Interface => 3,
+    }
+}
+
+impl JobQueue {
+    pub fn new(slots: u32, max_queued: usize, max_per_client: usize) -> Self {
+        JobQueue {
+            slots: slots.max(1),
+            max_queued,
+            max_per_client,
+            state: Mutex::new(QueueState {
+                next_ticket: 0,
+                used: 0,
+                waiting: VecDeque::new(),
+                running: Vec::new(),
+                averages: [
+                    Duration::from_secs(60),
+                    Duration::from_secs(60),
+                    Duration::from_secs(180),
+                    Duration::from_secs(10),
+                ],
+            }),
+        }
+    }
+
+    /// Queues a job for `client`: the bearer token hash of an MCP user, or
+    /// the [`client_key`] of an anonymous one. The job starts once the
+    /// returned ticket reports `Slot::Admitted`; dropping the ticket leaves
+    /// the queue or frees the slots.
+    pub fn enqueue(
+        &'static self,
+        kind: CommandKind,
+        client: &str,
+    ) -> Result<QueueTicket, QueueError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let client_jobs = state
+            .waiting
+            .iter()
+            .chain(state.running.iter().map(|running| &running.entry))
+            .filter(|entry| entry.client == client)
+            .count();
+        if client_jobs >= self.max_per_client {
+            return Err(QueueError::ClientLimit(self.max_per_client));
+        }
+        if state.waiting.len() >= self.max_queued {
+            return Err(QueueError::Full);
+        }
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let (tx, rx) = watch::channel(Slot::Waiting(QueuePosition {
+            position: state.waiting.len() + 1,
+            eta_secs: 0,
+        }));
+        state.waiting.push_back(Entry {
+            ticket,
+            kind,
+            weight: limits_for(kind).weight.min(self.slots),
+            client: client.to_string(),
+            tx,
+        });
+        self.dispatch(&mut state);
+
+        let mut rx = rx;
+        rx.mark_changed();
+        Ok(QueueTicket {
+            queue: self,
+            ticket,
+            rx,
+        })
+    }
+
+    /// Starts waiting jobs in order while they fit, then refreshes the
+    /// position of the rest.
+    fn dispatch(&self, state: &mut QueueState) {
+        while let Some(next) = state.waiting.front() {
+            if state.used + next.weight > self.slots {
+                break;
+            }
+            let Some(entry) = state.waiting.pop_front() else {
+                break;
+            };
+            state.used += entry.weight;
+            entry.tx.send_replace(Slot::Admitted);
+            state.running.push(Running {
+                entry,
+                started: Instant::now(),
+            });
+        }
+
+        // Slot-seconds of work ahead of each waiting job, spread over all slots.
+        let mut work: f64 = state
+            .running
+            .iter()
+            .map(|running| {
+                let average = state.averages[kind_index(running.entry.kind)];
+                average
+                    .saturating_sub(running.started.elapsed())
+                    .as_secs_f64()
+                    * running.entry.weight as f64
+            })
+            .sum();
+        for (index, entry) in state.waiting.iter().enumerate() {
+            let position = QueuePosition {
+                position: index + 1,
+                eta_secs: (work / self.slots as f64).ceil() as u64,
+            };
+            entry.tx.send_if_modified(|slot| {
+                let changed = *slot != Slot::Waiting(position);
+                *slot = Slot::Waiting(position);
+                changed
+            });
+            work += state.averages[kind_index(entry.kind)].as_secs_f64() * entry.weight as f64;
+        }
+    }
+
+    fn release(&self, ticket: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = state
+            .waiting
+            .iter()
+            .position(|entry| entry.ticket == ticket)
+        {
+            state.waiting.remove(index);
+        } else if let Some(index) = state
+            .running
+            .iter()
+            .position(|running| running.entry.ticket == ticket)
+        {
+            let running = state.running.swap_remove(index);
+            state.used -= running.entry.weight;
+            let average = &mut state.averages[kind_index(running.entry.kind)];
+            *average = average.mul_f64(0.7) + running.started.elapsed().mul_f64(0.3);
+        }
+        self.dispatch(&mut state);
+    }
+}
+
+/// A job's place in the queue, and its slots once admitted.
+pub struct QueueTicket {
+    queue: &'static JobQueue,
+    ticket: u64,
+    rx: watch::Receiver<Slot>,
+}
+
+impl QueueTicket {
+    /// Waits for the next change of the job's slot. The first call returns
+    /// the current state immediately.
+    pub async fn next(&mut self) -> Slot {
+        let _ = self.rx.changed().await;
+        *self.rx.borrow_and_update()
+    }
+
+    /// Waits until the job may start.
+    pub async fn admitted(&mut self) {
+        while self.next().await != Slot::Admitted {}
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        self.queue.release(self.ticket);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(slots: u32, max_per_client: usize) -> &'static JobQueue {
+        Box::leak(Box::new(JobQueue::new(slots, 8, max_per_client)))
+    }
+
+    #[actix_web::test]
+    async fn reports_positions_and_admits_in_order() {
+        let queue = queue(2, 4);
+        let mut audit = queue.enqueue(CommandKind::Audit, "a").unwrap();
+        assert_eq!(audit.next().await, Slot::Admitted);
+
+        let mut build = queue.enqueue(CommandKind::Build, "b").unwrap();
+        let mut test = queue.enqueue(CommandKind::Test, "c").unwrap();
+        assert!(matches!(
+            build.next().await,
+            Slot::Waiting(QueuePosition { position: 1, .. })
+        ));
+        assert!(matches!(
+            test.next().await,
+            Slot::Waiting(QueuePosition { position: 2, .. })
+        ));
+
+        drop(audit);
+        assert_eq!(build.next().await, Slot::Admitted);
+        assert_eq!(test.next().await, Slot::Admitted);
+    }
+
+    #[actix_web::test]
+    async fn caps_jobs_per_client() {
+        let queue = queue(1, 2);
+        let _first = queue.enqueue(CommandKind::Build, "203.0.113.7").unwrap();
+        let second = queue.enqueue(CommandKind::Build, "203.0.113.7").unwrap();
+        assert_eq!(
+            queue.enqueue(CommandKind::Build, "203.0.113.7").err(),
+            Some(QueueError::ClientLimit(2))
+        );
+        assert!(queue.enqueue(CommandKind::Build, "198.51.100.1").is_ok());
+
+        drop(second);
+        assert!(queue.enqueue(CommandKind::Build, "203.0.113.7").is_ok());
+    }
+
+    #[test]
+    fn believes_forwarded_addresses_only_from_trusted_proxies() {
+        let ip = |addr: &str| addr.parse::<IpAddr>().unwrap();
+        let proxy = ip("10.0.0.2");
+        let trusted = [proxy, ip("10.0.0.3")];
+
+        assert_eq!(
+            forwarded_client(ip("203.0.113.7"), "198.51.100.1", &trusted),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            forwarded_client(proxy, "198.51.100.1, 203.0.113.7, 10.0.0.3", &trusted),
+            ip("203.0.113.7")
+        );
+        assert_eq!(forwarded_client(proxy, "", &trusted), proxy);
+        assert_eq!(forwarded_client(proxy, "spoofed", &trusted), proxy);
+    }
+}
@@ -17,11 +17,12 @@ use serde_json::{json, Value};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
+use crate::models::TestSelector;
 use crate::routes::mcp::{
     bearer_hash, delete_project_file_for_user, list_project_files_for_user,
     move_project_file_for_user, project_snapshot_for_user, project_summaries_for_user,
     read_project_file_for_user, run_project_command_for_user, upsert_project_file_for_user,
-    McpState,
+    CommandOptions, McpState,
 };
 
 const OPENROUTER_MODEL: &str = "deepseek/deepseek-v4-flash";
@@ -152,7 +153,7 @@ pub async fn assistant(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let message = body.message.trim().to_string();
     if message.is_empty() {
@@ -464,8 +465,26 @@ async fn execute_tool_call(
         }
         "soropg_run_command" => {
             let command = required_str(&args, "command")?;
+            let options = CommandOptions {
+                tests: TestSelector {
+                    filter: args
+                        .get("filter")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    exact: args.get("exact").and_then(Value::as_bool),
+                    ignored: args.get("ignored").and_then(Value::as_bool),
+                },
+                raw_log: args.get("rawLog").and_then(Value::as_bool),
+                optimize: args.get("optimize").and_then(Value::as_bool),
+                language: args
+                    .get("language")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                locked: args.get("locked").and_then(Value::as_bool),
+            };
             serde_json::to_value(
-                run_project_command_for_user(state, user_key, project_id, command).await?,
+                run_project_command_for_user(state, user_key, project_id, command, &options)
+                    .await?,
             )
             .map_err(|error| error.to_string())
         }
@@ -498,6 +517,7 @@ Rules:
 - The preferred contract layout is Cargo.toml, src/lib.rs, and src/test.rs.
 - Use tools to read and change files. Do not claim edits were made unless a tool succeeded.
 - After edits, run tests or build when it is useful and not excessive.
+- Command results include structured compiler diagnostics with file, line, and column; use them to locate errors.
 - Deployment is not available through tools because wallet signing stays in the browser.
 
 Active project:
@@ -515,7 +535,7 @@ Available tools:
 - soropg_replace_file
 - soropg_delete_file
 - soropg_move_file
-- soropg_run_command with command build, test, or audit
+- soropg_run_command with command build, test, or audit; test accepts an optional filter to run a single test
 
 Custom server context:
 {custom_context}
@@ -602,7 +622,14 @@ fn tool_definitions() -> Value {
                 "name": "soropg_run_command",
                 "description": "Run a whitelisted SoroPG backend command.",
                 "parameters": with_project_id(json!({
-                    "command": { "type": "string", "enum": ["build", "test", "audit"] }
+                    "command": { "type": "string", "enum": ["build", "test", "audit", "bindings"] },
+                    "filter": { "type": "string", "description": "Test command only: run tests whose path contains this string, such as test::test_transfer" },
+                    "exact": { "type": "boolean", "description": "Test command only: match the filter exactly" },
+                    "ignored": { "type": "boolean", "description": "Test command only: run only #[ignore] tests" },
+                    "rawLog": { "type": "boolean", "description": "Audit command only: return the full Scout log instead of the findings summary" },
+                    "optimize": { "type": "boolean", "description": "Build command only: also produce an optimized WASM and report the size saving" },
+                    "language": { "type": "string", "enum": ["typescript", "rust", "python"], "description": "Bindings command only: client language to generate, TypeScript by default" },
+                    "locked": { "type": "boolean", "description": "Build against the project's Cargo.lock with --locked" }
                 }), vec!["command"])
             }
         }
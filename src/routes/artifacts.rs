@@ -0,0 +1,49 @@
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse, Responder};
+use serde_json::json;
+
+use crate::artifacts::{ArtifactMetadata, ARTIFACTS};
+
+/// Serves a stored build by the SHA-256 of its WASM: `{sha256}.wasm` for the
+/// module itself, `{sha256}.json` for its metadata.
+#[get("/artifacts/{name}")]
+pub async fn artifact(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    let (sha256, extension) = match name.rsplit_once('.') {
+        Some((sha256, extension @ ("wasm" | "json"))) => (sha256.to_ascii_lowercase(), extension),
+        _ => return artifact_not_found(),
+    };
+    let Some(build) = ARTIFACTS.find_wasm(&sha256) else {
+        return artifact_not_found();
+    };
+
+    if extension == "json" {
+        return HttpResponse::Ok().json(ArtifactMetadata::from(&build.record));
+    }
+
+    // The content never changes for a given hash.
+    let etag = format!("\"{}\"", sha256);
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type("application/wasm")
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", build.record.output_filename),
+        ))
+        .body(build.wasm)
+}
+
+fn artifact_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": "Artifact not found" }))
+}
@@ -0,0 +1,131 @@
+use actix_web::{http::header, post, web, HttpRequest, HttpResponse, Responder};
+use serde_json::json;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::{
+    bindings::{read_bindings, zip_files, BindingsLanguage},
+    docker::{run_in_docker_with_files_and_id_stream, BuildOptions, OutputChunk, BUILD_COMMAND},
+    jobs::JOBS,
+    limits::CommandKind,
+    models::{extract_main_source, CompileRequest},
+    queue::{client_key, QUEUE},
+    routes::events::{relay_sandbox_run, wait_for_slot, EventSink, StreamFormat},
+};
+
+/// Builds the project in the background and answers `202` with the job id.
+/// `GET /jobs/{id}/result` then serves the client bindings generated from the
+/// fresh WASM, as a JSON file map or, with `?format=zip` or
+/// `Accept: application/zip`, as a zip archive.
+#[post("/bindings")]
+pub async fn bindings(http_req: HttpRequest, req: web::Json<CompileRequest>) -> impl Responder {
+    let language = match BindingsLanguage::parse(req.language.as_deref()) {
+        Ok(language) => language,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let code = match &req.code {
+        Some(c) => c.clone(),
+        None => match &req.files {
+            Some(files_map) => match extract_main_source(files_map) {
+                Some(lib_rs_code) => lib_rs_code,
+                None => {
+                    return HttpResponse::BadRequest()
+                        .body("No code provided and no src/lib.rs or lib.rs file found")
+                }
+            },
+            None => return HttpResponse::BadRequest().body("No code or files provided"),
+        },
+    };
+    let wants_zip = http_req
+        .query_string()
+        .split('&')
+        .any(|pair| pair.eq_ignore_ascii_case("format=zip"))
+        || http_req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("application/zip"));
+
+    let mut ticket = match QUEUE.enqueue(CommandKind::Build, &client_key(&http_req)) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
+    };
+
+    let files = req.files.clone();
+    let options = BuildOptions {
+        bindings: Some(language),
+        locked: req.locked.unwrap_or(false),
+        tenant: Some(client_key(&http_req)),
+        ..BuildOptions::default()
+    };
+    let build_hash = req.input_hash();
+    let job = JOBS.start(CommandKind::Build, Some(build_hash.clone()));
+    let job_id = job.id.clone();
+
+    tokio::spawn(async move {
+        // Nobody reads the stream; the sink records the build log.
+        let (tx, _) = mpsc::unbounded_channel();
+        let sink = EventSink::new(tx, StreamFormat::Text).recording(&job);
+        if !wait_for_slot(&sink, &job, &mut ticket).await {
+            return;
+        }
+        let started = Instant::now();
+        let (output_tx, output_rx) = mpsc::unbounded_channel::<OutputChunk>();
+        let run = run_in_docker_with_files_and_id_stream(
+            code,
+            files,
+            BUILD_COMMAND,
+            Some(build_hash),
+            options,
+            CommandKind::Build,
+            output_tx,
+        );
+        let Some((res, output)) = relay_sandbox_run(&sink, &job, run, output_rx).await else {
+            return;
+        };
+        let recorder = job.recorder();
+        let workspace = match res {
+            Ok((tmp, _output_filename)) => tmp,
+            Err(e) => {
+                let message = format!(
+                    "Bindings Errors: \n{}\n{}",
+                    e,
+                    String::from_utf8_lossy(&output.log)
+                );
+                recorder.result(422, "text/plain; charset=utf-8", None, message.as_bytes());
+                sink.diagnostic("error", &message);
+                sink.failed(&e, started.elapsed());
+                return;
+            }
+        };
+
+        let output = read_bindings(workspace.path(), language).and_then(|files| {
+            if wants_zip {
+                let filename = format!("bindings-{}.zip", language.as_str());
+                zip_files(&files).map(|archive| ("application/zip", Some(filename), archive))
+            } else {
+                let body = json!({
+                    "language": language.as_str(),
+                    "files": files,
+                });
+                Ok(("application/json", None, body.to_string().into_bytes()))
+            }
+        });
+        match output {
+            Ok((content_type, filename, body)) => {
+                recorder.result(200, content_type, filename.as_deref(), &body);
+                sink.exit(Some(0), started.elapsed(), true);
+            }
+            Err(e) => {
+                recorder.result(500, "text/plain; charset=utf-8", None, e.as_bytes());
+                sink.diagnostic("error", &format!("{}\n", e));
+                sink.exit(Some(0), started.elapsed(), false);
+            }
+        }
+    });
+
+    HttpResponse::Accepted()
+        .insert_header(("X-Soropg-Job-Id", job_id.clone()))
+        .insert_header((header::LOCATION, format!("/jobs/{}/result", job_id)))
+        .json(json!({ "id": job_id }))
+}
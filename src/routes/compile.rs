@@ -1,50 +1,164 @@
-use actix_web::{post, web, HttpResponse, Responder};
-use base64::Engine;
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 use bytes::Bytes;
 use futures_util::StreamExt;
-use std::path::PathBuf;
-use std::time::Duration;
-use tokio::{sync::mpsc, time};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use tracing::{error, info};
+use tracing::{info, warn};
 
 use crate::{
-    docker::run_in_docker_with_files_and_id_stream,
-    models::{extract_main_source, CompileRequest},
-    semaphore::SEMAPHORE,
+    artifacts::{contract_build_hash, optimized_build_hash, CachedBuild, ARTIFACTS},
+    attestation::{attest, HostEvidence, VerifyTarget},
+    docker::{
+        artifact_path, optimized_filename, passes_policy, resolved_lockfile,
+        run_in_docker_with_files_and_id_stream, BuildOptions, OutputChannel, OutputChunk,
+        BUILD_COMMAND,
+    },
+    jobs::JOBS,
+    limits::CommandKind,
+    models::{extract_main_source, files_hash, CompileRequest},
+    queue::{client_key, QUEUE},
+    routes::events::{relay_sandbox_run, wait_for_slot, EventSink, StreamFormat},
+    workspace::{workspace_contracts, WorkspaceContract},
 };
 
-const WASM_BASE64_START: &[u8] = b"\n<<<SOROBAN_WASM_BASE64_START>>>\n";
-const WASM_BASE64_END: &[u8] = b"\n<<<SOROBAN_WASM_BASE64_END>>>\n";
+/// The sources a build compiles: the submitted files, or a code-only
+/// request's code as `src/lib.rs`.
+fn build_sources(req: &CompileRequest) -> Option<Cow<'_, HashMap<String, String>>> {
+    match (&req.files, &req.code) {
+        (Some(files), _) => Some(Cow::Borrowed(files)),
+        (None, Some(code)) => Some(Cow::Owned(HashMap::from([(
+            "src/lib.rs".to_string(),
+            code.clone(),
+        )]))),
+        (None, None) => None,
+    }
+}
 
-#[post("/compile")]
-pub async fn compile(req: web::Json<CompileRequest>) -> impl Responder {
-    let hash = {
-        use sha2::{Digest, Sha256};
-        let mut h = Sha256::new();
-        // Hash all files for better cache key
-        if let Some(ref files_map) = req.files {
-            let mut entries: Vec<_> = files_map.iter().collect();
-            entries.sort_by(|a, b| a.0.cmp(b.0));
-            for (filename, content) in entries {
-                h.update(filename.as_bytes());
-                h.update(content.as_bytes());
-            }
-        } else if let Some(ref code) = req.code {
-            h.update(code.as_bytes());
+/// Stores and reports the contracts of a workspace build after the first,
+/// which is reported as the regular artifact.
+fn send_other_contracts(
+    sink: &EventSink,
+    workspace: &Path,
+    contracts: &[WorkspaceContract],
+    hash: &str,
+    build_duration: Duration,
+) -> Result<(), String> {
+    for contract in contracts.iter().skip(1) {
+        let filename = contract.output_filename(Some(hash));
+        let wasm = std::fs::read(artifact_path(workspace, &filename))
+            .map_err(|e| format!("{}: {}", filename, e))?;
+        let key = contract_build_hash(hash, &contract.package);
+        if let Err(e) = ARTIFACTS.put(&key, &filename, &wasm, b"", build_duration) {
+            warn!(hash=%hash, contract=%contract.package, error=%e, "failed to cache contract artifact");
         }
-        hex::encode(h.finalize())
+        sink.contract_artifact(&contract.package, &filename, &wasm, false, false);
+    }
+    Ok(())
+}
+
+#[post("/compile")]
+pub async fn compile(http_req: HttpRequest, req: web::Json<CompileRequest>) -> impl Responder {
+    let format = StreamFormat::from_request(&http_req);
+    let hash = req.input_hash();
+    let verify = match req.verify.as_ref().map(VerifyTarget::from_request) {
+        Some(Ok(target)) => Some(target),
+        Some(Err(e)) => return HttpResponse::BadRequest().body(e),
+        None => None,
     };
+    let options = BuildOptions {
+        optimize: req.optimize.unwrap_or(false),
+        verify,
+        locked: req.locked.unwrap_or(false),
+        tenant: Some(client_key(&http_req)),
+        ..BuildOptions::default()
+    };
+    let optimized_hash = optimized_build_hash(&hash);
+    let contracts = match req.files.as_ref().map(workspace_contracts).transpose() {
+        Ok(contracts) => contracts.flatten(),
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    if contracts.is_some() && (options.optimize || options.verify.is_some()) {
+        return HttpResponse::BadRequest()
+            .body("Optimize and verification builds support single-contract projects only");
+    }
 
-    let permit = match SEMAPHORE.acquire().await {
-        Ok(p) => p,
-        Err(_) => {
-            error!("Semaphore closed");
-            return HttpResponse::ServiceUnavailable().body("Server shutting down");
-        }
+    // An optimized build is only served from cache when both artifacts are.
+    // Verification always rebuilds, with its own toolchain. Sources the
+    // current policies deny take the regular path, which reports why.
+    let cacheable = options.verify.is_none()
+        && build_sources(&req).is_some_and(|sources| passes_policy(&sources));
+    let cached_optimized = if options.optimize && cacheable {
+        ARTIFACTS.get(&optimized_hash)
+    } else {
+        None
     };
+    let cached = cacheable
+        .then(|| ARTIFACTS.get(&hash))
+        .flatten()
+        .filter(|_| !options.optimize || cached_optimized.is_some());
+    // A workspace build is only served from cache when every contract is.
+    let cached_contracts: Option<Vec<CachedBuild>> = match &contracts {
+        Some(contracts) if cached.is_some() => contracts
+            .iter()
+            .skip(1)
+            .map(|contract| ARTIFACTS.get(&contract_build_hash(&hash, &contract.package)))
+            .collect(),
+        _ => Some(Vec::new()),
+    };
+    let cached = cached.filter(|_| cached_contracts.is_some());
+    if let Some(cached) = cached {
+        info!(hash=%hash, filename=%cached.record.output_filename, "serving cached build");
+        let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+        let sink = EventSink::new(tx, format);
+        sink.log(OutputChannel::Stdout, Bytes::from(cached.log));
+        match &contracts {
+            Some(contracts) => {
+                sink.contract_artifact(
+                    &contracts[0].package,
+                    &cached.record.output_filename,
+                    &cached.wasm,
+                    true,
+                    true,
+                );
+                let others = cached_contracts.unwrap_or_default();
+                for (contract, build) in contracts.iter().skip(1).zip(others) {
+                    sink.contract_artifact(
+                        &contract.package,
+                        &build.record.output_filename,
+                        &build.wasm,
+                        true,
+                        false,
+                    );
+                }
+            }
+            None => {
+                sink.artifact(&cached.record.output_filename, &cached.wasm, true);
+            }
+        }
+        if let Some(lockfile) = &cached.lockfile {
+            sink.lockfile(lockfile);
+        }
+        if let Some(optimized) = cached_optimized {
+            sink.optimized_artifact(
+                &optimized.record.output_filename,
+                &optimized.wasm,
+                cached.wasm.len(),
+                true,
+            );
+        }
+        sink.exit(Some(0), Duration::ZERO, true);
+        drop(sink);
 
-    let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+        let stream = UnboundedReceiverStream::new(rx).map(Ok::<Bytes, actix_web::Error>);
+        return HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header(("X-Soropg-Cache", "hit"))
+            .streaming(stream);
+    }
 
     // Extract code from either the code field or lib.rs from files
     let code = match &req.code {
@@ -64,61 +178,203 @@ pub async fn compile(req: web::Json<CompileRequest>) -> impl Responder {
         }
     };
 
+    let mut ticket = match QUEUE.enqueue(CommandKind::Build, &client_key(&http_req)) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
     let files = req.files.clone();
     let build_hash = hash.clone();
+    // Attestations name the submitted files, whatever key the build caches under.
+    let source_sha256 = req
+        .files
+        .as_ref()
+        .map(files_hash)
+        .unwrap_or_else(|| hash.clone());
+
+    let job = JOBS.start(CommandKind::Build, Some(hash.clone()));
+    let job_id = job.id.clone();
 
     tokio::spawn(async move {
-        let _permit = permit;
-        let mut heartbeat = time::interval(Duration::from_secs(25));
+        let sink = EventSink::new(tx, format).recording(&job);
+        sink.job(&job.id);
+        if !wait_for_slot(&sink, &job, &mut ticket).await {
+            return;
+        }
+        let started = Instant::now();
+        let (output_tx, output_rx) = mpsc::unbounded_channel::<OutputChunk>();
 
         let compile_fut = run_in_docker_with_files_and_id_stream(
             code,
             files,
-            "stellar contract build",
+            BUILD_COMMAND,
             Some(build_hash),
-            tx.clone(),
+            options.clone(),
+            CommandKind::Build,
+            output_tx,
         );
-        tokio::pin!(compile_fut);
+        let Some((res, output)) = relay_sandbox_run(&sink, &job, compile_fut, output_rx).await
+        else {
+            return;
+        };
 
-        loop {
-            tokio::select! {
-                _ = heartbeat.tick() => {
-                    if tx.send(Bytes::from_static(b" ")).is_err() {
-                        break;
-                    }
-                }
-                res = &mut compile_fut => {
-                    match res {
-                        Ok((_tmp, output_filename)) => {
-                            let path = PathBuf::from(format!("/tmp/{}", output_filename));
+        match res {
+            Ok((tmp, output_filename)) => {
+                let path = artifact_path(tmp.path(), &output_filename);
+                match std::fs::read(&path) {
+                    Ok(wasm) => {
+                        // Pinned-toolchain builds stay out of the regular cache.
+                        if options.verify.is_none() {
+                            if let Err(e) = ARTIFACTS.put(
+                                &hash,
+                                &output_filename,
+                                &wasm,
+                                &output.log,
+                                started.elapsed(),
+                            ) {
+                                warn!(hash=%hash, error=%e, "failed to cache build artifact");
+                            }
+                        }
+                        match &contracts {
+                            Some(contracts) => {
+                                sink.contract_artifact(
+                                    &contracts[0].package,
+                                    &output_filename,
+                                    &wasm,
+                                    false,
+                                    true,
+                                );
+                                if let Err(e) = send_other_contracts(
+                                    &sink,
+                                    tmp.path(),
+                                    contracts,
+                                    &hash,
+                                    started.elapsed(),
+                                ) {
+                                    sink.diagnostic("error", &format!("Compile Error: {}\n", e));
+                                    sink.exit(None, started.elapsed(), false);
+                                    return;
+                                }
+                            }
+                            None => {
+                                sink.artifact(&output_filename, &wasm, false);
+                            }
+                        }
+                        if let Some(lockfile) = resolved_lockfile(tmp.path()) {
+                            if options.verify.is_none() {
+                                if let Err(e) = ARTIFACTS.put_lockfile(&hash, &lockfile) {
+                                    warn!(hash=%hash, error=%e, "failed to cache resolved lockfile");
+                                }
+                            }
+                            sink.lockfile(&lockfile);
+                        }
+                        if options.optimize {
+                            let optimized_filename = optimized_filename(&output_filename);
+                            let path = artifact_path(tmp.path(), &optimized_filename);
                             match std::fs::read(&path) {
-                                Ok(wasm) => {
-                                    let encoded = base64::engine::general_purpose::STANDARD.encode(wasm);
-                                    let _ = tx.send(Bytes::from_static(WASM_BASE64_START));
-                                    let _ = tx.send(Bytes::from(encoded));
-                                    let _ = tx.send(Bytes::from_static(WASM_BASE64_END));
-                                    info!(hash=%hash, filename=%output_filename, "compiled successfully");
+                                Ok(optimized) => {
+                                    if let Err(e) = ARTIFACTS.put(
+                                        &optimized_hash,
+                                        &optimized_filename,
+                                        &optimized,
+                                        b"",
+                                        started.elapsed(),
+                                    ) {
+                                        warn!(hash=%hash, error=%e, "failed to cache optimized artifact");
+                                    }
+                                    sink.optimized_artifact(
+                                        &optimized_filename,
+                                        &optimized,
+                                        wasm.len(),
+                                        false,
+                                    );
                                 }
                                 Err(e) => {
-                                    let msg = format!("Compile Error: {}\n", e);
-                                    let _ = tx.send(Bytes::from(msg));
+                                    sink.diagnostic("error", &format!("Optimize Error: {}\n", e));
+                                    sink.exit(None, started.elapsed(), false);
+                                    return;
                                 }
                             }
                         }
-                        Err(e) => {
-                            let msg = format!("Build Errors: \n{}\n", e);
-                            let _ = tx.send(Bytes::from(msg));
+                        if let Some(target) = &options.verify {
+                            let attested = match HostEvidence::gather(target).await {
+                                Ok(evidence) => {
+                                    attest(tmp.path(), &wasm, &source_sha256, target, evidence)
+                                }
+                                Err(e) => Err(e),
+                            };
+                            match attested {
+                                Ok(signed) => {
+                                    info!(hash=%hash, matches=signed.attestation.matches, "verification build attested");
+                                    sink.attestation(&signed);
+                                }
+                                Err(e) => {
+                                    sink.diagnostic(
+                                        "error",
+                                        &format!("Verification Error: {}\n", e),
+                                    );
+                                    sink.exit(None, started.elapsed(), false);
+                                    return;
+                                }
+                            }
                         }
+                        sink.exit(Some(0), started.elapsed(), true);
+                        info!(hash=%hash, filename=%output_filename, "compiled successfully");
+                    }
+                    Err(e) => {
+                        sink.diagnostic("error", &format!("Compile Error: {}\n", e));
+                        sink.exit(None, started.elapsed(), false);
                     }
-                    break;
                 }
             }
+            Err(e) => {
+                sink.diagnostic("error", &format!("Build Errors: \n{}\n", e));
+                sink.failed(&e, started.elapsed());
+            }
         }
     });
 
     let stream = UnboundedReceiverStream::new(rx).map(Ok::<Bytes, actix_web::Error>);
 
     HttpResponse::Ok()
-        .content_type("text/plain; charset=utf-8")
+        .content_type(format.content_type())
+        .insert_header(("X-Soropg-Job-Id", job_id))
         .streaming(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App};
+    use sha2::{Digest, Sha256};
+
+    #[actix_web::test]
+    async fn serves_code_only_builds_from_cache() {
+        std::env::set_var(
+            "SOROPG_ARTIFACT_DIR",
+            tempfile::TempDir::new().unwrap().keep(),
+        );
+        let code = "#![no_std]\npub fn cached() {}\n";
+        let hash = hex::encode(Sha256::digest(code.as_bytes()));
+        ARTIFACTS
+            .put(
+                &hash,
+                "project.wasm",
+                b"\0asm",
+                b"Finished\n",
+                Duration::ZERO,
+            )
+            .unwrap();
+
+        let app = actix_test::init_service(App::new().service(compile)).await;
+        let request = actix_test::TestRequest::post()
+            .uri("/compile")
+            .set_json(serde_json::json!({ "code": code }))
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.headers().get("X-Soropg-Cache").unwrap(), "hit");
+        let body = actix_test::read_body(response).await;
+        assert!(String::from_utf8_lossy(&body).contains("Finished\n"));
+    }
+}
@@ -0,0 +1,837 @@
+use std::{future::Future, time::Duration};
+
+use actix_web::{http::header, HttpRequest};
+use base64::Engine;
+use bytes::Bytes;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::{sync::mpsc, time};
+
+use crate::{
+    attestation::SignedAttestation,
+    diagnostics::{parse_cargo_line, CargoLine, Diagnostic},
+    docker::{OutputChannel, OutputChunk, RunTimings, SandboxError},
+    jobs::{JobHandle, JobRecorder, JobStatus},
+    queue::{QueuePosition, QueueTicket, Slot},
+    scout::{render_report, AuditReport},
+    test_results::{TestRecord, TestReport},
+};
+
+const WASM_BASE64_START: &[u8] = b"\n<<<SOROBAN_WASM_BASE64_START>>>\n";
+const WASM_BASE64_END: &[u8] = b"\n<<<SOROBAN_WASM_BASE64_END>>>\n";
+const CARGO_LOCK_BASE64_START: &[u8] = b"\n<<<SOROBAN_CARGO_LOCK_BASE64_START>>>\n";
+const CARGO_LOCK_BASE64_END: &[u8] = b"\n<<<SOROBAN_CARGO_LOCK_BASE64_END>>>\n";
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson; charset=utf-8";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+
+/// Wire format of a sandbox stream. Plain text is the default so the browser
+/// IDE keeps working; NDJSON is opted into with `Accept: application/x-ndjson`
+/// or `?format=ndjson`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    Text,
+    Ndjson,
+}
+
+impl StreamFormat {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let query_wants_ndjson = req
+            .query_string()
+            .split('&')
+            .any(|pair| pair.eq_ignore_ascii_case("format=ndjson"));
+        let accept_wants_ndjson = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("application/x-ndjson"));
+
+        if query_wants_ndjson || accept_wants_ndjson {
+            StreamFormat::Ndjson
+        } else {
+            StreamFormat::Text
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            StreamFormat::Text => "text/plain; charset=utf-8",
+            StreamFormat::Ndjson => NDJSON_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Writes typed events to a streaming response in the negotiated format.
+///
+/// In text mode every event degrades to the output the frontend has always
+/// parsed: raw logs, whitespace heartbeats and base64 sentinel markers.
+///
+/// A recording sink also writes the plain-text log and the outcome to the
+/// job's history entry, whatever the wire format.
+#[derive(Clone)]
+pub struct EventSink {
+    tx: mpsc::UnboundedSender<Bytes>,
+    format: StreamFormat,
+    logs: bool,
+    recorder: Option<JobRecorder>,
+}
+
+impl EventSink {
+    pub fn new(tx: mpsc::UnboundedSender<Bytes>, format: StreamFormat) -> Self {
+        EventSink {
+            tx,
+            format,
+            logs: true,
+            recorder: None,
+        }
+    }
+
+    /// Records the stream into the job's history.
+    pub fn recording(mut self, job: &JobHandle) -> Self {
+        self.recorder = Some(job.recorder());
+        self
+    }
+
+    fn record(&self, bytes: &[u8]) {
+        if let Some(recorder) = &self.recorder {
+            recorder.append(bytes);
+        }
+    }
+
+    fn record_outcome(
+        &self,
+        status: JobStatus,
+        code: Option<i32>,
+        killed: Option<&str>,
+        duration: Duration,
+    ) {
+        if let Some(recorder) = &self.recorder {
+            recorder.finish(status, code, killed, duration);
+        }
+    }
+
+    /// Drops sandbox log output; structured events are still sent.
+    pub fn without_logs(mut self) -> Self {
+        self.logs = false;
+        self
+    }
+
+    pub fn format(&self) -> StreamFormat {
+        self.format
+    }
+
+    fn send_event(&self, event: &str, data: Value) -> bool {
+        let line = json!({ "event": event, "data": data }).to_string() + "\n";
+        self.tx.send(Bytes::from(line)).is_ok()
+    }
+
+    pub fn log(&self, channel: OutputChannel, bytes: Bytes) -> bool {
+        self.record(&bytes);
+        if !self.logs {
+            return !self.tx.is_closed();
+        }
+        match self.format {
+            StreamFormat::Text => self.tx.send(bytes).is_ok(),
+            StreamFormat::Ndjson => self.send_event(
+                "log",
+                json!({
+                    "channel": channel.as_str(),
+                    "text": String::from_utf8_lossy(&bytes),
+                }),
+            ),
+        }
+    }
+
+    pub fn heartbeat(&self) -> bool {
+        match self.format {
+            StreamFormat::Text => self.tx.send(Bytes::from_static(b" ")).is_ok(),
+            StreamFormat::Ndjson => self.send_event("heartbeat", json!({})),
+        }
+    }
+
+    pub fn artifact(&self, filename: &str, wasm: &[u8], cached: bool) -> bool {
+        if let Some(recorder) = &self.recorder {
+            recorder.artifact(filename, wasm, false);
+        }
+        let encoded = base64::engine::general_purpose::STANDARD.encode(wasm);
+        match self.format {
+            StreamFormat::Text => {
+                let _ = self.tx.send(Bytes::from_static(WASM_BASE64_START));
+                let _ = self.tx.send(Bytes::from(encoded));
+                self.tx.send(Bytes::from_static(WASM_BASE64_END)).is_ok()
+            }
+            StreamFormat::Ndjson => self.send_event(
+                "artifact",
+                json!({
+                    "filename": filename,
+                    "size": wasm.len(),
+                    "sha256": hex::encode(Sha256::digest(wasm)),
+                    "cached": cached,
+                    "optimized": false,
+                    "wasmBase64": encoded,
+                }),
+            ),
+        }
+    }
+
+    /// Reports one contract of a Cargo workspace build. Text mode keeps a
+    /// single WASM payload, the `primary` contract, and lists the others with
+    /// their download path.
+    pub fn contract_artifact(
+        &self,
+        contract: &str,
+        filename: &str,
+        wasm: &[u8],
+        cached: bool,
+        primary: bool,
+    ) -> bool {
+        if let Some(recorder) = &self.recorder {
+            if primary {
+                recorder.artifact(filename, wasm, false);
+            }
+            recorder.contract(filename, wasm);
+        }
+        let sha256 = hex::encode(Sha256::digest(wasm));
+        let encoded = base64::engine::general_purpose::STANDARD.encode(wasm);
+        match self.format {
+            StreamFormat::Text if primary => {
+                let _ = self.tx.send(Bytes::from_static(WASM_BASE64_START));
+                let _ = self.tx.send(Bytes::from(encoded));
+                self.tx.send(Bytes::from_static(WASM_BASE64_END)).is_ok()
+            }
+            StreamFormat::Text => {
+                let summary = format!(
+                    "\nContract {}: {} ({} bytes) /artifacts/{}.wasm\n",
+                    contract,
+                    filename,
+                    wasm.len(),
+                    sha256
+                );
+                self.record(summary.as_bytes());
+                self.tx.send(Bytes::from(summary)).is_ok()
+            }
+            StreamFormat::Ndjson => self.send_event(
+                "artifact",
+                json!({
+                    "contract": contract,
+                    "filename": filename,
+                    "size": wasm.len(),
+                    "sha256": sha256,
+                    "cached": cached,
+                    "optimized": false,
+                    "wasmBase64": encoded,
+                }),
+            ),
+        }
+    }
+
+    /// Reports the `stellar contract optimize` output of a build next to the
+    /// regular artifact. Text mode keeps a single WASM payload for the
+    /// frontend and only logs the size saving.
+    pub fn optimized_artifact(
+        &self,
+        filename: &str,
+        wasm: &[u8],
+        original_size: usize,
+        cached: bool,
+    ) -> bool {
+        if let Some(recorder) = &self.recorder {
+            recorder.artifact(filename, wasm, true);
+        }
+        let sha256 = hex::encode(Sha256::digest(wasm));
+        let summary = format!(
+            "\nOptimized WASM: {} ({} bytes, {} bytes smaller than {} bytes) /artifacts/{}.wasm\n",
+            filename,
+            wasm.len(),
+            original_size.saturating_sub(wasm.len()),
+            original_size,
+            sha256
+        );
+        self.record(summary.as_bytes());
+        match self.format {
+            StreamFormat::Text => self.tx.send(Bytes::from(summary)).is_ok(),
+            StreamFormat::Ndjson => self.send_event(
+                "artifact",
+                json!({
+                    "filename": filename,
+                    "size": wasm.len(),
+                    "sha256": sha256,
+                    "cached": cached,
+                    "optimized": true,
+                    "originalSize": original_size,
+                    "wasmBase64": base64::engine::general_purpose::STANDARD.encode(wasm),
+                }),
+            ),
+        }
+    }
+
+    /// Sends the `Cargo.lock` a build resolved, so clients can persist it.
+    pub fn lockfile(&self, content: &str) -> bool {
+        match self.format {
+            StreamFormat::Text => {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(content);
+                let _ = self.tx.send(Bytes::from_static(CARGO_LOCK_BASE64_START));
+                let _ = self.tx.send(Bytes::from(encoded));
+                self.tx
+                    .send(Bytes::from_static(CARGO_LOCK_BASE64_END))
+                    .is_ok()
+            }
+            StreamFormat::Ndjson => self.send_event(
+                "lockfile",
+                json!({
+                    "path": "Cargo.lock",
+                    "sha256": hex::encode(Sha256::digest(content.as_bytes())),
+                    "content": content,
+                }),
+            ),
+        }
+    }
+
+    /// Reports the signed outcome of a verification build.
+    pub fn attestation(&self, signed: &SignedAttestation) -> bool {
+        let attestation = &signed.attestation;
+        let summary = format!(
+            "\nVerification: {} (built {}, expected {})\n",
+            if attestation.matches {
+                "match"
+            } else {
+                "MISMATCH"
+            },
+            attestation.output_sha256,
+            attestation.expected_sha256
+        );
+        self.record(summary.as_bytes());
+        match self.format {
+            StreamFormat::Text => {
+                let document = serde_json::to_string(signed).unwrap_or_default();
+                self.tx
+                    .send(Bytes::from(format!(
+                        "{}Attestation: {}\n",
+                        summary, document
+                    )))
+                    .is_ok()
+            }
+            StreamFormat::Ndjson => self.send_event("attestation", json!(signed)),
+        }
+    }
+
+    /// Reports a problem with the run. Text mode writes `message` verbatim, so
+    /// callers keep the exact wording the plain stream has always used.
+    pub fn diagnostic(&self, severity: &str, message: &str) -> bool {
+        self.record(message.as_bytes());
+        match self.format {
+            StreamFormat::Text => self.tx.send(Bytes::from(message.to_string())).is_ok(),
+            StreamFormat::Ndjson => {
+                self.compile_diagnostic(&Diagnostic::message(severity, message))
+            }
+        }
+    }
+
+    /// Reports a structured compiler diagnostic. Text mode already received
+    /// the rendered form through the log, so nothing extra is written.
+    pub fn compile_diagnostic(&self, diagnostic: &Diagnostic) -> bool {
+        match self.format {
+            StreamFormat::Text => true,
+            StreamFormat::Ndjson => self.send_event(
+                "diagnostic",
+                serde_json::to_value(diagnostic).unwrap_or_else(|_| json!({})),
+            ),
+        }
+    }
+
+    /// Reports one test case. Text mode already rendered it in the log.
+    pub fn test_result(&self, record: &TestRecord) -> bool {
+        match self.format {
+            StreamFormat::Text => true,
+            StreamFormat::Ndjson => self.send_event(
+                "test",
+                serde_json::to_value(record).unwrap_or_else(|_| json!({})),
+            ),
+        }
+    }
+
+    /// Reports the Scout findings of an audit as a single event.
+    pub fn findings(&self, report: &AuditReport) -> bool {
+        let rendered = render_report(report);
+        self.record(rendered.as_bytes());
+        match self.format {
+            StreamFormat::Text => self.tx.send(Bytes::from(rendered)).is_ok(),
+            StreamFormat::Ndjson => self.send_event("findings", json!(report)),
+        }
+    }
+
+    /// Final event of every stream. Text mode has no terminal marker.
+    pub fn exit(&self, code: Option<i32>, duration: Duration, success: bool) -> bool {
+        let status = if success {
+            JobStatus::Succeeded
+        } else {
+            JobStatus::Failed
+        };
+        self.record_outcome(status, code, None, duration);
+        match self.format {
+            StreamFormat::Text => true,
+            StreamFormat::Ndjson => self.send_event(
+                "exit",
+                json!({
+                    "code": code,
+                    "success": success,
+                    "durationMs": duration.as_millis() as u64,
+                }),
+            ),
+        }
+    }
+
+    /// First event of a sandbox stream, carrying the id `DELETE /jobs/{id}`
+    /// accepts. Text streams expose it through the `X-Soropg-Job-Id` header.
+    pub fn job(&self, id: &str) -> bool {
+        match self.format {
+            StreamFormat::Text => true,
+            StreamFormat::Ndjson => self.send_event("job", json!({ "id": id })),
+        }
+    }
+
+    /// Sent while the job waits for a sandbox slot, whenever its place in the
+    /// queue changes.
+    pub fn queued(&self, position: QueuePosition) -> bool {
+        match self.format {
+            StreamFormat::Text => self
+                .tx
+                .send(Bytes::from(format!(
+                    "Queued: position {}, about {}s\n",
+                    position.position, position.eta_secs
+                )))
+                .is_ok(),
+            StreamFormat::Ndjson => self.send_event("queued", json!(position)),
+        }
+    }
+
+    /// Where a finished run's time went: waiting in the queue, starting the
+    /// sandbox, and the job itself, next to the median startup of recent runs.
+    pub fn timings(&self, queue: Duration, timings: &RunTimings) -> bool {
+        let startup = if timings.warm { " (warm)" } else { "" };
+        let text = format!(
+            "Timings: queue {:.2}s, boot {:.2}s{}, build {:.2}s, p50 boot {:.2}s\n",
+            queue.as_secs_f64(),
+            timings.boot_ms as f64 / 1000.0,
+            startup,
+            timings.build_ms as f64 / 1000.0,
+            timings.boot_p50_ms as f64 / 1000.0
+        );
+        self.record(text.as_bytes());
+        match self.format {
+            StreamFormat::Text => self.tx.send(Bytes::from(text)).is_ok(),
+            StreamFormat::Ndjson => self.send_event(
+                "timings",
+                json!({
+                    "queueMs": queue.as_millis() as u64,
+                    "bootMs": timings.boot_ms,
+                    "buildMs": timings.build_ms,
+                    "warm": timings.warm,
+                    "bootP50Ms": timings.boot_p50_ms,
+                }),
+            ),
+        }
+    }
+
+    /// Final event of a run cancelled through the jobs endpoint.
+    pub fn cancelled(&self, duration: Duration) -> bool {
+        self.record(b"\nCancelled\n");
+        self.record_outcome(JobStatus::Cancelled, None, Some("cancelled"), duration);
+        match self.format {
+            StreamFormat::Text => self.tx.send(Bytes::from_static(b"\nCancelled\n")).is_ok(),
+            StreamFormat::Ndjson => self.send_event(
+                "exit",
+                json!({
+                    "code": null,
+                    "success": false,
+                    "durationMs": duration.as_millis() as u64,
+                    "killed": "cancelled",
+                    "message": "cancelled by request",
+                }),
+            ),
+        }
+    }
+
+    /// Final event of a failed sandbox run. Runs killed for exceeding a limit
+    /// say so, e.g. `timed out after 300s`.
+    pub fn failed(&self, error: &SandboxError, duration: Duration) -> bool {
+        self.record_outcome(
+            JobStatus::Failed,
+            error.exit_code,
+            error.killed.map(|reason| reason.as_str()),
+            duration,
+        );
+        match (self.format, error.killed) {
+            (StreamFormat::Text, _) => true,
+            (StreamFormat::Ndjson, None) => self.exit(error.exit_code, duration, false),
+            (StreamFormat::Ndjson, Some(reason)) => self.send_event(
+                "exit",
+                json!({
+                    "code": error.exit_code,
+                    "success": false,
+                    "durationMs": duration.as_millis() as u64,
+                    "killed": reason.as_str(),
+                    "message": reason.to_string(),
+                }),
+            ),
+        }
+    }
+}
+
+/// Splits raw pipe chunks into complete lines so NDJSON log events never cut a
+/// line (or a UTF-8 sequence) in half.
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, chunk: &[u8]) -> Option<Bytes> {
+        self.pending.extend_from_slice(chunk);
+        let end = self.pending.iter().rposition(|byte| *byte == b'\n')? + 1;
+        let rest = self.pending.split_off(end);
+        Some(Bytes::from(std::mem::replace(&mut self.pending, rest)))
+    }
+
+    fn flush(&mut self) -> Option<Bytes> {
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(Bytes::from(std::mem::take(&mut self.pending)))
+        }
+    }
+}
+
+/// Output relayed while a sandbox run was in flight.
+pub struct RelayedOutput {
+    /// Human-readable log, with cargo and libtest JSON replaced by their
+    /// rendered text.
+    pub log: Vec<u8>,
+    pub tests: Vec<TestRecord>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl RelayedOutput {
+    /// Last `lines` lines of the log, for reporting failures of runs whose
+    /// log was not streamed.
+    pub fn tail(&self, lines: usize) -> String {
+        let log = String::from_utf8_lossy(&self.log);
+        let all: Vec<&str> = log.lines().collect();
+        let start = all.len().saturating_sub(lines);
+        all[start..].join("\n") + "\n"
+    }
+}
+
+struct OutputRelay<'a> {
+    sink: &'a EventSink,
+    queue_wait: Duration,
+    stdout: LineBuffer,
+    stderr: LineBuffer,
+    log: Vec<u8>,
+    tests: TestReport,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl OutputRelay<'_> {
+    fn buffer(&mut self, channel: OutputChannel) -> &mut LineBuffer {
+        match channel {
+            OutputChannel::Stderr => &mut self.stderr,
+            _ => &mut self.stdout,
+        }
+    }
+
+    fn forward(&mut self, (channel, bytes): OutputChunk) {
+        if channel == OutputChannel::Timings {
+            if let Ok(timings) = serde_json::from_slice::<RunTimings>(&bytes) {
+                self.sink.timings(self.queue_wait, &timings);
+            }
+            return;
+        }
+        // Stdout is always line buffered because cargo JSON messages arrive
+        // there; raw stderr keeps streaming byte for byte in text mode.
+        if channel == OutputChannel::Stderr && self.sink.format() == StreamFormat::Text {
+            self.emit_text(channel, bytes);
+        } else if let Some(lines) = self.buffer(channel).push(&bytes) {
+            self.emit_lines(channel, &lines);
+        }
+    }
+
+    fn emit_text(&mut self, channel: OutputChannel, bytes: Bytes) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.log.extend_from_slice(&bytes);
+        self.sink.log(channel, bytes);
+    }
+
+    fn emit_lines(&mut self, channel: OutputChannel, lines: &[u8]) {
+        let mut text = Vec::with_capacity(lines.len());
+        for line in lines.split_inclusive(|byte| *byte == b'\n') {
+            let line_text = String::from_utf8_lossy(line);
+            if let Some(rendered) = self.tests.consume_line(&line_text) {
+                text.extend_from_slice(rendered.as_bytes());
+                continue;
+            }
+            match parse_cargo_line(&line_text) {
+                CargoLine::Diagnostic(diagnostic) => {
+                    if let Some(rendered) = &diagnostic.rendered {
+                        text.extend_from_slice(rendered.as_bytes());
+                    }
+                    self.emit_text(channel, Bytes::from(std::mem::take(&mut text)));
+                    self.sink.compile_diagnostic(&diagnostic);
+                    self.diagnostics.push(*diagnostic);
+                }
+                CargoLine::Message => {}
+                CargoLine::Text => text.extend_from_slice(line),
+            }
+        }
+        self.emit_text(channel, Bytes::from(text));
+    }
+
+    fn finish(mut self) -> RelayedOutput {
+        for channel in [OutputChannel::Stdout, OutputChannel::Stderr] {
+            if let Some(rest) = self.buffer(channel).flush() {
+                self.emit_lines(channel, &rest);
+            }
+        }
+        RelayedOutput {
+            log: self.log,
+            tests: self.tests.into_records(),
+            diagnostics: self.diagnostics,
+        }
+    }
+}
+
+/// Waits for the job's turn in the queue, reporting position changes and
+/// sending heartbeats. Returns `false` when the job was cancelled first;
+/// dropping the ticket then leaves the queue. A client going away does not
+/// give up the job's place, so it can reattach through the jobs API.
+pub async fn wait_for_slot(sink: &EventSink, job: &JobHandle, ticket: &mut QueueTicket) -> bool {
+    let mut heartbeat = time::interval(HEARTBEAT_INTERVAL);
+    heartbeat.reset();
+    let started = time::Instant::now();
+
+    loop {
+        tokio::select! {
+            slot = ticket.next() => match slot {
+                Slot::Admitted => {
+                    job.admitted();
+                    return true;
+                }
+                Slot::Waiting(position) => {
+                    sink.queued(position);
+                }
+            },
+            _ = heartbeat.tick() => {
+                sink.heartbeat();
+            }
+            _ = job.cancelled() => {
+                sink.cancelled(started.elapsed());
+                return false;
+            }
+        }
+    }
+}
+
+/// Drives a sandbox run while relaying its output and sending heartbeats.
+///
+/// Returns `None` when the job was cancelled before the run finished. The run
+/// is dropped then, which kills its container. A client going away does not
+/// stop the run: it goes on within its timeout, and its log stays available
+/// from `GET /jobs/{id}/log`.
+pub async fn relay_sandbox_run<T, E, F>(
+    sink: &EventSink,
+    job: &JobHandle,
+    run: F,
+    mut output: mpsc::UnboundedReceiver<OutputChunk>,
+) -> Option<(Result<T, E>, RelayedOutput)>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    let mut heartbeat = time::interval(HEARTBEAT_INTERVAL);
+    let mut relay = OutputRelay {
+        sink,
+        queue_wait: job.queue_wait(),
+        stdout: LineBuffer::default(),
+        stderr: LineBuffer::default(),
+        log: Vec::new(),
+        tests: TestReport::default(),
+        diagnostics: Vec::new(),
+    };
+    let started = time::Instant::now();
+    tokio::pin!(run);
+
+    loop {
+        tokio::select! {
+            _ = heartbeat.tick() => {
+                sink.heartbeat();
+            }
+            _ = job.cancelled() => {
+                sink.cancelled(started.elapsed());
+                return None;
+            }
+            Some(chunk) = output.recv() => relay.forward(chunk),
+            res = &mut run => {
+                while let Ok(chunk) = output.try_recv() {
+                    relay.forward(chunk);
+                }
+                return Some((res, relay.finish()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jobs::scratch_registry, limits::CommandKind};
+
+    #[test]
+    fn line_buffer_holds_back_partial_lines() {
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(b"Compiling").is_none());
+        assert_eq!(
+            buffer.push(b" soroban\nFinis").unwrap(),
+            "Compiling soroban\n"
+        );
+        assert_eq!(buffer.flush().unwrap(), "Finis");
+        assert!(buffer.flush().is_none());
+    }
+
+    #[test]
+    fn ndjson_events_are_tagged() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sink = EventSink::new(tx, StreamFormat::Ndjson);
+        sink.log(OutputChannel::Stderr, Bytes::from_static(b"warning\n"));
+        sink.exit(Some(101), Duration::from_millis(1500), false);
+
+        let log: Value = serde_json::from_slice(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(log["event"], "log");
+        assert_eq!(log["data"]["channel"], "stderr");
+        let exit: Value = serde_json::from_slice(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(exit["event"], "exit");
+        assert_eq!(exit["data"]["code"], 101);
+        assert_eq!(exit["data"]["success"], false);
+        assert_eq!(exit["data"]["durationMs"], 1500);
+    }
+
+    #[actix_web::test]
+    async fn cancelled_job_ends_stream() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sink = EventSink::new(tx, StreamFormat::Ndjson);
+        let (_output_tx, output_rx) = mpsc::unbounded_channel();
+        let jobs = scratch_registry();
+        let job = jobs.start(CommandKind::Build, None);
+        assert!(jobs.cancel(&job.id));
+
+        let run = std::future::pending::<Result<(), ()>>();
+        assert!(relay_sandbox_run(&sink, &job, run, output_rx)
+            .await
+            .is_none());
+
+        let mut last = None;
+        while let Ok(chunk) = rx.try_recv() {
+            last = Some(chunk);
+        }
+        let exit: Value = serde_json::from_slice(&last.unwrap()).unwrap();
+        assert_eq!(exit["event"], "exit");
+        assert_eq!(exit["data"]["killed"], "cancelled");
+    }
+
+    #[actix_web::test]
+    async fn run_outlives_its_client() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        drop(rx);
+        let jobs = scratch_registry();
+        let job = jobs.start(CommandKind::Build, None);
+        let sink = EventSink::new(tx, StreamFormat::Text).recording(&job);
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        let run = async move {
+            time::sleep(Duration::from_millis(20)).await;
+            let _ = output_tx.send((OutputChannel::Stderr, Bytes::from_static(b"Compiling\n")));
+            Ok::<(), ()>(())
+        };
+
+        let (res, _) = relay_sandbox_run(&sink, &job, run, output_rx)
+            .await
+            .unwrap();
+        assert!(res.is_ok());
+        let log = jobs.log(&job.id).unwrap();
+        assert_eq!(crate::jobs::read_log(&log.path, 0).unwrap(), b"Compiling\n");
+    }
+
+    #[actix_web::test]
+    async fn relay_renders_cargo_diagnostics_as_text() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sink = EventSink::new(tx, StreamFormat::Text);
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        let diagnostic = r#"{"reason":"compiler-message","message":{"message":"unused variable","code":null,"level":"warning","spans":[],"children":[],"rendered":"warning: unused variable\n"}}"#;
+        output_tx
+            .send((
+                OutputChannel::Stdout,
+                Bytes::from(format!("{}\n", diagnostic)),
+            ))
+            .unwrap();
+        output_tx
+            .send((OutputChannel::Stdout, Bytes::from_static(b"Finished")))
+            .unwrap();
+        drop(output_tx);
+
+        let job = scratch_registry().start(CommandKind::Test, None);
+        let (res, output) = relay_sandbox_run(&sink, &job, async { Ok::<(), ()>(()) }, output_rx)
+            .await
+            .unwrap();
+        assert!(res.is_ok());
+        assert_eq!(output.log, b"warning: unused variable\nFinished");
+        assert_eq!(output.diagnostics[0].message, "unused variable");
+
+        let mut streamed = Vec::new();
+        while let Ok(chunk) = rx.try_recv() {
+            streamed.extend_from_slice(&chunk);
+        }
+        assert!(streamed.ends_with(b"warning: unused variable\nFinished"));
+    }
+
+    #[actix_web::test]
+    async fn relay_reports_run_timings() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sink = EventSink::new(tx, StreamFormat::Ndjson);
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        // The sandbox cannot pass its own figures off as the server's.
+        output_tx
+            .send((
+                OutputChannel::Stdout,
+                Bytes::from_static(
+                    b"{\"bootMs\":1,\"buildMs\":1,\"warm\":false,\"bootP50Ms\":1}\n",
+                ),
+            ))
+            .unwrap();
+        output_tx
+            .send((
+                OutputChannel::Timings,
+                Bytes::from_static(
+                    b"{\"bootMs\":400,\"buildMs\":1200,\"warm\":true,\"bootP50Ms\":350}",
+                ),
+            ))
+            .unwrap();
+        drop(output_tx);
+
+        let job = scratch_registry().start(CommandKind::Build, None);
+        job.admitted();
+        let (_, output) = relay_sandbox_run(&sink, &job, async { Ok::<(), ()>(()) }, output_rx)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.log).contains("\"bootMs\":1"));
+
+        let event: Value = loop {
+            let event: Value = serde_json::from_slice(&rx.try_recv().unwrap()).unwrap();
+            if event["event"] == "timings" {
+                break event;
+            }
+        };
+        assert_eq!(event["data"]["bootMs"], 400);
+        assert_eq!(event["data"]["buildMs"], 1200);
+        assert_eq!(event["data"]["warm"], true);
+        assert_eq!(event["data"]["bootP50Ms"], 350);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use serde_json::json;
+
+use crate::{artifacts::ARTIFACTS, inspect::inspect_wasm};
+
+fn inspection_response(wasm: &[u8]) -> HttpResponse {
+    match inspect_wasm(wasm) {
+        Ok(inspection) => HttpResponse::Ok().json(inspection),
+        Err(e) => HttpResponse::UnprocessableEntity().json(json!({ "error": e })),
+    }
+}
+
+/// Decoded contract spec, metadata and section sizes of a stored build.
+#[get("/artifacts/{sha256}/inspect")]
+pub async fn inspect_artifact(path: web::Path<String>) -> impl Responder {
+    match ARTIFACTS.find_wasm(&path.into_inner().to_ascii_lowercase()) {
+        Some(build) => inspection_response(&build.wasm),
+        None => HttpResponse::NotFound().json(json!({ "error": "Artifact not found" })),
+    }
+}
+
+/// Same as `/artifacts/{sha256}/inspect` for an uploaded WASM module.
+#[post("/inspect")]
+pub async fn inspect_upload(body: web::Bytes) -> impl Responder {
+    inspection_response(&body)
+}
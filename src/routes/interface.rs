@@ -1,45 +1,52 @@
-use actix_web::{post, web, HttpResponse, Responder};
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 use bytes::Bytes;
 use futures_util::StreamExt;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::{sync::mpsc, time};
-use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use crate::{docker::run_in_docker_no_files, models::InterfaceRequest, semaphore::SEMAPHORE};
+use crate::{
+    docker::{network_args, run_in_docker_no_files, OutputChannel},
+    jobs::JOBS,
+    limits::CommandKind,
+    models::InterfaceRequest,
+    queue::{client_key, QUEUE},
+    routes::events::{wait_for_slot, EventSink, StreamFormat},
+};
 
 #[post("/interface")]
-pub async fn interface(req: web::Json<InterfaceRequest>) -> impl Responder {
+pub async fn interface(http_req: HttpRequest, req: web::Json<InterfaceRequest>) -> impl Responder {
     println!("Requesting contract interface");
 
-    let permit = match SEMAPHORE.acquire().await {
-        Ok(p) => p,
-        Err(_) => {
-            return HttpResponse::ServiceUnavailable().body("Server shutting down");
-        }
+    let format = StreamFormat::from_request(&http_req);
+    let mut ticket = match QUEUE.enqueue(CommandKind::Interface, &client_key(&http_req)) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
     };
 
-    let (tx, rx) = mpsc::channel::<Result<Bytes, String>>(10);
-    let network = req.network.clone();
+    let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
     let contract_id = req.contract.clone();
-    let clean_network: String = network.chars().filter(|c| c.is_alphanumeric()).collect();
     let clean_contract_id: String = contract_id
         .chars()
         .filter(|c| c.is_alphanumeric())
         .collect();
-    let mut clean_network_string = clean_network.clone();
-    if clean_network_string == "public" {
-        clean_network_string = "mainnet --rpc-url https://mainnet.sorobanrpc.com --network-passphrase \"Public Global Stellar Network ; September 2015\"".to_string();
-    } else if clean_network_string == "futurenet" {
-        clean_network_string = "futurenet --rpc-url https://rpc-futurenet.stellar.org --network-passphrase \"Test SDF Future Network ; October 2022\"".to_string();
-    }
     let command = format!(
-        "stellar contract info interface --network {} --contract-id {}",
-        clean_network_string, clean_contract_id
+        "stellar contract info interface {} --contract-id {}",
+        network_args(&req.network),
+        clean_contract_id
     );
     // let command = format!("{}", contract_id);
 
+    let job = JOBS.start(CommandKind::Interface, None);
+    let job_id = job.id.clone();
+
     tokio::spawn(async move {
-        let _permit = permit;
+        let sink = EventSink::new(tx, format).recording(&job);
+        sink.job(&job.id);
+        if !wait_for_slot(&sink, &job, &mut ticket).await {
+            return;
+        }
+        let started = Instant::now();
         let mut heartbeat = time::interval(Duration::from_secs(25));
 
         let interface_fut = run_in_docker_no_files(&command);
@@ -48,16 +55,22 @@ pub async fn interface(req: web::Json<InterfaceRequest>) -> impl Responder {
         loop {
             tokio::select! {
                 _ = heartbeat.tick() => {
-                    if tx.send(Ok(Bytes::from_static(b" "))).await.is_err() { break; }
+                    if !sink.heartbeat() { break; }
+                }
+                _ = job.cancelled() => {
+                    sink.cancelled(started.elapsed());
+                    break;
                 }
                 res = &mut interface_fut => {
                     match res {
                         Ok((stdout, _tmp)) => {
-                            let _ = tx.send(Ok(Bytes::from(stdout))).await;
+                            sink.log(OutputChannel::Stdout, Bytes::from(stdout));
+                            sink.exit(Some(0), started.elapsed(), true);
                         }
                         Err(e) => {
                             let msg = format!("Interface Errors: \n{}\n", e);
-                            let _ = tx.send(Ok(Bytes::from(msg))).await;
+                            sink.log(OutputChannel::Stdout, Bytes::from(msg));
+                            sink.exit(None, started.elapsed(), false);
                         }
                     }
                     break;
@@ -66,12 +79,10 @@ pub async fn interface(req: web::Json<InterfaceRequest>) -> impl Responder {
         }
     });
 
-    let stream = ReceiverStream::new(rx).map(|chunk| match chunk {
-        Ok(bytes) => Ok::<Bytes, actix_web::Error>(bytes),
-        Err(_) => unreachable!(),
-    });
+    let stream = UnboundedReceiverStream::new(rx).map(Ok::<Bytes, actix_web::Error>);
 
     HttpResponse::Ok()
-        .content_type("text/plain; charset=utf-8")
+        .content_type(format.content_type())
+        .insert_header(("X-Soropg-Job-Id", job_id))
         .streaming(stream)
 }
@@ -0,0 +1,134 @@
+use actix_web::{
+    delete, get,
+    http::{header, StatusCode},
+    web, HttpResponse, Responder,
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::jobs::{read_log, JOBS};
+
+#[derive(Deserialize)]
+pub struct LogQuery {
+    offset: Option<u64>,
+}
+
+fn job_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": "Job not found" }))
+}
+
+/// Status, timings and artifact of a running or finished job.
+#[get("/jobs/{id}")]
+pub async fn get_job(path: web::Path<String>) -> impl Responder {
+    match JOBS.get(&path.into_inner()) {
+        Some(record) => HttpResponse::Ok().json(record),
+        None => job_not_found(),
+    }
+}
+
+/// Plain-text log of a job from `offset`. For a running job the response
+/// keeps streaming until the job ends, so a client can reattach by passing
+/// the number of bytes it already has.
+#[get("/jobs/{id}/log")]
+pub async fn job_log(path: web::Path<String>, query: web::Query<LogQuery>) -> impl Responder {
+    let Some(log) = JOBS.log(&path.into_inner()) else {
+        return job_not_found();
+    };
+    let offset = query.offset.unwrap_or(0);
+    let status = log.record.status.as_str();
+
+    let Some(mut progress) = log.progress else {
+        let content = read_log(&log.path, offset).unwrap_or_default();
+        return HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .insert_header(("X-Soropg-Job-Status", status))
+            .insert_header(("X-Soropg-Log-Offset", offset.to_string()))
+            .body(content);
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+    let path = log.path;
+    tokio::spawn(async move {
+        let mut position = offset;
+        loop {
+            let done = progress.borrow_and_update().done;
+            if let Ok(content) = read_log(&path, position) {
+                if !content.is_empty() {
+                    position += content.len() as u64;
+                    if tx.send(Bytes::from(content)).is_err() {
+                        return;
+                    }
+                }
+            }
+            if done {
+                return;
+            }
+            tokio::select! {
+                changed = progress.changed() => {
+                    if changed.is_err() {
+                        if let Ok(content) = read_log(&path, position) {
+                            let _ = tx.send(Bytes::from(content));
+                        }
+                        return;
+                    }
+                }
+                _ = tx.closed() => return,
+            }
+        }
+    });
+
+    let stream = UnboundedReceiverStream::new(rx).map(Ok::<Bytes, actix_web::Error>);
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header(("X-Soropg-Job-Status", status))
+        .insert_header(("X-Soropg-Log-Offset", offset.to_string()))
+        .streaming(stream)
+}
+
+/// Response of a finished job that returns a document, such as a SARIF
+/// export or generated bindings: the status, content type and body the
+/// submitting route would have answered with.
+#[get("/jobs/{id}/result")]
+pub async fn job_result(path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    let Some(record) = JOBS.get(&id) else {
+        return job_not_found();
+    };
+    if !record.status.is_finished() {
+        return HttpResponse::Conflict()
+            .insert_header(("X-Soropg-Job-Status", record.status.as_str()))
+            .json(json!({ "error": "Job has not finished" }));
+    }
+    let (Some(result), Some(body)) = (record.result, JOBS.read_result(&id)) else {
+        return HttpResponse::NotFound().json(json!({ "error": "Job has no result" }));
+    };
+    let status = StatusCode::from_u16(result.status).unwrap_or(StatusCode::OK);
+    let mut response = HttpResponse::build(status);
+    response
+        .content_type(result.content_type)
+        .insert_header(("X-Soropg-Job-Status", record.status.as_str()));
+    if let Some(filename) = result.filename {
+        response.insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        ));
+    }
+    response.body(body)
+}
+
+/// Cancels a queued or running sandbox job. Its container is killed, its
+/// sandbox slot is released, and its stream ends with a cancellation event.
+/// Closing the stream does not cancel a job; this is the only way.
+#[delete("/jobs/{id}")]
+pub async fn cancel_job(path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    if JOBS.cancel(&id) {
+        HttpResponse::Accepted().json(json!({ "id": id, "cancelled": true }))
+    } else {
+        HttpResponse::NotFound().json(json!({ "error": "Job is not running" }))
+    }
+}
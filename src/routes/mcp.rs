@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Component, Path},
     sync::Mutex,
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
@@ -12,9 +12,28 @@ use actix_web::{
 };
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tracing::warn;
 
 use crate::{
-    docker::run_in_docker_with_files_and_id, models::extract_main_source, semaphore::SEMAPHORE,
+    artifacts::{
+        contract_build_hash, locked_build_hash, optimized_build_hash, ArtifactMetadata, ARTIFACTS,
+    },
+    bindings::{read_bindings, BindingsLanguage},
+    diagnostics::Diagnostic,
+    docker::{
+        artifact_path, audit_command, optimized_filename, resolved_lockfile,
+        run_in_docker_with_files_and_id_stream, test_command, BuildOptions, OutputChunk,
+        BUILD_COMMAND,
+    },
+    jobs::{JobRecorder, JobStatus, JOBS},
+    limits::CommandKind,
+    models::{extract_main_source, files_hash, TestSelector},
+    queue::{QueueTicket, QUEUE},
+    routes::events::{relay_sandbox_run, EventSink, StreamFormat},
+    scout::{apply_suppressions, collect_suppressions, read_findings, render_report, AuditReport},
+    test_results::TestRecord,
+    workspace::workspace_contracts,
 };
 
 const SESSION_TTL: Duration = Duration::from_secs(90);
@@ -152,6 +171,24 @@ pub struct MoveFileRequest {
 #[derive(Deserialize)]
 pub struct RunCommandRequest {
     pub command: String,
+    #[serde(flatten)]
+    pub options: CommandOptions,
+}
+
+/// Per-run options accepted next to `command`.
+#[derive(Deserialize, Default)]
+pub struct CommandOptions {
+    #[serde(flatten)]
+    pub tests: TestSelector,
+    /// Audit only: return the full Scout log instead of the findings summary.
+    #[serde(rename = "rawLog")]
+    pub raw_log: Option<bool>,
+    /// Build only: also produce a `stellar contract optimize` build.
+    pub optimize: Option<bool>,
+    /// Bindings only: `typescript` (default), `rust`, or `python`.
+    pub language: Option<String>,
+    /// Build against the project's `Cargo.lock` with `--locked`.
+    pub locked: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -161,12 +198,33 @@ pub struct MutationResponse {
     pub seq: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Default)]
 pub struct CommandResponse {
     pub ok: bool,
     pub command: String,
     pub output: String,
     pub revision: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<TestRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub findings: Option<AuditReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<ArtifactMetadata>,
+    #[serde(rename = "optimizedArtifact", skip_serializing_if = "Option::is_none")]
+    pub optimized_artifact: Option<ArtifactMetadata>,
+    #[serde(rename = "jobId", skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Generated bindings keyed by project path, ready to merge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<BTreeMap<String, String>>,
+    /// One artifact per contract of a Cargo workspace build.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub contracts: Vec<ArtifactMetadata>,
+    /// The `Cargo.lock` cargo resolved, for the client to persist.
+    #[serde(rename = "cargoLock", skip_serializing_if = "Option::is_none")]
+    pub cargo_lock: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -187,7 +245,7 @@ fn error_response(status: actix_web::http::StatusCode, message: impl Into<String
     })
 }
 
-pub(crate) fn bearer_hash(req: &HttpRequest) -> Result<String, HttpResponse> {
+pub(crate) fn bearer_hash(req: &HttpRequest) -> Result<String, Box<HttpResponse>> {
     let header = req
         .headers()
         .get(actix_web::http::header::AUTHORIZATION)
@@ -198,7 +256,8 @@ pub(crate) fn bearer_hash(req: &HttpRequest) -> Result<String, HttpResponse> {
         return Err(error_response(
             actix_web::http::StatusCode::UNAUTHORIZED,
             "Missing bearer token",
-        ));
+        )
+        .into());
     };
 
     let token = token.trim();
@@ -206,7 +265,8 @@ pub(crate) fn bearer_hash(req: &HttpRequest) -> Result<String, HttpResponse> {
         return Err(error_response(
             actix_web::http::StatusCode::UNAUTHORIZED,
             "Bearer token is too short",
-        ));
+        )
+        .into());
     }
 
     let mut hash = Sha256::new();
@@ -249,7 +309,7 @@ fn is_safe_relative_path(path: &str) -> bool {
     saw_segment
 }
 
-fn validate_files(files: &HashMap<String, String>) -> Result<(), String> {
+pub(crate) fn validate_files(files: &HashMap<String, String>) -> Result<(), String> {
     if files.len() > MAX_FILES {
         return Err(format!("Workspace exceeds {} files", MAX_FILES));
     }
@@ -316,9 +376,9 @@ fn mutate_project<F>(
     user_key: &str,
     project_id: &str,
     mut change: F,
-) -> Result<MutationResponse, HttpResponse>
+) -> Result<MutationResponse, Box<HttpResponse>>
 where
-    F: FnMut(&mut ProjectRecord) -> Result<(), HttpResponse>,
+    F: FnMut(&mut ProjectRecord) -> Result<(), Box<HttpResponse>>,
 {
     let mut store = state.store.lock().map_err(|_| {
         error_response(
@@ -590,16 +650,26 @@ pub(crate) async fn run_project_command_for_user(
     user_key: &str,
     project_id: &str,
     command: &str,
+    options: &CommandOptions,
 ) -> Result<CommandResponse, String> {
     let command_name = command.trim().to_ascii_lowercase();
     if command_name == "deploy" {
         return Err("Deploy is not supported by the MCP API in v1".to_string());
     }
     let docker_command = match command_name.as_str() {
-        "build" => "stellar contract build",
-        "test" => "cargo test",
-        "audit" => "CARGO_BUILD_JOBS=1 cargo scout-audit --debug -v --scout-source /scout-audit --local-detectors /scout-audit/nightly",
-        _ => return Err("Unsupported command. Allowed commands: build, test, audit, deploy".to_string()),
+        "build" => BUILD_COMMAND.to_string(),
+        "bindings" => {
+            BindingsLanguage::parse(options.language.as_deref())?;
+            BUILD_COMMAND.to_string()
+        }
+        "test" => test_command(&options.tests)?,
+        "audit" => audit_command(options.raw_log.unwrap_or(false)),
+        _ => {
+            return Err(
+                "Unsupported command. Allowed commands: build, test, audit, bindings, deploy"
+                    .to_string(),
+            )
+        }
     };
 
     let (files, revision) = {
@@ -618,48 +688,340 @@ pub(crate) async fn run_project_command_for_user(
         (project.files.clone(), project.revision)
     };
 
-    let Some(code) = extract_main_source(&files) else {
+    if extract_main_source(&files).is_none() {
         return Err("No src/lib.rs or lib.rs file found".to_string());
-    };
+    }
 
-    let permit = SEMAPHORE
-        .acquire()
-        .await
-        .map_err(|_| "Server shutting down".to_string())?;
-    let _permit = permit;
-
-    let build_id = if command_name == "build" || command_name == "audit" {
-        let mut hash = Sha256::new();
-        let mut entries: Vec<_> = files.iter().collect();
-        entries.sort_by(|a, b| a.0.cmp(b.0));
-        for (path, content) in entries {
-            hash.update(path.as_bytes());
-            hash.update(content.as_bytes());
-        }
-        Some(hex::encode(hash.finalize()))
+    let ticket = QUEUE
+        .enqueue(command_kind(&command_name), user_key)
+        .map_err(|e| e.to_string())?;
+
+    Ok(run_command_in_sandbox(
+        ticket,
+        user_key,
+        command_name,
+        &docker_command,
+        files,
+        revision,
+        options,
+    )
+    .await)
+}
+
+fn command_kind(command_name: &str) -> CommandKind {
+    match command_name {
+        "build" | "bindings" => CommandKind::Build,
+        "audit" => CommandKind::Audit,
+        _ => CommandKind::Test,
+    }
+}
+
+/// Runs a queued MCP command once `ticket` is admitted. The job is registered
+/// first, so it shows as queued in the job registry and can be cancelled
+/// while it waits.
+async fn run_command_in_sandbox(
+    mut ticket: QueueTicket,
+    user_key: &str,
+    command_name: String,
+    docker_command: &str,
+    files: HashMap<String, String>,
+    revision: u64,
+    options: &CommandOptions,
+) -> CommandResponse {
+    let locked = options.locked.unwrap_or(false);
+    let input_hash = if locked {
+        locked_build_hash(&files_hash(&files))
     } else {
-        None
+        files_hash(&files)
+    };
+    let build_id = match command_name.as_str() {
+        "build" | "bindings" | "audit" => Some(input_hash.clone()),
+        _ => None,
     };
 
-    match run_in_docker_with_files_and_id(code, Some(files), docker_command, build_id).await {
-        Ok((output, _tmp, output_filename)) => {
-            let mut output = String::from_utf8_lossy(&output).to_string();
-            if command_name == "build" {
-                output.push_str(&format!("\nBuild artifact: {}\n", output_filename));
+    let suppressions = match command_name.as_str() {
+        "audit" => match collect_suppressions(&files) {
+            Ok(suppressions) => suppressions,
+            Err(error) => {
+                return CommandResponse {
+                    ok: false,
+                    command: command_name,
+                    output: error,
+                    revision,
+                    ..CommandResponse::default()
+                }
             }
-            Ok(CommandResponse {
-                ok: true,
-                command: command_name,
-                output,
-                revision,
-            })
-        }
-        Err(error) => Ok(CommandResponse {
+        },
+        _ => Vec::new(),
+    };
+
+    // Validated by the callers before the job was queued.
+    let code = extract_main_source(&files).unwrap_or_default();
+    let bindings = match command_name.as_str() {
+        "bindings" => BindingsLanguage::parse(options.language.as_deref()).ok(),
+        _ => None,
+    };
+
+    let contracts = workspace_contracts(&files).ok().flatten();
+
+    let kind = command_kind(&command_name);
+    let job = JOBS.start(kind, Some(input_hash.clone()));
+    let queued = Instant::now();
+    let admitted = tokio::select! {
+        _ = ticket.admitted() => true,
+        _ = job.cancelled() => false,
+    };
+    if !admitted {
+        let output = "Cancelled while queued".to_string();
+        let recorder = job.recorder();
+        recorder.append(output.as_bytes());
+        recorder.finish(
+            JobStatus::Cancelled,
+            None,
+            Some("cancelled"),
+            queued.elapsed(),
+        );
+        return CommandResponse {
             ok: false,
             command: command_name,
-            output: error,
+            output,
             revision,
-        }),
+            job_id: Some(job.id.clone()),
+            ..CommandResponse::default()
+        };
+    }
+    job.admitted();
+    let started = Instant::now();
+    // Nobody reads the stream; the sink records the log as the run writes it,
+    // so `GET /jobs/{id}/log` can follow the command.
+    let (tx, _) = mpsc::unbounded_channel();
+    let sink = EventSink::new(tx, StreamFormat::Text).recording(&job);
+    let (output_tx, output_rx) = mpsc::unbounded_channel::<OutputChunk>();
+    let run = run_in_docker_with_files_and_id_stream(
+        code,
+        Some(files),
+        docker_command,
+        build_id,
+        BuildOptions {
+            optimize: options.optimize.unwrap_or(false),
+            bindings,
+            locked,
+            tenant: Some(user_key.to_string()),
+            ..BuildOptions::default()
+        },
+        kind,
+        output_tx,
+    );
+    let Some((res, relayed)) = relay_sandbox_run(&sink, &job, run, output_rx).await else {
+        return CommandResponse {
+            ok: false,
+            command: command_name,
+            output: "Cancelled".to_string(),
+            revision,
+            job_id: Some(job.id.clone()),
+            ..CommandResponse::default()
+        };
+    };
+    let (ok, output_filename, workspace, killed, failure) = match res {
+        Ok((tmp, output_filename)) => (true, Some(output_filename), Some(tmp), None, None),
+        Err(mut error) => {
+            let workspace = error.workspace.take();
+            (false, None, workspace, error.killed, Some(error.message))
+        }
+    };
+
+    let streamed = String::from_utf8_lossy(&relayed.log).to_string();
+    let mut output = streamed.clone();
+    if let Some(message) = failure {
+        output.push_str(&format!("\n{}\n", message));
+    }
+    let (diagnostics, tests) = (relayed.diagnostics, relayed.tests);
+    let recorder = job.recorder();
+    let cargo_lock = workspace
+        .as_ref()
+        .and_then(|tmp| resolved_lockfile(tmp.path()));
+    let mut artifact = None;
+    let mut optimized_artifact = None;
+    let mut contract_artifacts = Vec::new();
+    if let (Some(output_filename), Some(tmp), "build") =
+        (output_filename, &workspace, command_name.as_str())
+    {
+        output.push_str(&format!("\nBuild artifact: {}\n", output_filename));
+        artifact = store_artifact(
+            &recorder,
+            tmp.path(),
+            &input_hash,
+            &output_filename,
+            &relayed.log,
+            started.elapsed(),
+            match contracts {
+                Some(_) => ArtifactRole::Contract { primary: true },
+                None => ArtifactRole::Build,
+            },
+        );
+        if let Some(artifact) = &artifact {
+            output.push_str(&format!("Download: {}\n", artifact.url));
+            if let Some(lockfile) = &cargo_lock {
+                if let Err(e) = ARTIFACTS.put_lockfile(&input_hash, lockfile) {
+                    warn!(hash=%input_hash, error=%e, "failed to cache resolved lockfile");
+                }
+            }
+        }
+        if options.optimize.unwrap_or(false) {
+            optimized_artifact = store_artifact(
+                &recorder,
+                tmp.path(),
+                &optimized_build_hash(&input_hash),
+                &optimized_filename(&output_filename),
+                b"",
+                started.elapsed(),
+                ArtifactRole::Optimized,
+            );
+        }
+        if let Some(contracts) = &contracts {
+            contract_artifacts.extend(artifact.clone());
+            for contract in contracts.iter().skip(1) {
+                let stored = store_artifact(
+                    &recorder,
+                    tmp.path(),
+                    &contract_build_hash(&input_hash, &contract.package),
+                    &contract.output_filename(Some(&input_hash)),
+                    b"",
+                    started.elapsed(),
+                    ArtifactRole::Contract { primary: false },
+                );
+                if let Some(stored) = stored {
+                    output.push_str(&format!(
+                        "Contract {}: {}\nDownload: {}\n",
+                        contract.package, stored.filename, stored.url
+                    ));
+                    contract_artifacts.push(stored);
+                }
+            }
+        }
+        if let (Some(artifact), Some(optimized)) = (&artifact, &optimized_artifact) {
+            output.push_str(&format!(
+                "Optimized artifact: {} ({} bytes, {} bytes smaller)\nDownload: {}\n",
+                optimized.filename,
+                optimized.size,
+                artifact.size.saturating_sub(optimized.size),
+                optimized.url
+            ));
+        }
+    }
+
+    let mut ok = ok;
+    let mut files = None;
+    if let (true, Some(language), Some(tmp)) = (ok, bindings, &workspace) {
+        match read_bindings(tmp.path(), language) {
+            Ok(generated) => {
+                output.push_str(&format!(
+                    "\nGenerated {} {} binding files under bindings/{}/\n",
+                    generated.len(),
+                    language.as_str(),
+                    language.as_str()
+                ));
+                files = Some(generated);
+            }
+            Err(error) => {
+                ok = false;
+                output.push_str(&format!("\n{}\n", error));
+            }
+        }
+    }
+
+    // Scout exits non-zero when it reports issues; a readable report means
+    // the audit itself ran.
+    let findings = match (command_name.as_str(), &workspace) {
+        ("audit", Some(tmp)) => read_findings(tmp.path())
+            .ok()
+            .map(|findings| apply_suppressions(findings, &suppressions)),
+        _ => None,
+    };
+    if let Some(findings) = &findings {
+        ok = true;
+        if !options.raw_log.unwrap_or(false) {
+            output = render_report(findings);
+        }
+    }
+
+    // The log already holds what the run wrote.
+    let summary = output.strip_prefix(streamed.as_str()).unwrap_or(&output);
+    recorder.append(summary.as_bytes());
+    let status = if ok {
+        JobStatus::Succeeded
+    } else {
+        JobStatus::Failed
+    };
+    recorder.finish(
+        status,
+        None,
+        killed.map(|reason| reason.as_str()),
+        started.elapsed(),
+    );
+
+    CommandResponse {
+        ok,
+        command: command_name,
+        output,
+        revision,
+        diagnostics,
+        tests,
+        findings,
+        artifact,
+        optimized_artifact,
+        job_id: Some(job.id.clone()),
+        files,
+        contracts: contract_artifacts,
+        cargo_lock,
+    }
+}
+
+/// How a stored WASM shows up in the job record.
+#[derive(Clone, Copy)]
+enum ArtifactRole {
+    Build,
+    Optimized,
+    /// A Cargo workspace member; the primary one is also the job's artifact.
+    Contract {
+        primary: bool,
+    },
+}
+
+/// Reads a WASM the sandbox copied to the host and stores it in the artifact
+/// cache, returning its download metadata.
+fn store_artifact(
+    recorder: &JobRecorder,
+    workspace: &Path,
+    cache_key: &str,
+    filename: &str,
+    log: &[u8],
+    build_duration: Duration,
+    role: ArtifactRole,
+) -> Option<ArtifactMetadata> {
+    let wasm = match std::fs::read(artifact_path(workspace, filename)) {
+        Ok(wasm) => wasm,
+        Err(e) => {
+            warn!(file=%filename, error=%e, "failed to read build artifact");
+            return None;
+        }
+    };
+    match role {
+        ArtifactRole::Build => recorder.artifact(filename, &wasm, false),
+        ArtifactRole::Optimized => recorder.artifact(filename, &wasm, true),
+        ArtifactRole::Contract { primary } => {
+            if primary {
+                recorder.artifact(filename, &wasm, false);
+            }
+            recorder.contract(filename, &wasm);
+        }
+    }
+    match ARTIFACTS.put(cache_key, filename, &wasm, log, build_duration) {
+        Ok(record) => Some(ArtifactMetadata::from(&record)),
+        Err(e) => {
+            warn!(hash=%cache_key, error=%e, "failed to cache build artifact");
+            None
+        }
     }
 }
 
@@ -671,7 +1033,7 @@ pub async fn browser_heartbeat(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
 
     if body.session_id.trim().len() < 8 {
@@ -777,7 +1139,7 @@ pub async fn browser_changes(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let since = query.since.unwrap_or(0);
 
@@ -811,7 +1173,7 @@ pub async fn browser_changes(
 pub async fn list_projects(req: HttpRequest, state: Data<McpState>) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
 
     let mut store = match state.store.lock() {
@@ -846,7 +1208,7 @@ pub async fn get_project(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
 
@@ -881,7 +1243,7 @@ pub async fn list_files(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
 
@@ -928,7 +1290,7 @@ pub async fn read_file(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
     let file_path = match normalize_mcp_path(&query.path) {
@@ -976,7 +1338,7 @@ pub async fn upsert_file(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
     let file_path = match normalize_mcp_path(&body.path) {
@@ -1002,13 +1364,13 @@ pub async fn upsert_file(
             return Err(error_response(
                 actix_web::http::StatusCode::CONFLICT,
                 "File already exists",
-            ));
+            )
+            .into());
         }
         if mode == "replace" && !project.files.contains_key(&file_path) {
-            return Err(error_response(
-                actix_web::http::StatusCode::NOT_FOUND,
-                "File not found",
-            ));
+            return Err(
+                error_response(actix_web::http::StatusCode::NOT_FOUND, "File not found").into(),
+            );
         }
         let mut next_files = project.files.clone();
         next_files.insert(file_path.clone(), body.content.clone());
@@ -1019,7 +1381,7 @@ pub async fn upsert_file(
         Ok(())
     }) {
         Ok(response) => HttpResponse::Ok().json(response),
-        Err(response) => response,
+        Err(response) => *response,
     }
 }
 
@@ -1032,7 +1394,7 @@ pub async fn delete_file(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
     let file_path = match normalize_mcp_path(&query.path) {
@@ -1045,19 +1407,20 @@ pub async fn delete_file(
             return Err(error_response(
                 actix_web::http::StatusCode::BAD_REQUEST,
                 "Cargo.toml cannot be deleted",
-            ));
+            )
+            .into());
         }
         if project.files.len() <= 1 {
             return Err(error_response(
                 actix_web::http::StatusCode::BAD_REQUEST,
                 "Cannot delete the last file",
-            ));
+            )
+            .into());
         }
         if project.files.remove(&file_path).is_none() {
-            return Err(error_response(
-                actix_web::http::StatusCode::NOT_FOUND,
-                "File not found",
-            ));
+            return Err(
+                error_response(actix_web::http::StatusCode::NOT_FOUND, "File not found").into(),
+            );
         }
         if project.last_open_file.as_deref() == Some(&file_path) {
             project.last_open_file = project.files.keys().min().cloned();
@@ -1065,7 +1428,7 @@ pub async fn delete_file(
         Ok(())
     }) {
         Ok(response) => HttpResponse::Ok().json(response),
-        Err(response) => response,
+        Err(response) => *response,
     }
 }
 
@@ -1078,7 +1441,7 @@ pub async fn move_file(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
     let from_path = match normalize_mcp_path(&body.from_path) {
@@ -1095,19 +1458,22 @@ pub async fn move_file(
             return Err(error_response(
                 actix_web::http::StatusCode::BAD_REQUEST,
                 "Cargo.toml cannot be moved",
-            ));
+            )
+            .into());
         }
         if project.files.contains_key(&to_path) {
             return Err(error_response(
                 actix_web::http::StatusCode::CONFLICT,
                 "Destination file already exists",
-            ));
+            )
+            .into());
         }
         let Some(content) = project.files.remove(&from_path) else {
             return Err(error_response(
                 actix_web::http::StatusCode::NOT_FOUND,
                 "Source file not found",
-            ));
+            )
+            .into());
         };
         project.files.insert(to_path.clone(), content);
         if project.last_open_file.as_deref() == Some(&from_path) {
@@ -1116,7 +1482,7 @@ pub async fn move_file(
         Ok(())
     }) {
         Ok(response) => HttpResponse::Ok().json(response),
-        Err(response) => response,
+        Err(response) => *response,
     }
 }
 
@@ -1129,7 +1495,7 @@ pub async fn run_command(
 ) -> impl Responder {
     let user_key = match bearer_hash(&req) {
         Ok(key) => key,
-        Err(response) => return response,
+        Err(response) => return *response,
     };
     let project_id = path.into_inner();
     let command_name = body.command.trim().to_ascii_lowercase();
@@ -1142,13 +1508,20 @@ pub async fn run_command(
     }
 
     let docker_command = match command_name.as_str() {
-        "build" => "stellar contract build",
-        "test" => "cargo test",
-        "audit" => "CARGO_BUILD_JOBS=1 cargo scout-audit --debug -v --scout-source /scout-audit --local-detectors /scout-audit/nightly",
+        "build" => BUILD_COMMAND.to_string(),
+        "bindings" => match BindingsLanguage::parse(body.options.language.as_deref()) {
+            Ok(_) => BUILD_COMMAND.to_string(),
+            Err(error) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, error),
+        },
+        "test" => match test_command(&body.options.tests) {
+            Ok(command) => command,
+            Err(error) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, error),
+        },
+        "audit" => audit_command(body.options.raw_log.unwrap_or(false)),
         _ => {
             return error_response(
                 actix_web::http::StatusCode::BAD_REQUEST,
-                "Unsupported command. Allowed commands: build, test, audit, deploy",
+                "Unsupported command. Allowed commands: build, test, audit, bindings, deploy",
             )
         }
     };
@@ -1177,57 +1550,30 @@ pub async fn run_command(
         (project.files.clone(), project.revision)
     };
 
-    let Some(code) = extract_main_source(&files) else {
+    if extract_main_source(&files).is_none() {
         return error_response(
             actix_web::http::StatusCode::BAD_REQUEST,
             "No src/lib.rs or lib.rs file found",
         );
-    };
+    }
 
-    let permit = match SEMAPHORE.acquire().await {
-        Ok(permit) => permit,
-        Err(_) => {
-            return error_response(
-                actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
-                "Server shutting down",
-            )
-        }
-    };
-    let _permit = permit;
-
-    let build_id = if command_name == "build" || command_name == "audit" {
-        let mut hash = Sha256::new();
-        let mut entries: Vec<_> = files.iter().collect();
-        entries.sort_by(|a, b| a.0.cmp(b.0));
-        for (path, content) in entries {
-            hash.update(path.as_bytes());
-            hash.update(content.as_bytes());
-        }
-        Some(hex::encode(hash.finalize()))
-    } else {
-        None
+    let ticket = match QUEUE.enqueue(command_kind(&command_name), &user_key) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
     };
 
-    match run_in_docker_with_files_and_id(code, Some(files), docker_command, build_id).await {
-        Ok((output, _tmp, output_filename)) => {
-            let mut output = String::from_utf8_lossy(&output).to_string();
-            if command_name == "build" {
-                output.push_str(&format!("\nBuild artifact: {}\n", output_filename));
-            }
-            HttpResponse::Ok().json(CommandResponse {
-                ok: true,
-                command: command_name,
-                output,
-                revision,
-            })
-        }
-        Err(error) => HttpResponse::Ok().json(CommandResponse {
-            ok: false,
-            command: command_name,
-            output: error,
+    HttpResponse::Ok().json(
+        run_command_in_sandbox(
+            ticket,
+            &user_key,
+            command_name,
+            &docker_command,
+            files,
             revision,
-        }),
-    }
+            &body.options,
+        )
+        .await,
+    )
 }
 
 fn normalize_mcp_path(path: &str) -> Result<String, String> {
@@ -1,7 +1,12 @@
 pub mod ai;
+pub mod artifacts;
+pub mod bindings;
 pub mod compile;
+pub mod events;
 pub mod friendbot;
+pub mod inspect;
 pub mod interface;
+pub mod jobs;
 pub mod mcp;
 pub mod scout_audit;
 pub mod test;
@@ -1,88 +1,163 @@
-use actix_web::{post, web, HttpResponse, Responder};
+use actix_web::{http::header, post, web, HttpRequest, HttpResponse, Responder};
 use bytes::Bytes;
 use futures_util::StreamExt;
-use std::time::Duration;
-use tokio::{sync::mpsc, time};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Instant;
+use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use crate::{
-    docker::run_in_docker_with_files_and_id_stream,
+    docker::{audit_command, run_in_docker_with_files_and_id_stream, BuildOptions, OutputChunk},
+    jobs::JOBS,
+    limits::CommandKind,
     models::{extract_main_source, CompileRequest},
-    semaphore::SEMAPHORE,
+    queue::{client_key, QUEUE},
+    routes::{
+        events::{relay_sandbox_run, wait_for_slot, EventSink, StreamFormat},
+        mcp::validate_files,
+    },
+    scout::{
+        apply_suppressions, collect_suppressions, export_sarif, read_findings, read_report,
+        Suppression,
+    },
 };
 
-#[post("/scout-audit")]
-pub async fn scout_audit(req: web::Json<CompileRequest>) -> impl Responder {
-    println!("Running scout audit");
+/// Log lines included with a failed audit when the full log was not streamed.
+const FAILURE_LOG_TAIL_LINES: usize = 40;
 
-    let permit = match SEMAPHORE.acquire().await {
-        Ok(p) => p,
-        Err(_) => {
-            return HttpResponse::ServiceUnavailable().body("Server shutting down");
-        }
-    };
-
-    let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+/// Main source, cache key and suppressions of an audit request.
+struct AuditInput {
+    code: String,
+    build_hash: String,
+    suppressions: Vec<Suppression>,
+}
 
+fn audit_input(req: &CompileRequest) -> Result<AuditInput, Box<HttpResponse>> {
     let code = match &req.code {
         Some(c) => c.clone(),
         None => match &req.files {
             Some(files_map) => match extract_main_source(files_map) {
                 Some(lib_rs_code) => lib_rs_code,
                 None => {
-                    return HttpResponse::BadRequest()
+                    return Err(HttpResponse::BadRequest()
                         .body("No code provided and no src/lib.rs or lib.rs file found")
+                        .into())
                 }
             },
-            None => return HttpResponse::BadRequest().body("No code or files provided"),
+            None => {
+                return Err(HttpResponse::BadRequest()
+                    .body("No code or files provided")
+                    .into())
+            }
         },
     };
 
-    let files = req.files.clone();
-    let build_hash = {
-        use sha2::{Digest, Sha256};
-        let mut h = Sha256::new();
-        if let Some(ref files_map) = req.files {
-            let mut entries: Vec<_> = files_map.iter().collect();
-            entries.sort_by(|a, b| a.0.cmp(b.0));
-            for (filename, content) in entries {
-                h.update(filename.as_bytes());
-                h.update(content.as_bytes());
-            }
-        } else {
-            h.update(code.as_bytes());
+    let build_hash = req.input_hash();
+
+    let sources = match &req.files {
+        Some(files_map) => {
+            validate_files(files_map).map_err(|e| Box::new(HttpResponse::BadRequest().body(e)))?;
+            files_map.clone()
         }
-        hex::encode(h.finalize())
+        None => HashMap::from([("src/lib.rs".to_string(), code.clone())]),
     };
+    let suppressions =
+        collect_suppressions(&sources).map_err(|e| Box::new(HttpResponse::BadRequest().body(e)))?;
+
+    Ok(AuditInput {
+        code,
+        build_hash,
+        suppressions,
+    })
+}
+
+#[post("/scout-audit")]
+pub async fn scout_audit(http_req: HttpRequest, req: web::Json<CompileRequest>) -> impl Responder {
+    let format = StreamFormat::from_request(&http_req);
+    println!("Running scout audit");
+
+    let AuditInput {
+        code,
+        build_hash,
+        suppressions,
+    } = match audit_input(&req) {
+        Ok(input) => input,
+        Err(response) => return *response,
+    };
+
+    let mut ticket = match QUEUE.enqueue(CommandKind::Audit, &client_key(&http_req)) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+    let files = req.files.clone();
+    let options = BuildOptions {
+        locked: req.locked.unwrap_or(false),
+        tenant: Some(client_key(&http_req)),
+        ..BuildOptions::default()
+    };
+    let raw_log = req.raw_log.unwrap_or(false);
+
+    let job = JOBS.start(CommandKind::Audit, Some(build_hash.clone()));
+    let job_id = job.id.clone();
 
     tokio::spawn(async move {
-        let _permit = permit;
-        let mut heartbeat = time::interval(Duration::from_secs(25));
+        let sink = EventSink::new(tx, format).recording(&job);
+        let sink = if raw_log { sink } else { sink.without_logs() };
+        sink.job(&job.id);
+        if !wait_for_slot(&sink, &job, &mut ticket).await {
+            return;
+        }
+        let started = Instant::now();
+        let (output_tx, output_rx) = mpsc::unbounded_channel::<OutputChunk>();
 
+        let command = audit_command(raw_log);
         let scout_fut = run_in_docker_with_files_and_id_stream(
             code,
             files,
-            "CARGO_BUILD_JOBS=1 cargo scout-audit --debug -v --scout-source /scout-audit --local-detectors /scout-audit/nightly",
+            &command,
             Some(build_hash),
-            tx.clone(),
+            options,
+            CommandKind::Audit,
+            output_tx,
         );
-        tokio::pin!(scout_fut);
+        let Some((res, output)) = relay_sandbox_run(&sink, &job, scout_fut, output_rx).await else {
+            return;
+        };
+
+        // Scout exits non-zero when it finds issues, so the report is read
+        // from the workspace either way.
+        let (workspace, failure) = match res {
+            Ok((tmp, _output_filename)) => (Some(tmp), None),
+            Err(mut e) => (e.workspace.take(), Some(e)),
+        };
+        let findings = workspace.as_ref().map(|tmp| read_findings(tmp.path()));
 
-        loop {
-            tokio::select! {
-                _ = heartbeat.tick() => {
-                    if tx.send(Bytes::from_static(b" ")).is_err() { break; }
+        match (findings, failure) {
+            (Some(Ok(findings)), failure) => {
+                sink.findings(&apply_suppressions(findings, &suppressions));
+                let code = failure.as_ref().and_then(|e| e.exit_code).unwrap_or(0);
+                sink.exit(Some(code), started.elapsed(), true);
+            }
+            (findings, Some(e)) => {
+                if !raw_log {
+                    sink.diagnostic("error", &output.tail(FAILURE_LOG_TAIL_LINES));
                 }
-                res = &mut scout_fut => {
-                    match res {
-                        Ok((_tmp, _output_filename)) => {}
-                        Err(e) => {
-                            let msg = format!("Scout Audit Errors: \n{}\n", e);
-                            let _ = tx.send(Bytes::from(msg));
-                        }
-                    }
-                    break;
+                sink.diagnostic("error", &format!("Scout Audit Errors: \n{}\n", e));
+                if let Some(Err(report_error)) = findings {
+                    sink.diagnostic("warning", &format!("{}\n", report_error));
                 }
+                sink.failed(&e, started.elapsed());
+            }
+            (findings, None) => {
+                let message = match findings {
+                    Some(Err(report_error)) => report_error,
+                    _ => "Scout did not produce a report".to_string(),
+                };
+                sink.diagnostic("error", &format!("Scout Audit Errors: \n{}\n", message));
+                sink.exit(Some(0), started.elapsed(), false);
             }
         }
     });
@@ -90,6 +165,108 @@ pub async fn scout_audit(req: web::Json<CompileRequest>) -> impl Responder {
     let stream = UnboundedReceiverStream::new(rx).map(Ok::<Bytes, actix_web::Error>);
 
     HttpResponse::Ok()
-        .content_type("text/plain; charset=utf-8")
+        .content_type(format.content_type())
+        .insert_header(("X-Soropg-Job-Id", job_id))
         .streaming(stream)
 }
+
+/// Runs the same audit as `/scout-audit` in the background and answers
+/// `202` with the job id. `GET /jobs/{id}/result` then serves Scout's report
+/// as a SARIF 2.1.0 document with workspace-relative locations.
+#[post("/scout-audit/sarif")]
+pub async fn scout_audit_sarif(
+    http_req: HttpRequest,
+    req: web::Json<CompileRequest>,
+) -> impl Responder {
+    let AuditInput {
+        code,
+        build_hash,
+        suppressions,
+    } = match audit_input(&req) {
+        Ok(input) => input,
+        Err(response) => return *response,
+    };
+
+    let mut ticket = match QUEUE.enqueue(CommandKind::Audit, &client_key(&http_req)) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
+    };
+
+    let files = req.files.clone();
+    let options = BuildOptions {
+        locked: req.locked.unwrap_or(false),
+        tenant: Some(client_key(&http_req)),
+        ..BuildOptions::default()
+    };
+    let job = JOBS.start(CommandKind::Audit, Some(build_hash.clone()));
+    let job_id = job.id.clone();
+
+    tokio::spawn(async move {
+        // Nobody reads the stream; the sink only records the job.
+        let (tx, _) = mpsc::unbounded_channel();
+        let sink = EventSink::new(tx, StreamFormat::Text).recording(&job);
+        if !wait_for_slot(&sink, &job, &mut ticket).await {
+            return;
+        }
+        let started = Instant::now();
+        let (output_tx, output_rx) = mpsc::unbounded_channel::<OutputChunk>();
+
+        let command = audit_command(false);
+        let scout_fut = run_in_docker_with_files_and_id_stream(
+            code,
+            files,
+            &command,
+            Some(build_hash),
+            options,
+            CommandKind::Audit,
+            output_tx,
+        );
+        let Some((res, output)) = relay_sandbox_run(&sink, &job, scout_fut, output_rx).await else {
+            return;
+        };
+        let (workspace, failure) = match res {
+            Ok((tmp, _output_filename)) => (Some(tmp), None),
+            Err(mut e) => (e.workspace.take(), Some(e)),
+        };
+
+        let report = workspace
+            .as_ref()
+            .ok_or_else(|| "Scout did not produce a report".to_string())
+            .and_then(|tmp| read_report(tmp.path()))
+            .and_then(|raw| export_sarif(&raw, &suppressions));
+        let recorder = job.recorder();
+        match (report, failure) {
+            (Ok(report), failure) => {
+                recorder.result(
+                    200,
+                    "application/sarif+json",
+                    None,
+                    report.to_string().as_bytes(),
+                );
+                let code = failure.as_ref().and_then(|e| e.exit_code).unwrap_or(0);
+                sink.exit(Some(code), started.elapsed(), true);
+            }
+            (Err(_), Some(e)) => {
+                let message = format!(
+                    "Scout Audit Errors: \n{}\n{}",
+                    e,
+                    String::from_utf8_lossy(&output.log)
+                );
+                recorder.result(422, "text/plain; charset=utf-8", None, message.as_bytes());
+                sink.diagnostic("error", &message);
+                sink.failed(&e, started.elapsed());
+            }
+            (Err(e), None) => {
+                let message = format!("Scout Audit Errors: \n{}\n", e);
+                recorder.result(500, "text/plain; charset=utf-8", None, message.as_bytes());
+                sink.diagnostic("error", &message);
+                sink.exit(Some(0), started.elapsed(), false);
+            }
+        }
+    });
+
+    HttpResponse::Accepted()
+        .insert_header(("X-Soropg-Job-Id", job_id.clone()))
+        .insert_header((header::LOCATION, format!("/jobs/{}/result", job_id)))
+        .json(json!({ "id": job_id }))
+}
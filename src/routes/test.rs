@@ -1,25 +1,32 @@
-use actix_web::{post, web, HttpResponse, Responder};
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 use bytes::Bytes;
 use futures_util::StreamExt;
-use std::time::Duration;
-use tokio::{sync::mpsc, time};
+use std::time::Instant;
+use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use crate::{
-    docker::run_in_docker_with_files_and_id_stream,
+    docker::{run_in_docker_with_files_and_id_stream, test_command, BuildOptions, OutputChunk},
+    jobs::JOBS,
+    limits::CommandKind,
     models::{extract_main_source, CompileRequest},
-    semaphore::SEMAPHORE,
+    queue::{client_key, QUEUE},
+    routes::events::{relay_sandbox_run, wait_for_slot, EventSink, StreamFormat},
 };
 
 #[post("/test")]
-pub async fn test(req: web::Json<CompileRequest>) -> impl Responder {
+pub async fn test(http_req: HttpRequest, req: web::Json<CompileRequest>) -> impl Responder {
+    let format = StreamFormat::from_request(&http_req);
     println!("Running unit tests");
 
-    let permit = match SEMAPHORE.acquire().await {
-        Ok(p) => p,
-        Err(_) => {
-            return HttpResponse::ServiceUnavailable().body("Server shutting down");
-        }
+    let command = match test_command(&req.tests) {
+        Ok(command) => command,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+
+    let mut ticket = match QUEUE.enqueue(CommandKind::Test, &client_key(&http_req)) {
+        Ok(ticket) => ticket,
+        Err(e) => return e.into_response(),
     };
 
     let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
@@ -43,30 +50,47 @@ pub async fn test(req: web::Json<CompileRequest>) -> impl Responder {
     };
 
     let files = req.files.clone();
+    let options = BuildOptions {
+        locked: req.locked.unwrap_or(false),
+        tenant: Some(client_key(&http_req)),
+        ..BuildOptions::default()
+    };
+
+    let job = JOBS.start(CommandKind::Test, Some(req.input_hash()));
+    let job_id = job.id.clone();
 
     tokio::spawn(async move {
-        let _permit = permit;
-        let mut heartbeat = time::interval(Duration::from_secs(25));
+        let sink = EventSink::new(tx, format).recording(&job);
+        sink.job(&job.id);
+        if !wait_for_slot(&sink, &job, &mut ticket).await {
+            return;
+        }
+        let started = Instant::now();
+        let (output_tx, output_rx) = mpsc::unbounded_channel::<OutputChunk>();
 
-        let test_fut =
-            run_in_docker_with_files_and_id_stream(code, files, "cargo test", None, tx.clone());
-        tokio::pin!(test_fut);
+        let test_fut = run_in_docker_with_files_and_id_stream(
+            code,
+            files,
+            &command,
+            None,
+            options,
+            CommandKind::Test,
+            output_tx,
+        );
+        let Some((res, output)) = relay_sandbox_run(&sink, &job, test_fut, output_rx).await else {
+            return;
+        };
+        for record in &output.tests {
+            sink.test_result(record);
+        }
 
-        loop {
-            tokio::select! {
-                _ = heartbeat.tick() => {
-                    if tx.send(Bytes::from_static(b" ")).is_err() { break; }
-                }
-                res = &mut test_fut => {
-                    match res {
-                        Ok((_tmp, _output_filename)) => {}
-                        Err(e) => {
-                            let msg = format!("Test Errors: \n{}\n", e);
-                            let _ = tx.send(Bytes::from(msg));
-                        }
-                    }
-                    break;
-                }
+        match res {
+            Ok((_tmp, _output_filename)) => {
+                sink.exit(Some(0), started.elapsed(), true);
+            }
+            Err(e) => {
+                sink.diagnostic("error", &format!("Test Errors: \n{}\n", e));
+                sink.failed(&e, started.elapsed());
             }
         }
     });
@@ -74,6 +98,7 @@ pub async fn test(req: web::Json<CompileRequest>) -> impl Responder {
     let stream = UnboundedReceiverStream::new(rx).map(Ok::<Bytes, actix_web::Error>);
 
     HttpResponse::Ok()
-        .content_type("text/plain; charset=utf-8")
+        .content_type(format.content_type())
+        .insert_header(("X-Soropg-Job-Id", job_id))
         .streaming(stream)
 }
@@ -0,0 +1,543 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::docker::{legacy_workspace_path, AUDIT_REPORT_FILE};
+
+/// Workspace file listing acknowledged findings.
+pub const SUPPRESSIONS_FILE: &str = "scout.toml";
+const INLINE_MARKER: &str = "// scout-ignore:";
+
+const SANDBOX_PROJECT_DIR: &str = "/workspace/project/";
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// One Scout detector hit, with its location mapped back to the submitted
+/// workspace path (e.g. `src/lib.rs`).
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub detector: String,
+    pub severity: String,
+    pub message: String,
+    pub file: Option<String>,
+    pub start_line: Option<u32>,
+    pub end_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+/// Maps a SARIF artifact URI from the sandbox to a workspace-relative path.
+pub fn workspace_relative_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    path.strip_prefix(SANDBOX_PROJECT_DIR)
+        .or_else(|| path.strip_prefix("./"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+fn rule_for<'a>(run: &'a Value, rule_id: &str) -> Option<&'a Value> {
+    run.pointer("/tool/driver/rules")?
+        .as_array()?
+        .iter()
+        .find(|rule| rule.get("id").and_then(Value::as_str) == Some(rule_id))
+}
+
+fn text_at<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .filter(|text| !text.trim().is_empty())
+}
+
+fn number_at(value: &Value, pointer: &str) -> Option<u32> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_u64)
+        .map(|number| number as u32)
+}
+
+/// Parses the SARIF report written by `cargo scout-audit --output-format sarif`.
+pub fn parse_sarif(raw: &str) -> Result<Vec<Finding>, String> {
+    let report: Value =
+        serde_json::from_str(raw).map_err(|e| format!("Invalid Scout SARIF report: {}", e))?;
+    let runs = report
+        .get("runs")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "Scout SARIF report has no runs".to_string())?;
+
+    let mut findings = Vec::new();
+    for run in runs {
+        let Some(results) = run.get("results").and_then(Value::as_array) else {
+            continue;
+        };
+        for result in results {
+            findings.push(finding_from_result(run, result));
+        }
+    }
+    Ok(findings)
+}
+
+fn finding_from_result(run: &Value, result: &Value) -> Finding {
+    let detector = text_at(result, "/ruleId").unwrap_or("unknown").to_string();
+    let rule = rule_for(run, &detector);
+    let severity = text_at(result, "/properties/severity")
+        .or_else(|| rule.and_then(|rule| text_at(rule, "/properties/severity")))
+        .or_else(|| text_at(result, "/level"))
+        .or_else(|| rule.and_then(|rule| text_at(rule, "/defaultConfiguration/level")))
+        .unwrap_or("warning")
+        .to_ascii_lowercase();
+    let message = text_at(result, "/message/text")
+        .or_else(|| rule.and_then(|rule| text_at(rule, "/shortDescription/text")))
+        .unwrap_or_default()
+        .to_string();
+    let remediation = rule
+        .and_then(|rule| text_at(rule, "/helpUri"))
+        .map(str::to_string);
+
+    let location = result.pointer("/locations/0/physicalLocation");
+    let file = location
+        .and_then(|location| text_at(location, "/artifactLocation/uri"))
+        .map(workspace_relative_path);
+    let region = location.and_then(|location| location.get("region"));
+    let start_line = region.and_then(|region| number_at(region, "/startLine"));
+
+    Finding {
+        detector,
+        severity,
+        message,
+        file,
+        start_line,
+        end_line: region
+            .and_then(|region| number_at(region, "/endLine"))
+            .or(start_line),
+        start_column: region.and_then(|region| number_at(region, "/startColumn")),
+        end_column: region.and_then(|region| number_at(region, "/endColumn")),
+        remediation,
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuppressionSource {
+    /// A `// scout-ignore: <detector>` comment.
+    Inline,
+    /// An entry in `scout.toml`.
+    Baseline,
+}
+
+/// An acknowledged finding. Unset `file` and `line` match any location.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Suppression {
+    pub detector: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub source: SuppressionSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Suppression {
+    fn matches(&self, finding: &Finding) -> bool {
+        if self.detector != finding.detector {
+            return false;
+        }
+        if self.file.is_some() && self.file != finding.file {
+            return false;
+        }
+        match (self.line, finding.start_line) {
+            (None, _) => true,
+            (Some(line), Some(start)) => {
+                (start..=finding.end_line.unwrap_or(start)).contains(&line)
+            }
+            (Some(_), None) => false,
+        }
+    }
+
+    fn sarif_kind(&self) -> &'static str {
+        match self.source {
+            SuppressionSource::Inline => "inSource",
+            SuppressionSource::Baseline => "external",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SuppressionsFile {
+    #[serde(default)]
+    baseline: Vec<BaselineEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BaselineEntry {
+    detector: String,
+    file: Option<String>,
+    line: Option<u32>,
+    reason: Option<String>,
+}
+
+/// Gathers the inline `// scout-ignore:` comments of every Rust source and
+/// the `scout.toml` baseline. A comment on its own line covers the next line;
+/// a trailing comment covers its own line.
+///
+/// ```toml
+/// [[baseline]]
+/// detector = "unsafe-unwrap"
+/// file = "src/lib.rs"  # optional
+/// line = 12            # optional
+/// reason = "checked above"
+/// ```
+pub fn collect_suppressions(files: &HashMap<String, String>) -> Result<Vec<Suppression>, String> {
+    let mut suppressions = Vec::new();
+
+    let mut paths: Vec<_> = files.keys().collect();
+    paths.sort();
+    for path in paths {
+        if !path.ends_with(".rs") {
+            continue;
+        }
+        let file = legacy_workspace_path(path, files);
+        for (index, line) in files[path].lines().enumerate() {
+            let Some(at) = line.find(INLINE_MARKER) else {
+                continue;
+            };
+            let own_line = line[..at].trim().is_empty();
+            let target = index as u32 + if own_line { 2 } else { 1 };
+            for detector in line[at + INLINE_MARKER.len()..]
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|detector| !detector.is_empty())
+            {
+                suppressions.push(Suppression {
+                    detector: detector.to_string(),
+                    file: Some(file.clone()),
+                    line: Some(target),
+                    source: SuppressionSource::Inline,
+                    reason: None,
+                });
+            }
+        }
+    }
+
+    if let Some(raw) = files.get(SUPPRESSIONS_FILE) {
+        let baseline: SuppressionsFile =
+            toml::from_str(raw).map_err(|e| format!("Invalid {}: {}", SUPPRESSIONS_FILE, e))?;
+        suppressions.extend(baseline.baseline.into_iter().map(|entry| Suppression {
+            detector: entry.detector,
+            file: entry.file,
+            line: entry.line,
+            source: SuppressionSource::Baseline,
+            reason: entry.reason,
+        }));
+    }
+    Ok(suppressions)
+}
+
+/// Findings of one audit split against the workspace suppressions.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct AuditReport {
+    /// Findings not covered by any suppression.
+    pub new: Vec<Finding>,
+    /// Findings acknowledged by an inline comment or the baseline.
+    pub suppressed: Vec<Finding>,
+    /// Suppressions that no longer match any finding.
+    pub fixed: Vec<Suppression>,
+}
+
+pub fn apply_suppressions(findings: Vec<Finding>, suppressions: &[Suppression]) -> AuditReport {
+    let mut used = vec![false; suppressions.len()];
+    let mut report = AuditReport::default();
+    for finding in findings {
+        let mut suppressed = false;
+        for (index, suppression) in suppressions.iter().enumerate() {
+            if suppression.matches(&finding) {
+                used[index] = true;
+                suppressed = true;
+            }
+        }
+        if suppressed {
+            report.suppressed.push(finding);
+        } else {
+            report.new.push(finding);
+        }
+    }
+    report.fixed = suppressions
+        .iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(suppression, _)| suppression.clone())
+        .collect();
+    report
+}
+
+/// Rewrites a Scout SARIF report for export: every artifact URI is mapped to
+/// its workspace-relative path and anchored at `%SRCROOT%`, and sandbox paths
+/// quoted in messages are stripped, so nothing points into the sandbox.
+/// Suppressed results carry a SARIF `suppressions` entry.
+pub fn export_sarif(raw: &str, suppressions: &[Suppression]) -> Result<Value, String> {
+    let mut report: Value =
+        serde_json::from_str(raw).map_err(|e| format!("Invalid Scout SARIF report: {}", e))?;
+    let Some(object) = report.as_object_mut() else {
+        return Err("Scout SARIF report is not an object".to_string());
+    };
+    if !object.get("runs").is_some_and(Value::is_array) {
+        return Err("Scout SARIF report has no runs".to_string());
+    }
+    object.insert("version".to_string(), Value::from(SARIF_VERSION));
+    object.insert("$schema".to_string(), Value::from(SARIF_SCHEMA));
+    rewrite_artifact_locations(&mut report);
+    mark_suppressed_results(&mut report, suppressions);
+    Ok(report)
+}
+
+fn mark_suppressed_results(report: &mut Value, suppressions: &[Suppression]) {
+    let Some(runs) = report.get_mut("runs").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for run in runs {
+        let run_view = run.clone();
+        let Some(results) = run.get_mut("results").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for result in results {
+            let finding = finding_from_result(&run_view, result);
+            let entries: Vec<Value> = suppressions
+                .iter()
+                .filter(|suppression| suppression.matches(&finding))
+                .map(|suppression| match &suppression.reason {
+                    Some(reason) => {
+                        json!({ "kind": suppression.sarif_kind(), "justification": reason })
+                    }
+                    None => json!({ "kind": suppression.sarif_kind() }),
+                })
+                .collect();
+            if let (false, Some(result)) = (entries.is_empty(), result.as_object_mut()) {
+                result.insert("suppressions".to_string(), Value::from(entries));
+            }
+        }
+    }
+}
+
+fn rewrite_artifact_locations(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(uri)) = object.get("uri") {
+                if !uri.starts_with("http://") && !uri.starts_with("https://") {
+                    let relative = workspace_relative_path(uri);
+                    object.insert("uri".to_string(), Value::from(relative));
+                    object.insert("uriBaseId".to_string(), Value::from("%SRCROOT%"));
+                }
+            }
+            object
+                .iter_mut()
+                .filter(|(key, _)| key.as_str() != "uri")
+                .for_each(|(_, child)| rewrite_artifact_locations(child));
+        }
+        Value::Array(items) => items.iter_mut().for_each(rewrite_artifact_locations),
+        // Messages and snippets can quote sandbox paths too.
+        Value::String(text) if text.contains(SANDBOX_PROJECT_DIR) => {
+            *text = text.replace(SANDBOX_PROJECT_DIR, "");
+        }
+        _ => {}
+    }
+}
+
+/// Reads the raw SARIF report Scout left in a job workspace.
+pub fn read_report(workspace: &Path) -> Result<String, String> {
+    fs::read_to_string(workspace.join(AUDIT_REPORT_FILE))
+        .map_err(|e| format!("Scout did not produce a report: {}", e))
+}
+
+/// Reads the report Scout left in a job workspace.
+pub fn read_findings(workspace: &Path) -> Result<Vec<Finding>, String> {
+    parse_sarif(&read_report(workspace)?)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// Plain-text summary of an audit for the text stream and MCP output.
+pub fn render_report(report: &AuditReport) -> String {
+    let mut text = if report.new.is_empty() {
+        "\nScout found no new issues.\n".to_string()
+    } else {
+        format!("\nScout found {}:\n", plural(report.new.len(), "new issue"))
+    };
+    text.push_str(&render_findings(&report.new));
+
+    if !report.suppressed.is_empty() {
+        text.push_str(&format!(
+            "\n{}:\n",
+            plural(report.suppressed.len(), "suppressed finding")
+        ));
+        text.push_str(&render_findings(&report.suppressed));
+    }
+    if !report.fixed.is_empty() {
+        text.push_str(&format!(
+            "\n{} no longer reported:\n",
+            plural(report.fixed.len(), "suppression")
+        ));
+        for suppression in &report.fixed {
+            text.push_str(&format!("  {}", suppression.detector));
+            if let Some(file) = &suppression.file {
+                text.push_str(&format!(" in {}", file));
+                if let Some(line) = suppression.line {
+                    text.push_str(&format!(":{}", line));
+                }
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+fn render_findings(findings: &[Finding]) -> String {
+    let mut text = String::new();
+    for finding in findings {
+        text.push_str(&format!(
+            "\n[{}] {}: {}\n",
+            finding.severity, finding.detector, finding.message
+        ));
+        if let Some(file) = &finding.file {
+            text.push_str(&format!("  --> {}", file));
+            if let Some(line) = finding.start_line {
+                text.push_str(&format!(":{}", line));
+                if let Some(column) = finding.start_column {
+                    text.push_str(&format!(":{}", column));
+                }
+            }
+            text.push('\n');
+        }
+        if let Some(remediation) = &finding.remediation {
+            text.push_str(&format!("  Remediation: {}\n", remediation));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"{
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": "scout", "rules": [{
+                "id": "unsafe-unwrap",
+                "shortDescription": { "text": "Unsafe usage of `unwrap`" },
+                "helpUri": "https://example.com/detectors/unsafe-unwrap",
+                "properties": { "severity": "Medium" }
+            }] } },
+            "results": [{
+                "ruleId": "unsafe-unwrap",
+                "level": "warning",
+                "message": { "text": "Unsafe usage of `unwrap`" },
+                "locations": [{ "physicalLocation": {
+                    "artifactLocation": { "uri": "/workspace/project/src/lib.rs" },
+                    "region": { "startLine": 12, "startColumn": 9, "endLine": 12, "endColumn": 30 }
+                } }]
+            }]
+        }]
+    }"#;
+
+    #[test]
+    fn parses_sarif_findings() {
+        let findings = parse_sarif(REPORT).unwrap();
+        assert_eq!(
+            findings,
+            vec![Finding {
+                detector: "unsafe-unwrap".to_string(),
+                severity: "medium".to_string(),
+                message: "Unsafe usage of `unwrap`".to_string(),
+                file: Some("src/lib.rs".to_string()),
+                start_line: Some(12),
+                end_line: Some(12),
+                start_column: Some(9),
+                end_column: Some(30),
+                remediation: Some("https://example.com/detectors/unsafe-unwrap".to_string()),
+            }]
+        );
+        assert!(render_findings(&findings).contains("  --> src/lib.rs:12:9\n"));
+    }
+
+    #[test]
+    fn exported_sarif_has_no_sandbox_paths() {
+        let report = export_sarif(REPORT, &[]).unwrap();
+        let location = &report["runs"][0]["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/lib.rs");
+        assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+        assert_eq!(report["version"], "2.1.0");
+        assert!(!report.to_string().contains("/workspace/project"));
+        assert_eq!(
+            report["runs"][0]["tool"]["driver"]["rules"][0]["helpUri"],
+            "https://example.com/detectors/unsafe-unwrap"
+        );
+    }
+
+    #[test]
+    fn splits_new_suppressed_and_fixed_findings() {
+        let files = HashMap::from([
+            (
+                "src/lib.rs".to_string(),
+                "fn a() {}\n// scout-ignore: unsafe-unwrap\nfn b() {}\n".to_string(),
+            ),
+            (
+                SUPPRESSIONS_FILE.to_string(),
+                "[[baseline]]\ndetector = \"unsafe-expect\"\nreason = \"reviewed\"\n".to_string(),
+            ),
+        ]);
+        let suppressions = collect_suppressions(&files).unwrap();
+        assert_eq!(suppressions[0].line, Some(3));
+
+        let finding = |detector: &str, line: u32| Finding {
+            detector: detector.to_string(),
+            severity: "medium".to_string(),
+            message: String::new(),
+            file: Some("src/lib.rs".to_string()),
+            start_line: Some(line),
+            end_line: Some(line),
+            start_column: None,
+            end_column: None,
+            remediation: None,
+        };
+        let report = apply_suppressions(
+            vec![finding("unsafe-unwrap", 3), finding("unsafe-unwrap", 9)],
+            &suppressions,
+        );
+        assert_eq!(report.suppressed, vec![finding("unsafe-unwrap", 3)]);
+        assert_eq!(report.new, vec![finding("unsafe-unwrap", 9)]);
+        assert_eq!(report.fixed.len(), 1);
+        assert_eq!(report.fixed[0].source, SuppressionSource::Baseline);
+
+        let exported = export_sarif(REPORT, &suppressions).unwrap();
+        assert!(exported["runs"][0]["results"][0]
+            .get("suppressions")
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_baseline_fields() {
+        let files = HashMap::from([(
+            SUPPRESSIONS_FILE.to_string(),
+            "[[baseline]]\ndetektor = \"unsafe-unwrap\"\n".to_string(),
+        )]);
+        assert!(collect_suppressions(&files).is_err());
+    }
+
+    #[test]
+    fn maps_sandbox_paths_to_workspace_paths() {
+        assert_eq!(
+            workspace_relative_path("file:///workspace/project/src/lib.rs"),
+            "src/lib.rs"
+        );
+        assert_eq!(workspace_relative_path("./src/test.rs"), "src/test.rs");
+        assert_eq!(workspace_relative_path("src/lib.rs"), "src/lib.rs");
+    }
+}
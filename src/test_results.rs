@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// Outcome of a single libtest test case.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TestRecord {
+    pub name: String,
+    pub module: Option<String>,
+    pub status: TestStatus,
+    pub duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panic: Option<PanicInfo>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PanicInfo {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct LibtestEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    name: Option<String>,
+    exec_time: Option<f64>,
+    stdout: Option<String>,
+    test_count: Option<u64>,
+    passed: Option<u64>,
+    failed: Option<u64>,
+    ignored: Option<u64>,
+    measured: Option<u64>,
+    filtered_out: Option<u64>,
+}
+
+/// Collects per-test records from `cargo test -- --format json` output and
+/// renders each event back into the familiar libtest text.
+#[derive(Default)]
+pub struct TestReport {
+    records: Vec<TestRecord>,
+    suite_failures: Vec<usize>,
+}
+
+impl TestReport {
+    /// Consumes one output line. Returns the human-readable rendering when the
+    /// line was a libtest JSON event, or `None` for any other output.
+    pub fn consume_line(&mut self, line: &str) -> Option<String> {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') || !trimmed.contains("\"type\"") {
+            return None;
+        }
+        let event = serde_json::from_str::<LibtestEvent>(trimmed).ok()?;
+        match (event.kind.as_str(), event.event.as_str()) {
+            ("suite", "started") => {
+                self.suite_failures.clear();
+                let count = event.test_count.unwrap_or(0);
+                Some(format!(
+                    "\nrunning {} test{}\n",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ))
+            }
+            ("suite", outcome) => Some(self.render_suite_end(outcome, &event)),
+            ("test", "started") => Some(String::new()),
+            ("test", outcome) => {
+                let status = match outcome {
+                    "ok" => TestStatus::Passed,
+                    "failed" => TestStatus::Failed,
+                    "ignored" => TestStatus::Ignored,
+                    _ => return Some(String::new()),
+                };
+                let name = event.name.unwrap_or_default();
+                let stdout = event.stdout.filter(|stdout| !stdout.is_empty());
+                let panic = match status {
+                    TestStatus::Failed => stdout.as_deref().and_then(parse_panic),
+                    _ => None,
+                };
+                let duration_ms = event.exec_time.map(|secs| (secs * 1000.0).round() as u64);
+                let rendered = format!(
+                    "test {} ... {}{}\n",
+                    name,
+                    match status {
+                        TestStatus::Passed => "ok",
+                        TestStatus::Failed => "FAILED",
+                        TestStatus::Ignored => "ignored",
+                    },
+                    event
+                        .exec_time
+                        .map(|secs| format!(" <{:.3}s>", secs))
+                        .unwrap_or_default()
+                );
+                if status == TestStatus::Failed {
+                    self.suite_failures.push(self.records.len());
+                }
+                self.records.push(TestRecord {
+                    module: name.rsplit_once("::").map(|(module, _)| module.to_string()),
+                    name,
+                    status,
+                    duration_ms,
+                    stdout,
+                    panic,
+                });
+                Some(rendered)
+            }
+            _ => Some(String::new()),
+        }
+    }
+
+    fn render_suite_end(&mut self, outcome: &str, event: &LibtestEvent) -> String {
+        let mut text = String::new();
+        if !self.suite_failures.is_empty() {
+            text.push_str("\nfailures:\n\n");
+            for index in &self.suite_failures {
+                let record = &self.records[*index];
+                text.push_str(&format!("---- {} stdout ----\n", record.name));
+                text.push_str(record.stdout.as_deref().unwrap_or_default());
+                text.push('\n');
+            }
+            text.push_str("\nfailures:\n");
+            for index in &self.suite_failures {
+                text.push_str(&format!("    {}\n", self.records[*index].name));
+            }
+        }
+        text.push_str(&format!(
+            "\ntest result: {}. {} passed; {} failed; {} ignored; {} measured; {} filtered out{}\n\n",
+            if outcome == "ok" { "ok" } else { "FAILED" },
+            event.passed.unwrap_or(0),
+            event.failed.unwrap_or(0),
+            event.ignored.unwrap_or(0),
+            event.measured.unwrap_or(0),
+            event.filtered_out.unwrap_or(0),
+            event
+                .exec_time
+                .map(|secs| format!("; finished in {:.2}s", secs))
+                .unwrap_or_default()
+        ));
+        self.suite_failures.clear();
+        text
+    }
+
+    pub fn into_records(self) -> Vec<TestRecord> {
+        self.records
+    }
+}
+
+/// Extracts the panic message and location from a failed test's captured
+/// output, e.g. `thread 'test::x' panicked at src/test.rs:20:5:\nmessage`.
+fn parse_panic(stdout: &str) -> Option<PanicInfo> {
+    let start = stdout.find("panicked at ")?;
+    let rest = &stdout[start + "panicked at ".len()..];
+
+    // Older toolchains print `panicked at 'message', src/test.rs:20:5`.
+    if let Some(quoted) = rest.strip_prefix('\'') {
+        let (message, location) = quoted.split_once("', ")?;
+        let location = location.lines().next().unwrap_or_default();
+        return Some(with_location(message.to_string(), location));
+    }
+
+    let (location, message) = rest.split_once(":\n").unwrap_or((rest.trim_end(), ""));
+    let message = message
+        .lines()
+        .take_while(|line| !line.starts_with("note: run with `RUST_BACKTRACE"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(with_location(message.trim().to_string(), location))
+}
+
+fn with_location(message: String, location: &str) -> PanicInfo {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let column = parts.next().and_then(|value| value.parse().ok());
+    let line = parts.next().and_then(|value| value.parse().ok());
+    let file = parts.next().map(str::to_string);
+    match (file, line, column) {
+        (Some(file), Some(line), Some(column)) => PanicInfo {
+            message,
+            file: Some(file),
+            line: Some(line),
+            column: Some(column),
+        },
+        _ => PanicInfo {
+            message,
+            file: None,
+            line: None,
+            column: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replaces libtest JSON events in buffered output with their text rendering
+    /// and returns the parsed records.
+    fn split_test_output(output: &str) -> (String, Vec<TestRecord>) {
+        let mut report = TestReport::default();
+        let mut text = String::with_capacity(output.len());
+        for line in output.split_inclusive('\n') {
+            match report.consume_line(line) {
+                Some(rendered) => text.push_str(&rendered),
+                None => text.push_str(line),
+            }
+        }
+        (text, report.into_records())
+    }
+
+    const OUTPUT: &str = r#"     Running unittests src/lib.rs (target/debug/deps/hello-1234)
+{ "type": "suite", "event": "started", "test_count": 2 }
+{ "type": "test", "event": "started", "name": "test::test_hello" }
+{ "type": "test", "event": "started", "name": "test::test_overflow" }
+{ "type": "test", "name": "test::test_hello", "event": "ok", "exec_time": 0.012 }
+{ "type": "test", "name": "test::test_overflow", "event": "failed", "exec_time": 0.004, "stdout": "\nthread 'test::test_overflow' panicked at src/test.rs:21:5:\nassertion `left == right` failed\n  left: 1\n right: 2\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "ignored": 0, "measured": 0, "filtered_out": 0, "exec_time": 0.02 }
+"#;
+
+    #[test]
+    fn collects_per_test_records() {
+        let (text, records) = split_test_output(OUTPUT);
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].name, "test::test_hello");
+        assert_eq!(records[0].module.as_deref(), Some("test"));
+        assert_eq!(records[0].status, TestStatus::Passed);
+        assert_eq!(records[0].duration_ms, Some(12));
+
+        let panic = records[1].panic.as_ref().expect("panic info");
+        assert_eq!(records[1].status, TestStatus::Failed);
+        assert_eq!(panic.file.as_deref(), Some("src/test.rs"));
+        assert_eq!((panic.line, panic.column), (Some(21), Some(5)));
+        assert_eq!(
+            panic.message,
+            "assertion `left == right` failed\n  left: 1\n right: 2"
+        );
+
+        assert!(text.contains("test test::test_hello ... ok <0.012s>\n"));
+        assert!(text.contains("---- test::test_overflow stdout ----\n"));
+        assert!(text.contains("test result: FAILED. 1 passed; 1 failed;"));
+        assert!(!text.contains("\"type\""));
+    }
+
+    #[test]
+    fn parses_legacy_panic_format() {
+        let panic = parse_panic(
+            "thread 'x' panicked at 'HostError: Error(Contract, #1)', src/lib.rs:9:13\n",
+        )
+        .unwrap();
+        assert_eq!(panic.message, "HostError: Error(Contract, #1)");
+        assert_eq!(panic.file.as_deref(), Some("src/lib.rs"));
+        assert_eq!(panic.line, Some(9));
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeSet, HashMap};
+
+use toml::Value;
+
+/// A contract crate of a Cargo workspace project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkspaceContract {
+    /// Package name from the member's `Cargo.toml`.
+    pub package: String,
+    /// Member directory relative to the project root.
+    pub dir: String,
+}
+
+impl WorkspaceContract {
+    /// File stem cargo gives the member's WASM.
+    pub fn wasm_name(&self) -> String {
+        self.package.replace('-', "_")
+    }
+
+    /// Host filename of the member's WASM, named like single-crate outputs.
+    pub fn output_filename(&self, build_id: Option<&str>) -> String {
+        match build_id {
+            Some(id) => format!("{}-{}.wasm", self.package, &id[..8]),
+            None => format!("{}.wasm", self.package),
+        }
+    }
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|item| item.trim_end_matches('/').to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Lists the contract members, crates with `crate-type = ["cdylib"]`, of a
+/// project whose root `Cargo.toml` is a virtual workspace manifest. Returns
+/// `None` for projects with a root package, which build as a single crate.
+///
+/// Members are exact paths or `dir/*` globs, expanded against the submitted
+/// files.
+pub fn workspace_contracts(
+    files: &HashMap<String, String>,
+) -> Result<Option<Vec<WorkspaceContract>>, String> {
+    let Some(root) = files.get("Cargo.toml") else {
+        return Ok(None);
+    };
+    let manifest: Value = toml::from_str(root).map_err(|e| format!("Invalid Cargo.toml: {}", e))?;
+    let Some(workspace) = manifest.get("workspace") else {
+        return Ok(None);
+    };
+    if manifest.get("package").is_some() {
+        return Ok(None);
+    }
+    let exclude = string_list(workspace.get("exclude"));
+
+    let mut dirs = BTreeSet::new();
+    for member in string_list(workspace.get("members")) {
+        if let Some(parent) = member.strip_suffix("/*") {
+            let prefix = format!("{}/", parent);
+            for path in files.keys() {
+                let Some(name) = path
+                    .strip_prefix(&prefix)
+                    .and_then(|rest| rest.strip_suffix("/Cargo.toml"))
+                else {
+                    continue;
+                };
+                if !name.contains('/') {
+                    dirs.insert(format!("{}{}", prefix, name));
+                }
+            }
+        } else if member.contains(['*', '?', '[']) {
+            return Err(format!(
+                "Unsupported workspace member pattern: {}. Use exact paths or dir/*",
+                member
+            ));
+        } else {
+            dirs.insert(member);
+        }
+    }
+
+    let mut contracts = Vec::new();
+    for dir in dirs {
+        if exclude.contains(&dir) {
+            continue;
+        }
+        let manifest_path = format!("{}/Cargo.toml", dir);
+        let raw = files
+            .get(&manifest_path)
+            .ok_or_else(|| format!("Workspace member {} has no Cargo.toml", dir))?;
+        let member: Value =
+            toml::from_str(raw).map_err(|e| format!("Invalid {}: {}", manifest_path, e))?;
+        let package = member
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("{} has no package name", manifest_path))?;
+        // The name ends up in sandbox commands and filenames.
+        if package.is_empty()
+            || !package
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("Invalid package name in {}", manifest_path));
+        }
+        let is_contract = member
+            .get("lib")
+            .and_then(|lib| lib.get("crate-type"))
+            .and_then(Value::as_array)
+            .is_some_and(|types| types.iter().any(|kind| kind.as_str() == Some("cdylib")));
+        if is_contract {
+            contracts.push(WorkspaceContract {
+                package: package.to_string(),
+                dir,
+            });
+        }
+    }
+
+    if contracts.is_empty() {
+        return Err(
+            "The Cargo workspace has no contract members with crate-type = [\"cdylib\"]"
+                .to_string(),
+        );
+    }
+    Ok(Some(contracts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str, cdylib: bool) -> String {
+        let lib = if cdylib {
+            "[lib]\ncrate-type = [\"cdylib\"]\n"
+        } else {
+            ""
+        };
+        format!("[package]\nname = \"{}\"\n{}", name, lib)
+    }
+
+    #[test]
+    fn lists_contract_members() {
+        let files = HashMap::from([
+            (
+                "Cargo.toml".to_string(),
+                "[workspace]\nmembers = [\"contracts/*\", \"shared\"]\nexclude = [\"contracts/old\"]\n"
+                    .to_string(),
+            ),
+            ("contracts/token/Cargo.toml".to_string(), member("token", true)),
+            ("contracts/pool/Cargo.toml".to_string(), member("liquidity-pool", true)),
+            ("contracts/old/Cargo.toml".to_string(), member("old", true)),
+            ("contracts/pool/src/lib.rs".to_string(), String::new()),
+            ("shared/Cargo.toml".to_string(), member("shared", false)),
+        ]);
+
+        let contracts = workspace_contracts(&files).unwrap().unwrap();
+        assert_eq!(
+            contracts
+                .iter()
+                .map(|contract| contract.package.as_str())
+                .collect::<Vec<_>>(),
+            vec!["liquidity-pool", "token"]
+        );
+        assert_eq!(contracts[0].dir, "contracts/pool");
+        assert_eq!(contracts[0].wasm_name(), "liquidity_pool");
+        assert_eq!(
+            contracts[0].output_filename(Some("0123456789abcdef")),
+            "liquidity-pool-01234567.wasm"
+        );
+    }
+
+    #[test]
+    fn ignores_single_crates_and_rejects_bad_members() {
+        let single = HashMap::from([("Cargo.toml".to_string(), member("project", true))]);
+        assert_eq!(workspace_contracts(&single).unwrap(), None);
+
+        let missing = HashMap::from([(
+            "Cargo.toml".to_string(),
+            "[workspace]\nmembers = [\"token\"]\n".to_string(),
+        )]);
+        assert!(workspace_contracts(&missing).is_err());
+
+        let shell = HashMap::from([
+            (
+                "Cargo.toml".to_string(),
+                "[workspace]\nmembers = [\"token\"]\n".to_string(),
+            ),
+            ("token/Cargo.toml".to_string(), member("x;id", true)),
+        ]);
+        assert!(workspace_contracts(&shell).is_err());
+    }
+}